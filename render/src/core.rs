@@ -114,6 +114,19 @@ impl RenderCore {
         }
     }
 
+    /// Whether Vulkan can be loaded with a device able to present to a window
+    pub fn is_supported() -> bool {
+        let instance = match Instance::new(None, &vulkano_win::required_extensions(), None) {
+            Ok(instance) => instance,
+            Err(_) => return false,
+        };
+
+        // Bound so the device iterator is dropped before the instance it borrows
+        let found = PhysicalDevice::enumerate(&instance)
+            .any(|device| Self::check_device_extensions_supported(&device));
+        found
+    }
+
     fn check_validation_layer_support() -> bool {
         let layers: Vec<_> = layers_list().unwrap()
             .map(|l| l.name().to_owned())
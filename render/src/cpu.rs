@@ -0,0 +1,436 @@
+use std::mem;
+
+use glyph_brush::{
+    GlyphBrush,
+    GlyphBrushBuilder,
+    BrushAction,
+    BrushError,
+    Rectangle,
+};
+use softbuffer::GraphicsContext;
+use raw_window_handle::{
+    HasRawWindowHandle,
+    RawWindowHandle,
+};
+use winit_window_handle::{
+    HasRawWindowHandle as HasWinitWindowHandle,
+    RawWindowHandle as WinitWindowHandle,
+};
+use winit::window::{
+    Window,
+    WindowBuilder,
+};
+use winit::dpi::LogicalSize;
+use winit::event_loop::EventLoop;
+
+use display_list::{
+    DisplayList,
+    Quad,
+};
+use text::{
+    GlyphExtra,
+    TextVertex,
+    into_vertex,
+    font,
+};
+
+// Matches the clear colour of the primitive render pass
+const CLEAR_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// Rasterizes display lists on the CPU into a framebuffer of 0RGB pixels
+pub struct CpuRasterizer {
+    size: [usize; 2],
+    pixels: Vec<u32>,
+    glyph_brush: GlyphBrush<TextVertex, GlyphExtra>,
+    atlas: Vec<u8>,
+    atlas_size: [usize; 2],
+    vertices: Vec<TextVertex>,
+}
+
+impl CpuRasterizer {
+    pub fn new(size: [usize; 2]) -> Self {
+        let glyph_brush = GlyphBrushBuilder::using_font(font::default_font())
+            .cache_glyph_positioning(true)
+            .cache_redraws(true)
+            .build();
+        let atlas_size = glyph_brush.texture_dimensions();
+        let atlas_size = [atlas_size.0 as usize, atlas_size.1 as usize];
+
+        Self {
+            size,
+            pixels: vec![pack(CLEAR_COLOUR); size[0] * size[1]],
+            glyph_brush,
+            atlas: vec![0; atlas_size[0] * atlas_size[1]],
+            atlas_size,
+            vertices: vec![],
+        }
+    }
+
+    pub fn size(&self) -> [usize; 2] {
+        self.size
+    }
+
+    pub fn resize(&mut self, size: [usize; 2]) {
+        self.size = size;
+        self.pixels = vec![pack(CLEAR_COLOUR); size[0] * size[1]];
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// The framebuffer as RGBA bytes, row by row from the top left
+    pub fn rgba(&self) -> Vec<u8> {
+        self.pixels.iter()
+            .flat_map(|pixel| {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                vec![r, g, b, 255]
+            })
+            .collect()
+    }
+
+    /// Redraws the framebuffer a layer at a time, the glyph runs of a layer over its quads as they
+    /// are by the Vulkan backend
+    pub fn draw(&mut self, display_list: &DisplayList) {
+        let clear = pack(CLEAR_COLOUR);
+        for pixel in self.pixels.iter_mut() {
+            *pixel = clear;
+        }
+
+        let layers = display_list.flatten();
+        for layer in layers.iter() {
+            for run in layer.glyphs.iter() {
+                self.glyph_brush.queue(&run.to_section(layer.layer).to_borrowed());
+            }
+        }
+        self.process_glyphs();
+
+        let vertices = mem::replace(&mut self.vertices, vec![]);
+        for layer in layers.iter() {
+            for quad in layer.quads.iter() {
+                self.fill_quad(quad);
+            }
+            for vertex in vertices.iter().filter(|vertex| vertex.layer == layer.layer) {
+                self.blit_glyph(vertex);
+            }
+        }
+        self.vertices = vertices;
+    }
+
+    fn fill_quad(&mut self, quad: &Quad) {
+        let max = quad.rect.max();
+        let x0 = quad.rect.position[0].round().max(0.0) as usize;
+        let y0 = quad.rect.position[1].round().max(0.0) as usize;
+        let x1 = (max[0].round().max(0.0) as usize).min(self.size[0]);
+        let y1 = (max[1].round().max(0.0) as usize).min(self.size[1]);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                self.blend(x, y, quad.colour, 1.0);
+            }
+        }
+    }
+
+    fn process_glyphs(&mut self) {
+        let action = loop {
+            let result = {
+                let atlas = &mut self.atlas;
+                let atlas_width = self.atlas_size[0];
+                self.glyph_brush.process_queued(
+                    |rect, data| update_atlas(atlas, atlas_width, rect, data),
+                    into_vertex)
+            };
+            match result {
+                Ok(action) => break action,
+                Err(BrushError::TextureTooSmall { suggested }) => {
+                    self.atlas_size = [suggested.0 as usize, suggested.1 as usize];
+                    self.atlas = vec![0; self.atlas_size[0] * self.atlas_size[1]];
+                    self.glyph_brush.resize_texture(suggested.0, suggested.1);
+                },
+            }
+        };
+        if let BrushAction::Draw(vertices) = action {
+            self.vertices = vertices;
+        }
+    }
+
+    // Text vertices are flipped for the Vulkan pipeline, the top of the glyph is in right_bottom
+    fn blit_glyph(&mut self, vertex: &TextVertex) {
+        let (x0, y0) = (vertex.left_top[0], vertex.right_bottom[1]);
+        let (x1, y1) = (vertex.right_bottom[0], vertex.left_top[1]);
+        let (u0, v0) = (vertex.tex_left_top[0], vertex.tex_right_bottom[1]);
+        let (u1, v1) = (vertex.tex_right_bottom[0], vertex.tex_left_top[1]);
+        if x1 <= x0 || y1 <= y0 {
+            return;
+        }
+
+        let px0 = x0.floor().max(0.0) as usize;
+        let py0 = y0.floor().max(0.0) as usize;
+        let px1 = (x1.ceil().max(0.0) as usize).min(self.size[0]);
+        let py1 = (y1.ceil().max(0.0) as usize).min(self.size[1]);
+
+        for py in py0..py1 {
+            let fy = (py as f32 + 0.5 - y0) / (y1 - y0);
+            if fy < 0.0 || fy > 1.0 {
+                continue;
+            }
+            let ty = ((v0 + (v1 - v0) * fy) * self.atlas_size[1] as f32) as usize;
+            let ty = ty.min(self.atlas_size[1] - 1);
+
+            for px in px0..px1 {
+                let fx = (px as f32 + 0.5 - x0) / (x1 - x0);
+                if fx < 0.0 || fx > 1.0 {
+                    continue;
+                }
+                let tx = ((u0 + (u1 - u0) * fx) * self.atlas_size[0] as f32) as usize;
+                let tx = tx.min(self.atlas_size[0] - 1);
+
+                let coverage = self.atlas[ty * self.atlas_size[0] + tx] as f32 / 255.0;
+                if coverage > 0.0 {
+                    self.blend(px, py, vertex.colour, coverage);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn blend(&mut self, x: usize, y: usize, colour: [f32; 4], coverage: f32) {
+        let ix = y * self.size[0] + x;
+        let alpha = colour[3] * coverage;
+        let [_, r, g, b] = self.pixels[ix].to_be_bytes();
+        let dst = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0];
+
+        self.pixels[ix] = pack([
+            colour[0] * alpha + dst[0] * (1.0 - alpha),
+            colour[1] * alpha + dst[1] * (1.0 - alpha),
+            colour[2] * alpha + dst[2] * (1.0 - alpha),
+            1.0,
+        ]);
+    }
+}
+
+#[inline]
+fn pack(colour: [f32; 4]) -> u32 {
+    let channel = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u32;
+    (channel(colour[0]) << 16) | (channel(colour[1]) << 8) | channel(colour[2])
+}
+
+fn update_atlas(atlas: &mut Vec<u8>, atlas_width: usize, rect: Rectangle<u32>, data: &[u8]) {
+    let width = (rect.max[0] - rect.min[0]) as usize;
+    let height = (rect.max[1] - rect.min[1]) as usize;
+
+    for row in 0..height {
+        let dst = (rect.min[1] as usize + row) * atlas_width + rect.min[0] as usize;
+        atlas[dst..dst + width].copy_from_slice(&data[row * width..(row + 1) * width]);
+    }
+}
+
+/// A winit window as softbuffer sees it, winit 0.24 hands out raw-window-handle 0.3 handles
+/// while softbuffer only accepts 0.4 ones
+#[derive(Debug)]
+struct SurfaceWindow {
+    window: Window,
+    handle: RawWindowHandle,
+}
+
+impl SurfaceWindow {
+    fn new(window: Window) -> Result<Self, String> {
+        let handle = match window.raw_window_handle() {
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            WinitWindowHandle::Xlib(window) => {
+                let mut handle = raw_window_handle::XlibHandle::empty();
+                handle.window = window.window;
+                handle.display = window.display;
+                RawWindowHandle::Xlib(handle)
+            },
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            WinitWindowHandle::Wayland(window) => {
+                let mut handle = raw_window_handle::WaylandHandle::empty();
+                handle.surface = window.surface;
+                handle.display = window.display;
+                RawWindowHandle::Wayland(handle)
+            },
+            #[cfg(target_os = "windows")]
+            WinitWindowHandle::Windows(window) => {
+                let mut handle = raw_window_handle::Win32Handle::empty();
+                handle.hwnd = window.hwnd;
+                handle.hinstance = window.hinstance;
+                RawWindowHandle::Win32(handle)
+            },
+            #[cfg(target_os = "macos")]
+            WinitWindowHandle::MacOS(window) => {
+                let mut handle = raw_window_handle::AppKitHandle::empty();
+                handle.ns_window = window.ns_window;
+                handle.ns_view = window.ns_view;
+                RawWindowHandle::AppKit(handle)
+            },
+            handle => return Err(format!("Software rendering is not supported for window handle {:?}", handle)),
+        };
+
+        Ok(Self {
+            window,
+            handle,
+        })
+    }
+}
+
+unsafe impl HasRawWindowHandle for SurfaceWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.handle
+    }
+}
+
+/// Presents frames rasterized on the CPU to a window, for machines without a Vulkan device
+pub struct CpuRenderer {
+    context: GraphicsContext<SurfaceWindow>,
+    rasterizer: CpuRasterizer,
+}
+
+impl CpuRenderer {
+    pub fn new<L>(events_loop: &EventLoop<L>, title: &str) -> Result<Self, String> {
+        let window = WindowBuilder::new()
+            .with_title(title)
+            .with_resizable(true)
+            .with_inner_size(LogicalSize::new(1024.0, 768.0))
+            .build(events_loop)
+            .map_err(|err| format!("Unable to create window: {}", err))?;
+        let size: [u32; 2] = window.inner_size().into();
+
+        let context = unsafe { GraphicsContext::new(SurfaceWindow::new(window)?) }
+            .map_err(|err| format!("Unable to create software rendering surface: {}", err))?;
+
+        Ok(Self {
+            context,
+            rasterizer: CpuRasterizer::new([size[0] as usize, size[1] as usize]),
+        })
+    }
+
+    pub fn submit(&mut self, display_list: &DisplayList) {
+        self.resize_to_window();
+        self.rasterizer.draw(display_list);
+    }
+
+    pub fn draw_frame(&mut self) {
+        let size = self.rasterizer.size();
+        self.context.set_buffer(self.rasterizer.pixels(), size[0] as u16, size[1] as u16);
+    }
+
+    // The framebuffer is only resized when the next display list arrives, so a frame presented
+    // in between still matches the size of the pixels it holds
+    fn resize_to_window(&mut self) {
+        let size: [u32; 2] = self.context.window().window.inner_size().into();
+        let size = [size[0] as usize, size[1] as usize];
+        if size != self.rasterizer.size() {
+            self.rasterizer.resize(size);
+        }
+    }
+
+    pub fn get_screen_dimensions(&self) -> [f32; 2] {
+        self.context.window().window.inner_size().into()
+    }
+
+    pub fn request_redraw(&self) {
+        self.context.window().window.request_redraw();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use display_list::{
+        Align,
+        GlyphRun,
+        GlyphSpan,
+        Rect,
+    };
+
+    const FONT_SIZE: f32 = 20.0;
+
+    #[test]
+    fn cpu_rasterizer_fills_quads() {
+        let mut list = DisplayList::new(FONT_SIZE);
+        list.push_quad(Quad {
+            rect: Rect::new([2.0, 1.0], [3.0, 2.0]),
+            depth: 0.0,
+            colour: [1.0, 0.0, 0.0, 1.0],
+        });
+
+        let mut rasterizer = CpuRasterizer::new([8, 4]);
+        rasterizer.draw(&list);
+
+        let red = (0..4)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| rasterizer.pixels()[y * 8 + x] == 0xff0000)
+            .collect::<Vec<_>>();
+        assert_eq!(red, vec![(2, 1), (3, 1), (4, 1), (2, 2), (3, 2), (4, 2)]);
+    }
+
+    // A line of white text at the top left of the list
+    fn white_text(text: &str) -> GlyphRun {
+        GlyphRun {
+            position: [0.0, 0.0],
+            bounds: [f32::INFINITY, FONT_SIZE],
+            wrap: false,
+            h_align: Align::Start,
+            v_align: Align::Start,
+            spans: vec![GlyphSpan { text: text.to_string(), scale: FONT_SIZE, colour: [1.0; 4], depth: 0.0 }],
+            clip: None,
+        }
+    }
+
+    // Columns of the framebuffer with any pixel other than black or red
+    fn text_columns(rasterizer: &CpuRasterizer) -> Vec<usize> {
+        let [width, height] = rasterizer.size();
+        (0..width)
+            .filter(|&x| (0..height).any(|y| ![0x000000, 0xff0000].contains(&rasterizer.pixels()[y * width + x])))
+            .collect()
+    }
+
+    #[test]
+    fn cpu_rasterizer_draws_higher_layers_over_text() {
+        let mut list = DisplayList::new(FONT_SIZE);
+        list.push_glyphs(white_text("MMMM"));
+        list.push_layer(1);
+        list.push_quad(Quad {
+            rect: Rect::new([0.0, 0.0], [40.0, 30.0]),
+            depth: 0.0,
+            colour: [1.0, 0.0, 0.0, 1.0],
+        });
+        list.pop_layer();
+
+        let mut rasterizer = CpuRasterizer::new([80, 30]);
+        rasterizer.draw(&list);
+
+        let columns = text_columns(&rasterizer);
+        assert!(!columns.is_empty());
+        assert!(columns.iter().all(|&x| x >= 40));
+    }
+
+    #[test]
+    fn cpu_rasterizer_cuts_glyphs_off_at_the_clip_rect() {
+        let mut list = DisplayList::new(FONT_SIZE);
+        list.push_clip(Rect::new([10.0, 0.0], [20.0, 30.0]));
+        list.push_glyphs(white_text("MMMM"));
+        list.pop_clip();
+
+        let mut rasterizer = CpuRasterizer::new([80, 30]);
+        rasterizer.draw(&list);
+
+        let columns = text_columns(&rasterizer);
+        assert!(!columns.is_empty());
+        assert!(columns.iter().all(|&x| x >= 10 && x < 30));
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+
+use glyph_brush::{
+    Section,
+    OwnedSection,
+    Text,
+    Layout,
+    HorizontalAlign,
+    VerticalAlign,
+};
+use glyph_brush::ab_glyph::PxScale;
+
+use text::GlyphExtra;
+use text::font::{
+    self,
+    FontBounds,
+};
+
+/// An axis aligned rectangle in screen coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+}
+
+impl Rect {
+    pub fn new(position: [f32; 2], size: [f32; 2]) -> Self {
+        Self { position, size }
+    }
+
+    pub fn max(&self) -> [f32; 2] {
+        [self.position[0] + self.size[0], self.position[1] + self.size[1]]
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let (max, other_max) = (self.max(), other.max());
+        let position = [
+            self.position[0].max(other.position[0]),
+            self.position[1].max(other.position[1]),
+        ];
+        let size = [
+            (max[0].min(other_max[0]) - position[0]).max(0.0),
+            (max[1].min(other_max[1]) - position[1]).max(0.0),
+        ];
+
+        Rect { position, size }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size[0] <= 0.0 || self.size[1] <= 0.0
+    }
+}
+
+/// A solid coloured rectangle
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quad {
+    pub rect: Rect,
+    pub depth: f32,
+    pub colour: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+}
+
+/// A piece of a glyph run sharing one scale and colour
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphSpan {
+    pub text: String,
+    pub scale: f32,
+    pub colour: [f32; 4],
+    pub depth: f32,
+}
+
+/// Text laid out from a position within bounds, either on a single line or wrapped
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphRun {
+    pub position: [f32; 2],
+    pub bounds: [f32; 2],
+    pub wrap: bool,
+    pub h_align: Align,
+    pub v_align: Align,
+    pub spans: Vec<GlyphSpan>,
+    /// Glyphs are cut off outside of this rect, set from the clip rects it was pushed within
+    pub clip: Option<Rect>,
+}
+
+impl GlyphRun {
+    pub fn from_section(section: &Section) -> Self {
+        let (wrap, h_align, v_align) = match section.layout {
+            Layout::SingleLine { h_align, v_align, .. } => (false, h_align, v_align),
+            Layout::Wrap { h_align, v_align, .. } => (true, h_align, v_align),
+        };
+        let h_align = match h_align {
+            HorizontalAlign::Left => Align::Start,
+            HorizontalAlign::Center => Align::Center,
+            HorizontalAlign::Right => Align::End,
+        };
+        let v_align = match v_align {
+            VerticalAlign::Top => Align::Start,
+            VerticalAlign::Center => Align::Center,
+            VerticalAlign::Bottom => Align::End,
+        };
+
+        Self {
+            position: [section.screen_position.0, section.screen_position.1],
+            bounds: [section.bounds.0, section.bounds.1],
+            wrap,
+            h_align,
+            v_align,
+            spans: section.text.iter()
+                .map(|text| GlyphSpan {
+                    text: text.text.to_string(),
+                    scale: text.scale.y,
+                    colour: text.extra.color,
+                    depth: text.extra.z,
+                })
+                .collect(),
+            clip: None,
+        }
+    }
+
+    /// The glyph_brush section for backends which lay text out with glyph_brush, drawn in `layer`
+    pub fn to_section(&self, layer: u32) -> OwnedSection<GlyphExtra> {
+        let h_align = match self.h_align {
+            Align::Start => HorizontalAlign::Left,
+            Align::Center => HorizontalAlign::Center,
+            Align::End => HorizontalAlign::Right,
+        };
+        let v_align = match self.v_align {
+            Align::Start => VerticalAlign::Top,
+            Align::Center => VerticalAlign::Center,
+            Align::End => VerticalAlign::Bottom,
+        };
+        let layout = if self.wrap {
+            Layout::default_wrap()
+        } else {
+            Layout::default_single_line()
+        };
+        let clip = self.clip
+            .map(|clip| [clip.position[0], clip.position[1], clip.max()[0], clip.max()[1]])
+            .unwrap_or(GlyphExtra::default().clip);
+
+        Section::default()
+            .with_screen_position((self.position[0], self.position[1]))
+            .with_bounds((self.bounds[0], self.bounds[1]))
+            .with_layout(layout.h_align(h_align).v_align(v_align))
+            .with_text(self.spans.iter()
+                .map(|span| Text::<GlyphExtra>::new(span.text.as_str())
+                    .with_scale(PxScale::from(span.scale))
+                    .with_extra(GlyphExtra {
+                        colour: span.colour,
+                        z: span.depth,
+                        layer,
+                        clip,
+                    }))
+                .collect())
+            .to_owned()
+    }
+
+    // Runs lying wholly outside of the clip rect are dropped, the rest keep their layout and have
+    // their glyphs cut off by the backend
+    fn clip(mut self, clip: &Rect) -> Option<Self> {
+        let (min, max) = self.extent();
+        let clip_max = clip.max();
+        if min[0] >= clip_max[0] || max[0] <= clip.position[0]
+        || min[1] >= clip_max[1] || max[1] <= clip.position[1] {
+            return None;
+        }
+        self.clip = Some(self.clip.map(|own| own.intersect(clip)).unwrap_or(*clip));
+
+        Some(self)
+    }
+
+    // The area the run is laid out in, which the position anchors according to the alignment.
+    // Bounds may be infinite, so the corners are kept apart rather than made into a rect.
+    fn extent(&self) -> ([f32; 2], [f32; 2]) {
+        let mut min = [0.0; 2];
+        let mut max = [0.0; 2];
+        for (axis, align) in [self.h_align, self.v_align].iter().enumerate() {
+            let (position, bounds) = (self.position[axis], self.bounds[axis]);
+            let (low, high) = match align {
+                Align::Start => (position, position + bounds),
+                Align::Center => (position - bounds / 2.0, position + bounds / 2.0),
+                Align::End => (position - bounds, position),
+            };
+            min[axis] = low;
+            max[axis] = high;
+        }
+
+        (min, max)
+    }
+}
+
+/// The quads and glyph runs of one layer with clip rects applied, the glyphs are drawn over the
+/// quads
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layer {
+    pub layer: u32,
+    pub quads: Vec<Quad>,
+    pub glyphs: Vec<GlyphRun>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayItem {
+    Quad(Quad),
+    Glyphs(GlyphRun),
+    PushClip(Rect),
+    PopClip,
+}
+
+/// Everything to be drawn for a frame, built by widgets and consumed by a rendering backend
+///
+/// Items are drawn lowest layer first and in the order pushed within a layer, glyph runs are
+/// drawn over quads of the same layer. The list also measures text, so widgets can lay
+/// themselves out without access to a backend.
+pub struct DisplayList {
+    items: Vec<(u32, DisplayItem)>,
+    layers: Vec<u32>,
+    font_bounds: FontBounds,
+}
+
+impl DisplayList {
+    pub fn new(font_size: f32) -> Self {
+        Self {
+            items: vec![],
+            layers: vec![],
+            font_bounds: FontBounds::new(font::default_font(), font_size),
+        }
+    }
+
+    fn layer(&self) -> u32 {
+        self.layers.last().cloned().unwrap_or(0)
+    }
+
+    pub fn push_quad(&mut self, quad: Quad) {
+        let layer = self.layer();
+        self.items.push((layer, DisplayItem::Quad(quad)));
+    }
+
+    pub fn push_glyphs(&mut self, run: GlyphRun) {
+        let layer = self.layer();
+        self.items.push((layer, DisplayItem::Glyphs(run)));
+    }
+
+    pub fn push_section(&mut self, section: &Section) {
+        self.push_glyphs(GlyphRun::from_section(section));
+    }
+
+    /// Clips following items to `rect`, nested clips are intersected
+    pub fn push_clip(&mut self, rect: Rect) {
+        let layer = self.layer();
+        self.items.push((layer, DisplayItem::PushClip(rect)));
+    }
+
+    pub fn pop_clip(&mut self) {
+        let layer = self.layer();
+        self.items.push((layer, DisplayItem::PopClip));
+    }
+
+    /// Draws following items over everything on lower layers
+    pub fn push_layer(&mut self, layer: u32) {
+        self.layers.push(layer);
+    }
+
+    pub fn pop_layer(&mut self) {
+        self.layers.pop();
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.layers.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &DisplayItem> {
+        self.items.iter().map(|(_, item)| item)
+    }
+
+    /// The layers with anything to draw, lowest first, with the clip rects applied
+    pub fn flatten(&self) -> Vec<Layer> {
+        let mut clips: Vec<Vec<Rect>> = vec![];
+        let mut layers: BTreeMap<u32, Layer> = BTreeMap::new();
+
+        for (layer, item) in self.items.iter() {
+            let index = *layer as usize;
+            if clips.len() <= index {
+                clips.resize(index + 1, vec![]);
+            }
+            let clip = clips[index].last().cloned();
+
+            match item {
+                DisplayItem::PushClip(rect) => {
+                    clips[index].push(clip.map(|c| c.intersect(rect)).unwrap_or(*rect));
+                },
+                DisplayItem::PopClip => {
+                    clips[index].pop();
+                },
+                DisplayItem::Quad(quad) => {
+                    let rect = clip.map(|c| c.intersect(&quad.rect)).unwrap_or(quad.rect);
+                    if !rect.is_empty() {
+                        layers.entry(*layer).or_insert_with(|| Layer { layer: *layer, ..Layer::default() })
+                            .quads.push(Quad { rect, ..quad.clone() });
+                    }
+                },
+                DisplayItem::Glyphs(run) => {
+                    let run = match clip {
+                        Some(clip) => run.clone().clip(&clip),
+                        None => Some(run.clone()),
+                    };
+                    if let Some(run) = run {
+                        layers.entry(*layer).or_insert_with(|| Layer { layer: *layer, ..Layer::default() })
+                            .glyphs.push(run);
+                    }
+                },
+            }
+        }
+
+        layers.into_iter().map(|(_, layer)| layer).collect()
+    }
+
+    pub fn text_width(&self, text: &str) -> f32 {
+        text.chars()
+            .map(|ch| self.font_bounds.get_char_bounds(ch).max.x)
+            .sum()
+    }
+
+    /// Byte offset of the char boundary nearest to `x` along a single line of text
+    pub fn offset_at(&self, text: &str, x: f32) -> usize {
+        let mut left = 0.0;
+        for (i, ch) in text.char_indices() {
+            let width = self.font_bounds.get_char_bounds(ch).max.x;
+            if x < left + width / 2.0 {
+                return i;
+            }
+            left += width;
+        }
+
+        text.len()
+    }
+
+    pub fn font_size(&self) -> f32 {
+        self.font_bounds.get_scale()
+    }
+
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.font_bounds.set_scale(font_size);
+    }
+
+    /// Screen position of the character at byte `offset` within a single line section
+    pub fn cursor_position(&self, section: &Section, offset: usize) -> (f32, f32) {
+        let mut pos: (f32, f32) = section.screen_position;
+        if offset == 0 {
+            return pos;
+        }
+
+        let line_string: String = section.text.iter().flat_map(|t| t.text.chars()).collect();
+        for (i, ch) in line_string.char_indices() {
+            if offset <= i {
+                break;
+            }
+            let bounds = self.font_bounds.get_char_bounds(ch);
+
+            pos.0 += bounds.max.x;
+            if bounds.max.y > pos.1 {
+                pos.1 = bounds.max.y;
+            }
+        }
+
+        pos
+    }
+}
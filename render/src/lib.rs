@@ -2,10 +2,15 @@ extern crate vulkano;
 extern crate vulkano_win;
 extern crate winit;
 extern crate glyph_brush;
+extern crate softbuffer;
+extern crate raw_window_handle;
+extern crate winit_window_handle;
 
 mod core;
 
 pub mod text;
+pub mod display_list;
+pub mod cpu;
 pub mod primitive;
 pub mod uniform;
 
@@ -34,9 +39,106 @@ use winit::event_loop::EventLoop;
 
 use self::core::RenderCore;
 use self::text::TextContext;
-use self::primitive::PrimitiveContext;
+use self::primitive::{
+    Primitive,
+    PrimitiveContext,
+};
+use self::display_list::{
+    DisplayList,
+};
+use self::cpu::CpuRenderer;
+
+/// Which rendering backend draws the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// Vulkan when a device is available, otherwise the CPU rasterizer
+    Auto,
+    Vulkan,
+    Cpu,
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Backend::Auto),
+            "vulkan" => Some(Backend::Vulkan),
+            "cpu" => Some(Backend::Cpu),
+            _ => None,
+        }
+    }
+
+    /// The backend that will actually draw, Auto only picks Vulkan when a device supports it
+    pub fn resolve(self, vulkan_supported: bool) -> Self {
+        match self {
+            Backend::Auto if vulkan_supported => Backend::Vulkan,
+            Backend::Auto => Backend::Cpu,
+            backend => backend,
+        }
+    }
+}
+
+pub enum Renderer {
+    Vulkan(VulkanRenderer),
+    Cpu(CpuRenderer),
+}
+
+impl Renderer {
+    /// Opens the window with the backend, failing when the CPU backend cannot draw to it
+    pub fn new<L>(events_loop: &EventLoop<L>, title: &str, backend: Backend) -> Result<Self, String> {
+        let backend = match backend {
+            Backend::Auto => {
+                let resolved = backend.resolve(RenderCore::is_supported());
+                if resolved == Backend::Cpu {
+                    println!("No Vulkan device found, falling back to CPU rendering");
+                }
+                resolved
+            },
+            backend => backend,
+        };
+
+        match backend {
+            Backend::Cpu => CpuRenderer::new(events_loop, title).map(Renderer::Cpu),
+            _ => Ok(Renderer::Vulkan(VulkanRenderer::new(events_loop, title))),
+        }
+    }
+
+    /// Queues the quads and glyph runs of a display list for the next frame
+    pub fn submit(&mut self, display_list: &DisplayList) {
+        match self {
+            Renderer::Vulkan(renderer) => renderer.submit(display_list),
+            Renderer::Cpu(renderer) => renderer.submit(display_list),
+        }
+    }
+
+    pub fn draw_frame(&mut self) {
+        match self {
+            Renderer::Vulkan(renderer) => renderer.draw_frame(),
+            Renderer::Cpu(renderer) => renderer.draw_frame(),
+        }
+    }
 
-pub struct Renderer {
+    pub fn recreate_swap_chain_next_frame(&mut self) {
+        if let Renderer::Vulkan(renderer) = self {
+            renderer.recreate_swap_chain_next_frame();
+        }
+    }
+
+    pub fn get_screen_dimensions(&self) -> [f32; 2] {
+        match self {
+            Renderer::Vulkan(renderer) => renderer.get_screen_dimensions(),
+            Renderer::Cpu(renderer) => renderer.get_screen_dimensions(),
+        }
+    }
+
+    pub fn request_redraw(&self) {
+        match self {
+            Renderer::Vulkan(renderer) => renderer.request_redraw(),
+            Renderer::Cpu(renderer) => renderer.request_redraw(),
+        }
+    }
+}
+
+pub struct VulkanRenderer {
     core: RenderCore,
     render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
     swap_chain_frame_buffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
@@ -48,7 +150,7 @@ pub struct Renderer {
     primitive_context: Arc<RefCell<PrimitiveContext>>,
 }
 
-impl Renderer {
+impl VulkanRenderer {
     pub fn new<L>(events_loop: &EventLoop<L>, title: &str) -> Self {
         let core = RenderCore::new(events_loop, title);
         let render_pass = core.create_render_pass(None);
@@ -108,6 +210,29 @@ impl Renderer {
         self.primitive_context.clone()
     }
 
+    pub fn submit(&mut self, display_list: &DisplayList) {
+        let mut primitive_context = self.primitive_context.borrow_mut();
+        let mut text_context = self.text_context.borrow_mut();
+
+        // Quads from every widget share the queue, each is indexed by its place in the list
+        let mut quads = 0;
+        for layer in display_list.flatten() {
+            for quad in layer.quads.iter() {
+                primitive_context.queue_primitive(quads, Primitive {
+                    top_left: quad.rect.position,
+                    bottom_right: quad.rect.max(),
+                    depth: quad.depth,
+                    colour: quad.colour,
+                    layer: layer.layer,
+                });
+                quads += 1;
+            }
+            for run in layer.glyphs.iter() {
+                text_context.queue_text(&run.to_section(layer.layer).to_borrowed());
+            }
+        }
+    }
+
     pub fn draw_frame(&mut self) { 
         self.previous_frame_end.as_mut().unwrap().cleanup_finished();
 
@@ -207,13 +332,22 @@ impl Renderer {
                 self.core.get_graphics_queue().family()
         ).expect("unable to create AutoCommandBufferBuilder");
 
-        self.primitive_context
-            .borrow_mut()
-            .draw_primitives(&mut builder, image_index);
-
-        self.text_context
-            .borrow_mut()
-            .draw_text(&mut builder, image_index);
+        let mut primitive_context = self.primitive_context.borrow_mut();
+        let mut text_context = self.text_context.borrow_mut();
+        primitive_context.process_primitives();
+        text_context.process_text();
+
+        // Each layer is drawn over the ones below it, its text over its primitives
+        let mut layers = primitive_context.layers();
+        layers.extend(text_context.layers());
+        layers.sort();
+        layers.dedup();
+
+        primitive_context.clear(&mut builder, image_index);
+        for layer in layers {
+            primitive_context.draw_layer(&mut builder, image_index, layer);
+            text_context.draw_layer(&mut builder, image_index, layer);
+        }
             
         let command_buffer = builder.build()
             .expect("unable to build command buffer from builder");
@@ -234,3 +368,15 @@ impl Renderer {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_backend_falls_back_to_cpu_without_vulkan() {
+        assert_eq!(Backend::Auto.resolve(true), Backend::Vulkan);
+        assert_eq!(Backend::Auto.resolve(false), Backend::Cpu);
+        assert_eq!(Backend::Vulkan.resolve(false), Backend::Vulkan);
+        assert_eq!(Backend::Cpu.resolve(true), Backend::Cpu);
+    }
+}
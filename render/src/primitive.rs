@@ -1,5 +1,6 @@
 mod shaders;
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::iter;
 
@@ -8,6 +9,7 @@ use vulkano::{
         Device,
         Queue,
     },
+    format::ClearValue,
     pipeline::{
         GraphicsPipeline,
         viewport::Viewport,
@@ -55,6 +57,8 @@ pub struct Primitive {
     pub bottom_right: [f32; 2],
     pub depth: f32,
     pub colour: [f32; 4],
+    /// Display list layer the quad is drawn in
+    pub layer: u32,
 }
 
 pub struct PrimitiveContext {
@@ -63,11 +67,14 @@ pub struct PrimitiveContext {
     pipeline: Arc<GraphicsPipeline<SingleBufferDefinition<Vertex>, 
         Box<dyn PipelineLayoutAbstract + Send + Sync>, 
         Arc<dyn RenderPassAbstract + Send + Sync>>>,
+    // The first pass of a frame clears the image, later ones draw over what is there
+    clear_framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
     framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
     
     uniform_buffer_pool: CpuBufferPool<UniformTransform>,
     vertex_buffer: Option<Arc<dyn TypedBufferAccess<Content=[Vertex]> + Send + Sync>>,
-    index_buffer: Option<Arc<dyn TypedBufferAccess<Content=[u16]> + Send + Sync>>,
+    // One index buffer for the primitives of each layer, lowest layer first
+    index_buffers: Vec<(u32, Arc<ImmutableBuffer<[u16]>>)>,
 
     descriptor_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
     dimensions: [f32; 2],
@@ -91,7 +98,7 @@ impl PrimitiveContext {
         let fragment_shader = fragment_shader::Shader::load(device.clone())
             .expect("unable to load primitive fragment shader");
 
-        let render_pass = Arc::new(
+        let clear_render_pass = Arc::new(
             vulkano::single_pass_renderpass!(device.clone(),
                 attachments: {
                     color: {
@@ -107,13 +114,33 @@ impl PrimitiveContext {
                 }
             ).unwrap()) as Arc<dyn RenderPassAbstract + Send + Sync>;
 
-        let framebuffers = images.iter().map(|image| {
-            Arc::new(
-                Framebuffer::start(render_pass.clone())
-                .add(image.clone()).unwrap()
-                .build().unwrap()
-            ) as Arc<dyn FramebufferAbstract + Send + Sync>
-        }).collect::<Vec<_>>();
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: swapchain.format(),
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            ).unwrap()) as Arc<dyn RenderPassAbstract + Send + Sync>;
+
+        let create_framebuffers = |render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>| {
+            images.iter().map(|image| {
+                Arc::new(
+                    Framebuffer::start(render_pass.clone())
+                    .add(image.clone()).unwrap()
+                    .build().unwrap()
+                ) as Arc<dyn FramebufferAbstract + Send + Sync>
+            }).collect::<Vec<_>>()
+        };
+        let clear_framebuffers = create_framebuffers(&clear_render_pass);
+        let framebuffers = create_framebuffers(&render_pass);
 
         let pipeline = Arc::new(GraphicsPipeline::start()
             .vertex_input_single_buffer::<Vertex>()
@@ -140,11 +167,12 @@ impl PrimitiveContext {
             device: device.clone(),
             queue,
             pipeline,
+            clear_framebuffers,
             framebuffers,
             uniform_buffer_pool,
             primitives: Vec::new(),
             vertex_buffer: None,
-            index_buffer: None,
+            index_buffers: vec![],
             descriptor_set: None,
             dimensions: [0.0, 0.0],
             primitives_len: 0,
@@ -195,54 +223,24 @@ impl PrimitiveContext {
     }
 
     #[inline]
-    fn upload_buffers(&mut self, verts: Vec<Vertex>, indices: Vec<u16>) {
+    fn upload_buffers(&mut self, verts: Vec<Vertex>, layers: BTreeMap<u32, Vec<u16>>) {
         let (vertex_buffer, _future) = ImmutableBuffer::from_iter(
             verts.into_iter(),
             BufferUsage::vertex_buffer(),
             self.queue.clone(),
         ).expect("unable to create primitive vertex buffer");
 
-        let (index_buffer, _future) = ImmutableBuffer::from_iter(
-            indices.into_iter(),
-            BufferUsage::index_buffer(),
-            self.queue.clone(),
-        ).expect("unable to create primitive index buffer");
-
+        self.index_buffers = layers.into_iter()
+            .map(|(layer, indices)| {
+                let (index_buffer, _future) = ImmutableBuffer::from_iter(
+                    indices.into_iter(),
+                    BufferUsage::index_buffer(),
+                    self.queue.clone(),
+                ).expect("unable to create primitive index buffer");
+                (layer, index_buffer)
+            })
+            .collect();
         self.vertex_buffer = Some(Arc::new(vertex_buffer));
-        self.index_buffer = Some(Arc::new(index_buffer));
-    }
-
-    fn draw_internal<'a>(&'a mut self,
-        builder: &'a mut AutoCommandBufferBuilder,
-        image_num: usize,
-    ) {
-        self.check_recreate_descriptor_set(image_num);
-    
-        if self.vertex_buffer.is_none()
-        || self.index_buffer.is_none() 
-        || self.descriptor_set.is_none() {
-            return;
-        }
-
-        builder
-            .begin_render_pass(
-                self.framebuffers[image_num].clone(),
-                SubpassContents::Inline,
-                vec![[0.0, 0.0, 0.0, 1.0].into()],
-            ).expect("unable to begin primitive render pass");
-
-        builder.draw_indexed(
-            self.pipeline.clone(),
-            &DynamicState::none(),
-            self.vertex_buffer.clone().unwrap(),
-            self.index_buffer.clone().unwrap(),
-            self.descriptor_set.clone().unwrap(),
-            (),
-        ).expect("unable to draw to command buffer for primitive");
-
-        builder
-            .end_render_pass()
-            .expect("unable to end primitive render pass");
     }
 
     fn check_recreate_descriptor_set(&mut self, image_num: usize) {
@@ -270,23 +268,17 @@ impl PrimitiveContext {
         self.dimensions = dimensions;
     }
 
-    pub fn draw_primitives<'a>(&'a mut self, 
-        builder: &'a mut AutoCommandBufferBuilder,
-        image_num: usize,
-    ) -> bool {
+    /// Builds the buffers for the primitives queued for the frame, before its layers are drawn
+    pub fn process_primitives(&mut self) {
         if self.pristine {
-            self.draw_internal(builder, image_num);
-            return true;
+            return;
         }
 
         let len_prims = self.primitives.len();
-        if len_prims == 0 {
-            return true;
-        }
         self.primitives_len = len_prims;
 
         let mut verts: Vec<Vertex> = Vec::with_capacity(len_prims * 4);
-        let mut indices: Vec<u16> = Vec::with_capacity(len_prims * 6);
+        let mut layers: BTreeMap<u32, Vec<u16>> = BTreeMap::new();
         let mut i: u16 = 0;
 
         // process the Primitives to vertices and indices...
@@ -294,18 +286,78 @@ impl PrimitiveContext {
             let (prim_verts, prim_indices) = Self::primitive_to_buffer(i, prim);
 
             verts.extend(prim_verts.iter());
-            indices.extend(prim_indices.iter());
+            layers.entry(prim.layer).or_default().extend(prim_indices.iter());
             i += 1;
         }
 
-        self.upload_buffers(verts, indices); 
-
-        self.draw_internal(builder, image_num);
+        if verts.is_empty() {
+            self.vertex_buffer = None;
+            self.index_buffers.clear();
+        } else {
+            self.upload_buffers(verts, layers);
+        }
 
         self.primitives.clear();
         self.pristine = true;
+    }
 
-        true
+    /// Layers with primitives to draw, lowest first
+    pub fn layers(&self) -> Vec<u32> {
+        self.index_buffers.iter().map(|(layer, _)| *layer).collect()
     }
 
+    /// Clears the image, the first thing drawn each frame
+    pub fn clear(&mut self,
+        builder: &mut AutoCommandBufferBuilder,
+        image_num: usize,
+    ) {
+        builder
+            .begin_render_pass(
+                self.clear_framebuffers[image_num].clone(),
+                SubpassContents::Inline,
+                vec![[0.0, 0.0, 0.0, 1.0].into()],
+            ).expect("unable to begin primitive render pass")
+            .end_render_pass()
+            .expect("unable to end primitive render pass");
+    }
+
+    /// Draws the primitives of one layer, false if it has none
+    pub fn draw_layer<'a>(&'a mut self,
+        builder: &'a mut AutoCommandBufferBuilder,
+        image_num: usize,
+        layer: u32,
+    ) -> bool {
+        self.check_recreate_descriptor_set(image_num);
+
+        let index_buffer = self.index_buffers.iter()
+            .find(|(index_layer, _)| *index_layer == layer)
+            .map(|(_, index_buffer)| index_buffer.clone());
+        if self.vertex_buffer.is_none()
+        || index_buffer.is_none() 
+        || self.descriptor_set.is_none() {
+            return false;
+        }
+
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            ).expect("unable to begin primitive render pass");
+
+        builder.draw_indexed(
+            self.pipeline.clone(),
+            &DynamicState::none(),
+            self.vertex_buffer.clone().unwrap(),
+            index_buffer.unwrap(),
+            self.descriptor_set.clone().unwrap(),
+            (),
+        ).expect("unable to draw to command buffer for primitive");
+
+        builder
+            .end_render_pass()
+            .expect("unable to end primitive render pass");
+
+        true
+    }
 }
@@ -1,5 +1,5 @@
 mod shaders;
-mod font;
+pub(crate) mod font;
 mod unicode;
 
 use self::shaders::{
@@ -13,6 +13,11 @@ use uniform::{
 };
 
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::hash::{
+    Hash,
+    Hasher,
+};
 use std::sync::Arc;
 use std::iter;
 use vulkano::device::{
@@ -40,7 +45,6 @@ use vulkano::buffer::{
     CpuAccessibleBuffer,
     ImmutableBuffer,
     CpuBufferPool,
-    TypedBufferAccess,
 };
 use vulkano::swapchain::Swapchain;
 use vulkano::image::{
@@ -76,11 +80,9 @@ use glyph_brush::{
     Rectangle,
 };
 use glyph_brush::ab_glyph::{
-    FontArc,
     Rect,
     point,
 };
-use self::font::FontBounds;
 
 pub struct TextContext {
     device: Arc<Device>,
@@ -91,10 +93,10 @@ pub struct TextContext {
     framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
     uniform_buffer_pool: CpuBufferPool<UniformTransform>,
     vertex_buffer: Option<Arc<ImmutableBuffer<[Vertex]>>>,
-    index_buffer: Option<Arc<dyn TypedBufferAccess<Content=[u16]> + Send + Sync>>,
+    // One index buffer for the glyphs of each layer, lowest layer first
+    index_buffers: Vec<(u32, Arc<ImmutableBuffer<[u16]>>)>,
     
-    glyph_brush: RefCell<GlyphBrush<TextVertex>>,
-    font_bounds: FontBounds,
+    glyph_brush: RefCell<GlyphBrush<TextVertex, GlyphExtra>>,
 
     descriptor_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
     texture: TextureCache,
@@ -106,6 +108,7 @@ pub struct TextVertex {
     pub left_top: [f32; 2],
     pub right_bottom: [f32; 2],
     pub depth: f32,
+    pub layer: u32,
     pub tex_left_top: [f32; 2],
     pub tex_right_bottom: [f32; 2],
     pub colour: [f32; 4],
@@ -119,21 +122,67 @@ struct TextureCache {
     dirty: bool,
 }
 
+/// Per glyph data passed through glyph_brush to vertex generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphExtra {
+    pub colour: [f32; 4],
+    pub z: f32,
+    /// Display list layer the glyph is drawn in
+    pub layer: u32,
+    /// Glyphs are cut off outside of this rect, as min x, min y, max x, max y
+    pub clip: [f32; 4],
+}
+
+impl Default for GlyphExtra {
+    fn default() -> Self {
+        Self {
+            colour: [0.0, 0.0, 0.0, 1.0],
+            z: 0.0,
+            layer: 0,
+            clip: [f32::NEG_INFINITY, f32::NEG_INFINITY, f32::INFINITY, f32::INFINITY],
+        }
+    }
+}
+
+impl Hash for GlyphExtra {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in self.colour.iter().chain(iter::once(&self.z)).chain(self.clip.iter()) {
+            value.to_bits().hash(state);
+        }
+        self.layer.hash(state);
+    }
+}
+
 #[inline]
 pub fn into_vertex(GlyphVertex {
     mut tex_coords,
     pixel_coords,
     bounds,
     extra,
-}: GlyphVertex) -> TextVertex {
+}: GlyphVertex<GlyphExtra>) -> TextVertex {
    
-    let gl_bounds = bounds;
+    let gl_bounds = Rect {
+        min: point(bounds.min.x.max(extra.clip[0]), bounds.min.y.max(extra.clip[1])),
+        max: point(bounds.max.x.min(extra.clip[2]), bounds.max.y.min(extra.clip[3])),
+    };
 
     let mut gl_rect = Rect {
         min: point(pixel_coords.min.x as f32, pixel_coords.min.y as f32),
         max: point(pixel_coords.max.x as f32, pixel_coords.max.y as f32),
     };
 
+    // Glyphs wholly outside are left with nothing to draw
+    if gl_rect.min.x >= gl_bounds.max.x || gl_rect.max.x <= gl_bounds.min.x
+    || gl_rect.min.y >= gl_bounds.max.y || gl_rect.max.y <= gl_bounds.min.y {
+        return TextVertex {
+            left_top: [gl_rect.min.x, gl_rect.min.y],
+            right_bottom: [gl_rect.min.x, gl_rect.min.y],
+            depth: extra.z,
+            layer: extra.layer,
+            ..TextVertex::default()
+        };
+    }
+
     // handle overlapping bounds, modify uv_rect to preserve texture aspect
     if gl_rect.max.x > gl_bounds.max.x {
         let old_width = gl_rect.width();
@@ -160,14 +209,10 @@ pub fn into_vertex(GlyphVertex {
         left_top: [gl_rect.min.x, gl_rect.max.y],
         right_bottom: [gl_rect.max.x, gl_rect.min.y], 
         depth: extra.z,
+        layer: extra.layer,
         tex_left_top: [tex_coords.min.x, tex_coords.max.y],
         tex_right_bottom: [tex_coords.max.x, tex_coords.min.y],
-        colour: [
-            extra.color[0],
-            extra.color[1],
-            extra.color[2],
-            extra.color[3]
-        ],
+        colour: extra.colour,
     }
 
 }
@@ -198,13 +243,8 @@ impl TextContext {
         let fragment_shader = fragment_shader::Shader::load(device.clone())
             .expect("unable to load fragment shader");
 
-        let font = FontArc::try_from_slice(include_bytes!("../../fonts/Hack-Regular.ttf"))
-            .expect("unable to load font");
-
-        let font_bounds = FontBounds::new(font.clone(), 20.0);
-        
         let glyph_brush = RefCell::from(
-            GlyphBrushBuilder::using_font(font)
+            GlyphBrushBuilder::using_font(font::default_font())
                 .cache_glyph_positioning(true)
                 .cache_redraws(true)
                 .build());
@@ -273,7 +313,6 @@ impl TextContext {
             device: device.clone(),
             queue,
             glyph_brush,
-            font_bounds,
             texture: TextureCache {
                 image: None,
                 cache_dimensions,
@@ -285,58 +324,16 @@ impl TextContext {
             framebuffers,
             uniform_buffer_pool,
             vertex_buffer: None, 
-            index_buffer: None,
+            index_buffers: vec![],
             dimensions: [0.0, 0.0],
             descriptor_set: None,
         }
     }
 
-    pub fn queue_text(&mut self, section: &Section) {
+    pub fn queue_text(&mut self, section: &Section<GlyphExtra>) {
         self.glyph_brush.borrow_mut().queue(section);
     }
 
-    pub fn get_text_width(&self, text: &str) -> f32 {
-        let mut w: f32 = 0.0;
-        for (_, ch) in text.char_indices() {
-            let bounds = self.font_bounds.get_char_bounds(ch);
-            w += bounds.max.x;
-        }
-
-        w
-    }
-
-    pub fn get_font_size(&self) -> f32 {
-        self.font_bounds.get_scale()
-    }
-
-    pub fn set_font_size(&mut self, font_size: f32) {
-        self.font_bounds.set_scale(font_size);
-    }
-
-    pub fn get_cursor_position(&mut self, section: &Section, offset: usize) -> (f32, f32) {
-        let mut pos: (f32, f32) = section.screen_position;
-        if offset == 0 {
-            return pos;
-        }
-      
-        // TODO: NOT IDEAL, but need a way to simplify two iter loops char_indices with index
-        // offset being summed properly...
-        let line_string: String = section.text.iter().flat_map(|t| t.text.chars()).collect();
-        for (i, ch) in line_string.char_indices(){
-            if offset <= i {
-                break;
-            }
-            let bounds = self.font_bounds.get_char_bounds(ch);
-
-            pos.0 += bounds.max.x;
-            if bounds.max.y > pos.1 {
-                pos.1 = bounds.max.y;
-            }
-        }
-
-        pos 
-    }
-
     fn update_texture(
         cache_dimensions: (usize, usize), 
         cache_pixel_buffer: &mut Vec<u8>, 
@@ -410,7 +407,7 @@ impl TextContext {
     }
 
     fn upload_vertices(&mut self, vertices: Vec<TextVertex>) {
-        let mut indices = vec!();
+        let mut layers: BTreeMap<u32, Vec<u16>> = BTreeMap::new();
         let mut quadrupled_verts = vec!();
         let mut i = 0;
 
@@ -422,13 +419,10 @@ impl TextContext {
             quadrupled_verts.push(glyph_verts[3]);
 
             let ic = i * 4;
-            indices.push(ic);
-            indices.push(ic+1);
-            indices.push(ic+2);
-
-            indices.push(ic+1);
-            indices.push(ic+2);
-            indices.push(ic+3);
+            layers.entry(v.layer).or_default().extend_from_slice(&[
+                ic, ic+1, ic+2,
+                ic+1, ic+2, ic+3,
+            ]);
             i += 1;
         }
 
@@ -438,14 +432,17 @@ impl TextContext {
             self.queue.clone(),
         ).expect("TextContext: unable to create vertex buffer");
 
-        let (index_buffer, _ifuture) = ImmutableBuffer::from_iter(
-            indices.into_iter(),
-            BufferUsage::index_buffer(),
-            self.queue.clone(),
-        ).expect("TextContext: unable to create index buffer");
-         
+        self.index_buffers = layers.into_iter()
+            .map(|(layer, indices)| {
+                let (index_buffer, _ifuture) = ImmutableBuffer::from_iter(
+                    indices.into_iter(),
+                    BufferUsage::index_buffer(),
+                    self.queue.clone(),
+                ).expect("TextContext: unable to create index buffer");
+                (layer, index_buffer)
+            })
+            .collect();
         self.vertex_buffer = Some(vertex_buffer);
-        self.index_buffer = Some(index_buffer);
     }
 
     fn check_recreate_descriptor_set(&mut self, image_num: usize) {
@@ -477,15 +474,24 @@ impl TextContext {
         self.texture.dirty = false;
     }
 
-    #[inline]
-    fn draw_internal<'a>(&'a mut self, 
+    /// Layers with text to draw, lowest first
+    pub fn layers(&self) -> Vec<u32> {
+        self.index_buffers.iter().map(|(layer, _)| *layer).collect()
+    }
+
+    /// Draws the text of one layer, false if it has none
+    pub fn draw_layer<'a>(&'a mut self, 
         builder: &'a mut AutoCommandBufferBuilder, 
         image_num: usize,
+        layer: u32,
     ) -> bool {
         self.check_recreate_descriptor_set(image_num);
 
+        let index_buffer = self.index_buffers.iter()
+            .find(|(index_layer, _)| *index_layer == layer)
+            .map(|(_, index_buffer)| index_buffer.clone());
         if self.vertex_buffer.is_none()
-        || self.index_buffer.is_none()
+        || index_buffer.is_none()
         || self.texture.image.is_none() {
             return false;
         }
@@ -501,7 +507,7 @@ impl TextContext {
                 self.pipeline.clone(),
                 &DynamicState::none(),
                 self.vertex_buffer.clone().unwrap(),
-                self.index_buffer.clone().unwrap(),
+                index_buffer.unwrap(),
                 self.descriptor_set.clone().unwrap(), 
                 (),
             ).expect("unable to draw to command buffer for glyph")
@@ -512,10 +518,8 @@ impl TextContext {
         true
     }
 
-    pub fn draw_text<'a>(&'a mut self, 
-        builder: &'a mut AutoCommandBufferBuilder, 
-        image_num: usize,
-    ) -> bool {
+    /// Lays out the text queued for the frame, before its layers are drawn
+    pub fn process_text(&mut self) {
         let cache_dimensions = self.texture.cache_dimensions;
         let cache_pixel_buffer = &mut self.texture.cache_pixel_buffer;
         let mut updated_texture = false;
@@ -543,8 +547,5 @@ impl TextContext {
             self.texture.image = Some(self.upload_texture());
             self.texture.dirty = true;
         }
-
-        self.draw_internal(builder, image_num)
     }
 }
-
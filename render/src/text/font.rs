@@ -7,6 +7,11 @@ use glyph_brush::ab_glyph::{
 };
 use super::unicode;
 
+pub fn default_font() -> FontArc {
+    FontArc::try_from_slice(include_bytes!("../../../fonts/Hack-Regular.ttf"))
+        .expect("unable to load font")
+}
+
 pub struct FontBounds {
     bounds: HashMap<char, Rect>,
     font_size: f32,
@@ -1,3 +1,8 @@
+use serde::{
+    Serialize,
+    Deserialize,
+};
+
 use super::motion::Motion;
 use super::mode::Mode;
 use super::quantity::Quantity;
@@ -16,25 +21,85 @@ pub enum PluginAction {
     Stop(PluginId),
 }
 
+/// Horizontal splits stack views on top of each other (`:sp`),
+/// vertical splits place them side by side (`:vsp`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowAction {
+    Resize((SplitDirection, isize)),
+    Focus(Motion),
+    FocusNext,
+    FocusPrevious,
+    Exchange,
+    Rotate(Motion),
+    Equalize,
+    Only,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TabAction {
+    New(Option<String>),
+    Close,
+    Next,
+    Previous,
+    Goto(usize),
+}
+
 pub type MotionQuantity = (Motion, Option<Quantity>);
 
+/// What a mouse button does where it was pressed, the position and the number of clicks come
+/// with the input. A double click works on words and a triple click on lines.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseAction {
+    /// Places a single cursor, or selects the word or line
+    Select,
+    /// Extends the last selection to the pointer
+    SelectExtend,
+    /// Adds a cursor, or a selection of the word or line
+    AddCursor,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Action {
     Open(Option<String>),
+    OpenAt((String, usize)),
     Save(Option<String>),
+    SaveSession(Option<String>),
     Split(Option<String>),
+    VerticalSplit(Option<String>),
+    Window(WindowAction),
+    Tab(TabAction),
     Motion(MotionQuantity),
     Select(MotionQuantity),
     Delete(MotionQuantity),
     AddCursor(Motion),
+    Mouse(MouseAction),
     InsertChar(char),
     SetMode(Mode),
     SetTheme(String),
     SetLanguage(String),
+    Search(String),
+    /// Replaces every match of a regex pattern, optionally case sensitive
+    Substitute((String, String, bool)),
     Plugin(PluginAction),
     DefineCommand((String, Box<Action>)),
+    /// Closes the view, refused while its buffer has unsaved changes
     Close,
+    /// Closes the view, discarding any unsaved changes
+    ForceClose,
+    /// Quits, refused while any buffer has unsaved changes
+    QuitAll,
+    /// Quits, discarding any unsaved changes
+    ForceQuitAll,
+    /// Starts the core again once it has stopped, reopening every view
+    RestartCore,
     Execute,
+    ExCommand(String),
     ToggleLineNumbers,
     Indent,
     Outdent,
@@ -19,6 +19,17 @@ pub struct Config {
 }
 
 impl Config {
+    /// Overlays every value which is set in `config`
+    pub fn merge(&mut self, config: Config) {
+        if config.font_face.is_some() { self.font_face = config.font_face; }
+        if config.font_size.is_some() { self.font_size = config.font_size; }
+        if config.line_ending.is_some() { self.line_ending = config.line_ending; }
+        if config.plugin_search_path.is_some() { self.plugin_search_path = config.plugin_search_path; }
+        if config.tab_size.is_some() { self.tab_size = config.tab_size; }
+        if config.translate_tabs_to_spaces.is_some() { self.translate_tabs_to_spaces = config.translate_tabs_to_spaces; }
+        if config.word_wrap.is_some() { self.word_wrap = config.word_wrap; }
+    }
+
     pub fn get_json_changes(&self, config: Config) -> Value {
         let old_json = serde_json::to_value(self.clone()).unwrap();
         let config_json = serde_json::to_value(config.clone()).unwrap();
@@ -0,0 +1,217 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    json,
+    Value,
+};
+
+use xi_core_lib::rpc::GestureType;
+
+/// How `find_next` and `find_previous` change the selection
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModifySelection {
+    None,
+    Set,
+    Add,
+    AddRemovingCurrent,
+}
+
+/// One of the searches of a `multi_find`, the id is None for a new search
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FindQuery {
+    pub id: Option<usize>,
+    pub chars: String,
+    pub case_sensitive: bool,
+    pub regex: bool,
+    pub whole_words: bool,
+}
+
+/// The methods of xi's `edit` notification, sent for a single view
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum EditCommand {
+    Insert { chars: String },
+    Resize { width: f32, height: f32 },
+    /// The first and last line of the viewport
+    Scroll(usize, usize),
+    /// The first and last line to send in full, for lines which were only invalidated
+    RequestLines(usize, usize),
+    GotoLine { line: usize },
+    Gesture { line: usize, col: usize, ty: GestureType },
+    Find { chars: String, case_sensitive: bool, regex: bool, whole_words: bool },
+    MultiFind { queries: Vec<FindQuery> },
+    FindNext { wrap_around: bool, allow_same: bool, modify_selection: ModifySelection },
+    FindPrevious { wrap_around: bool, allow_same: bool, modify_selection: ModifySelection },
+    FindAll,
+    Replace { chars: String, preserve_case: bool },
+    ReplaceNext,
+    ReplaceAll,
+    SelectionForFind { case_sensitive: bool },
+    SelectionForReplace,
+    Undo,
+    Redo,
+    Yank,
+    Copy,
+    Paste,
+    Transpose,
+    Uppercase,
+    Lowercase,
+    Indent,
+    Outdent,
+    InsertNewline,
+    InsertTab,
+    DuplicateLine,
+    SelectAll,
+    CollapseSelections,
+    CancelOperation,
+    AddSelectionAbove,
+    AddSelectionBelow,
+    SelectionIntoLines,
+    DeleteBackward,
+    DeleteForward,
+    DeleteWordBackward,
+    DeleteWordForward,
+    DeleteToBeginningOfLine,
+    DeleteToEndOfParagraph,
+    MoveUp,
+    MoveUpAndModifySelection,
+    MoveDown,
+    MoveDownAndModifySelection,
+    MoveLeft,
+    MoveLeftAndModifySelection,
+    MoveRight,
+    MoveRightAndModifySelection,
+    MoveWordLeft,
+    MoveWordLeftAndModifySelection,
+    MoveWordRight,
+    MoveWordRightAndModifySelection,
+    MoveToBeginningOfParagraph,
+    MoveToBeginningOfParagraphAndModifySelection,
+    MoveToEndOfParagraph,
+    MoveToEndOfParagraphAndModifySelection,
+    MoveToLeftEndOfLine,
+    MoveToLeftEndOfLineAndModifySelection,
+    MoveToRightEndOfLine,
+    MoveToRightEndOfLineAndModifySelection,
+    MoveToBeginningOfDocument,
+    MoveToBeginningOfDocumentAndModifySelection,
+    MoveToEndOfDocument,
+    MoveToEndOfDocumentAndModifySelection,
+    ScrollPageUp,
+    PageUpAndModifySelection,
+    ScrollPageDown,
+    PageDownAndModifySelection,
+}
+
+impl EditCommand {
+    /// The method and params as they go inside an `edit` notification, commands without
+    /// parameters are sent with an empty array
+    pub fn to_method_params(&self) -> (String, Value) {
+        let mut value = serde_json::to_value(self).expect("edit commands always serialize");
+        let method = value["method"].as_str().unwrap_or_default().to_string();
+        let params = match value.get_mut("params") {
+            Some(params) => params.take(),
+            None => json!([]),
+        };
+
+        (method, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xi_core_lib::rpc::{
+        EditCommand as CoreEditCommand,
+        EditNotification,
+        SelectionGranularity,
+    };
+
+    fn method_params(command: EditCommand) -> (String, Value) {
+        command.to_method_params()
+    }
+
+    #[test]
+    fn struct_variants_send_named_params() {
+        assert_eq!(method_params(EditCommand::Gesture {
+            line: 1,
+            col: 4,
+            ty: GestureType::Select { granularity: SelectionGranularity::Point, multi: false },
+        }), ("gesture".to_string(), json!({
+            "line": 1, "col": 4, "ty": { "select": { "granularity": "point", "multi": false } },
+        })));
+        assert_eq!(method_params(EditCommand::Find {
+            chars: "foo".to_string(), case_sensitive: true, regex: false, whole_words: true,
+        }), ("find".to_string(), json!({
+            "chars": "foo", "case_sensitive": true, "regex": false, "whole_words": true,
+        })));
+        assert_eq!(method_params(EditCommand::FindNext {
+            wrap_around: true, allow_same: false, modify_selection: ModifySelection::AddRemovingCurrent,
+        }), ("find_next".to_string(), json!({
+            "wrap_around": true, "allow_same": false, "modify_selection": "add_removing_current",
+        })));
+        assert_eq!(method_params(EditCommand::Resize { width: 1280.0, height: 677.5 }),
+            ("resize".to_string(), json!({ "width": 1280.0, "height": 677.5 })));
+        assert_eq!(method_params(EditCommand::SelectionForFind { case_sensitive: false }),
+            ("selection_for_find".to_string(), json!({ "case_sensitive": false })));
+    }
+
+    #[test]
+    fn tuple_variants_send_an_array() {
+        assert_eq!(method_params(EditCommand::Scroll(0, 32)), ("scroll".to_string(), json!([0, 32])));
+        assert_eq!(method_params(EditCommand::RequestLines(40, 80)), ("request_lines".to_string(), json!([40, 80])));
+    }
+
+    #[test]
+    fn unit_variants_send_an_empty_array() {
+        assert_eq!(method_params(EditCommand::Yank), ("yank".to_string(), json!([])));
+        assert_eq!(method_params(EditCommand::CollapseSelections), ("collapse_selections".to_string(), json!([])));
+        assert_eq!(method_params(EditCommand::MoveToBeginningOfParagraph),
+            ("move_to_beginning_of_paragraph".to_string(), json!([])));
+    }
+
+    // Copy, Paste and CancelOperation are left out, as xi takes copy as a request, paste with the
+    // text to insert, and has no cancel_operation
+    #[test]
+    fn xi_accepts_the_commands() {
+        let commands = vec![
+            EditCommand::Insert { chars: "a".to_string() },
+            EditCommand::Resize { width: 800.0, height: 600.0 },
+            EditCommand::Scroll(0, 30),
+            EditCommand::RequestLines(30, 60),
+            EditCommand::GotoLine { line: 3 },
+            EditCommand::Gesture { line: 0, col: 2, ty: GestureType::SelectExtend { granularity: SelectionGranularity::Word } },
+            EditCommand::Find { chars: "a".to_string(), case_sensitive: false, regex: true, whole_words: false },
+            EditCommand::MultiFind { queries: vec![FindQuery {
+                id: None, chars: "a".to_string(), case_sensitive: false, regex: false, whole_words: false,
+            }] },
+            EditCommand::FindNext { wrap_around: true, allow_same: true, modify_selection: ModifySelection::Set },
+            EditCommand::FindPrevious { wrap_around: false, allow_same: false, modify_selection: ModifySelection::None },
+            EditCommand::FindAll,
+            EditCommand::Replace { chars: "b".to_string(), preserve_case: true },
+            EditCommand::ReplaceNext,
+            EditCommand::ReplaceAll,
+            EditCommand::SelectionForFind { case_sensitive: true },
+            EditCommand::SelectionForReplace,
+            EditCommand::Yank,
+            EditCommand::CollapseSelections,
+            EditCommand::DeleteToEndOfParagraph,
+            EditCommand::MoveToBeginningOfParagraph,
+            EditCommand::MoveToBeginningOfParagraphAndModifySelection,
+            EditCommand::MoveToEndOfParagraph,
+            EditCommand::MoveToEndOfParagraphAndModifySelection,
+            EditCommand::PageDownAndModifySelection,
+        ];
+
+        for command in commands {
+            let (method, params) = command.to_method_params();
+            let edit = json!({ "method": method, "params": params, "view_id": "view-id-1" });
+            if let Err(err) = serde_json::from_value::<CoreEditCommand<EditNotification>>(edit) {
+                panic!("xi rejects {}: {}", method, err);
+            }
+        }
+    }
+}
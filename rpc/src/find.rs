@@ -3,10 +3,11 @@ use serde::Deserialize;
 #[derive(Clone, Debug, Deserialize)]
 pub struct Query {
     pub id: usize,
-    pub chars: String,
-    pub case_sensitive: bool,
-    pub is_regex: bool,
-    pub whole_words: bool,
+    /// The search and its flags are null while the query has no search set
+    pub chars: Option<String>,
+    pub case_sensitive: Option<bool>,
+    pub is_regex: Option<bool>,
+    pub whole_words: Option<bool>,
     pub matches: usize,
     pub lines: Vec<usize>,
 }
@@ -19,12 +20,12 @@ pub struct FindStatus {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Status {
-    chars: String,
-    preserve_case: bool,
+    pub chars: String,
+    pub preserve_case: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ReplaceStatus {
-    view_id: String,
-    status: Status, 
+    pub view_id: String,
+    pub status: Status,
 }
@@ -10,6 +10,8 @@ mod mode;
 mod motion;
 mod find;
 mod plugins;
+mod notification;
+mod edit;
 pub mod theme;
 
 pub use xi_core_lib::rpc::GestureType;
@@ -29,4 +31,6 @@ pub use quantity::*;
 pub use mode::*;
 pub use motion::*;
 pub use plugins::*;
+pub use notification::*;
+pub use edit::*;
 
@@ -0,0 +1,388 @@
+use serde::Deserialize;
+use serde_json::{
+    json,
+    Value,
+};
+
+use annotations::Annotation;
+use config::Config;
+use theme::{
+    Theme,
+    Style,
+};
+use find::{
+    FindStatus,
+    ReplaceStatus,
+};
+use plugins::{
+    Command,
+    PluginId,
+};
+
+pub type ViewId = String;
+
+/// Notifications sent from xi-core to the frontend
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum CoreNotification {
+    Update(Update),
+    ScrollTo(ScrollTo),
+    DefStyle(Style),
+    ThemeChanged(ThemeChanged),
+    AvailableThemes(AvailableThemes),
+    AvailableLanguages(AvailableLanguages),
+    LanguageChanged(LanguageChanged),
+    ConfigChanged(ConfigChanged),
+    AvailablePlugins(AvailablePlugins),
+    PluginStarted(PluginStarted),
+    PluginStopped(PluginStopped),
+    UpdateCmds(UpdateCmds),
+    FindStatus(FindStatus),
+    ReplaceStatus(ReplaceStatus),
+    ShowHover(ShowHover),
+    Alert(Alert),
+    AddStatusItem(AddStatusItem),
+    UpdateStatusItem(UpdateStatusItem),
+    RemoveStatusItem(RemoveStatusItem),
+}
+
+/// Requests sent from xi-core to the frontend, which expect a result
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum CoreRequest {
+    MeasureWidth(Vec<MeasureWidth>),
+}
+
+impl CoreNotification {
+    pub fn decode(method: &str, params: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(json!({ "method": method, "params": params }))
+    }
+
+    /// The view the notification is for, or None when it applies to the whole editor
+    pub fn view_id(&self) -> Option<&str> {
+        let view_id = match self {
+            CoreNotification::Update(update) => &update.view_id,
+            CoreNotification::ScrollTo(scroll_to) => &scroll_to.view_id,
+            CoreNotification::LanguageChanged(language) => &language.view_id,
+            CoreNotification::ConfigChanged(config) => &config.view_id,
+            CoreNotification::AvailablePlugins(available) => &available.view_id,
+            CoreNotification::PluginStarted(started) => &started.view_id,
+            CoreNotification::PluginStopped(stopped) => &stopped.view_id,
+            CoreNotification::UpdateCmds(update) => &update.view_id,
+            CoreNotification::FindStatus(find_status) => &find_status.view_id,
+            CoreNotification::ReplaceStatus(replace_status) => &replace_status.view_id,
+            CoreNotification::AddStatusItem(item) => &item.view_id,
+            CoreNotification::UpdateStatusItem(item) => &item.view_id,
+            CoreNotification::RemoveStatusItem(item) => &item.view_id,
+            CoreNotification::DefStyle(_)
+            | CoreNotification::ThemeChanged(_)
+            | CoreNotification::AvailableThemes(_)
+            | CoreNotification::AvailableLanguages(_)
+            | CoreNotification::ShowHover(_)
+            | CoreNotification::Alert(_) => return None,
+        };
+
+        Some(view_id)
+    }
+}
+
+impl CoreRequest {
+    pub fn decode(method: &str, params: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(json!({ "method": method, "params": params }))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Update {
+    pub view_id: ViewId,
+    pub update: ViewUpdate,
+}
+
+/// Rebuilds the line cache of a view from the old one, applying each op in order
+#[derive(Clone, Debug, Deserialize)]
+pub struct ViewUpdate {
+    pub ops: Vec<UpdateOp>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    pub pristine: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum UpdateOp {
+    /// New lines, inserted in place of none of the old ones
+    Ins { lines: Vec<UpdateLine> },
+    /// Keeps the next n old lines
+    Copy { n: usize },
+    /// Drops the next n old lines
+    Skip { n: usize },
+    /// n lines the frontend has no text for yet
+    Invalidate { n: usize },
+    /// Keeps the next old lines, taking new cursors from these
+    Update { lines: Vec<UpdateLine> },
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UpdateLine {
+    pub text: Option<String>,
+    pub ln: Option<u64>,
+    /// Cursor offsets in utf-8 bytes
+    #[serde(default)]
+    pub cursor: Vec<usize>,
+    /// Triples of start, relative to the end of the previous span, length and style id
+    #[serde(default)]
+    pub styles: Vec<i64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScrollTo {
+    pub view_id: ViewId,
+    pub line: usize,
+    #[serde(default)]
+    pub col: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ThemeChanged {
+    pub name: String,
+    pub theme: Theme,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AvailableThemes {
+    pub themes: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AvailableLanguages {
+    pub languages: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LanguageChanged {
+    pub view_id: ViewId,
+    pub language_id: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfigChanged {
+    pub view_id: ViewId,
+    pub changes: Config,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PluginDescription {
+    pub name: PluginId,
+    pub running: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AvailablePlugins {
+    pub view_id: ViewId,
+    pub plugins: Vec<PluginDescription>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PluginStarted {
+    pub view_id: ViewId,
+    pub plugin: PluginId,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PluginStopped {
+    pub view_id: ViewId,
+    pub plugin: PluginId,
+    #[serde(default)]
+    pub code: i32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UpdateCmds {
+    pub view_id: ViewId,
+    pub plugin: PluginId,
+    pub cmds: Vec<Command>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShowHover {
+    pub request_id: usize,
+    pub result: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Alert {
+    pub msg: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AddStatusItem {
+    pub view_id: ViewId,
+    pub source: String,
+    pub key: String,
+    pub value: String,
+    pub alignment: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UpdateStatusItem {
+    pub view_id: ViewId,
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RemoveStatusItem {
+    pub view_id: ViewId,
+    pub key: String,
+}
+
+/// Strings to measure in the given style, answered with a width for each
+#[derive(Clone, Debug, Deserialize)]
+pub struct MeasureWidth {
+    pub id: usize,
+    pub strings: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Payloads as xi-core sends them, each with the view it is for
+    fn payloads() -> Vec<(&'static str, Value, Option<&'static str>)> {
+        vec![
+            ("update", json!({
+                "update": {
+                    "annotations": [
+                        { "n": 1, "payloads": null, "ranges": [[1, 3, 1, 3]], "type": "selection" },
+                        { "n": 1, "payloads": [{ "id": 1 }], "ranges": [[1, 0, 1, 3]], "type": "find" },
+                    ],
+                    "ops": [
+                        { "lines": [{ "ln": 1, "styles": [], "text": "hello world\n" }], "n": 1, "op": "ins" },
+                        { "lines": [{ "cursor": [3], "ln": 2, "styles": [0, 3, 9], "text": "baz bar\n" }], "n": 1, "op": "ins" },
+                        { "n": 2, "op": "skip" },
+                        { "ln": 3, "n": 1, "op": "copy" },
+                        { "n": 4, "op": "invalidate" },
+                    ],
+                    "pristine": false,
+                },
+                "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            ("scroll_to", json!({ "col": 3, "line": 1, "view_id": "view-id-1" }), Some("view-id-1")),
+            ("def_style", json!({ "fg_color": 4285297092u32, "id": 9, "italic": true, "weight": 700 }), None),
+            ("theme_changed", json!({
+                "name": "Solarized (dark)",
+                "theme": {
+                    "accent": null, "active_guide": null,
+                    "background": { "a": 255, "b": 54, "g": 43, "r": 0 },
+                    "bracket_contents_foreground": null, "bracket_contents_options": null,
+                    "brackets_background": null, "brackets_foreground": null, "brackets_options": null,
+                    "caret": { "a": 255, "b": 213, "g": 232, "r": 238 },
+                    "find_highlight": null, "find_highlight_foreground": null,
+                    "foreground": { "a": 255, "b": 150, "g": 148, "r": 131 },
+                    "guide": null,
+                    "gutter": { "a": 255, "b": 66, "g": 54, "r": 7 },
+                    "gutter_foreground": null, "highlight": null, "highlight_foreground": null,
+                    "inactive_selection": null, "inactive_selection_foreground": null,
+                    "line_highlight": { "a": 18, "b": 255, "g": 209, "r": 28 },
+                    "minimap_border": null,
+                    "misspelling": { "a": 255, "b": 47, "g": 50, "r": 220 },
+                    "phantom_css": null, "popup_css": null,
+                    "selection": { "a": 255, "b": 85, "g": 76, "r": 44 },
+                    "selection_background": null,
+                    "selection_border": { "a": 255, "b": 117, "g": 110, "r": 88 },
+                    "selection_foreground": null, "shadow": null, "stack_guide": null,
+                    "tags_foreground": null, "tags_options": null,
+                },
+            }), None),
+            ("config_changed", json!({
+                "changes": {
+                    "auto_indent": true, "autodetect_whitespace": true, "font_face": "InconsolataGo",
+                    "font_size": 14, "line_ending": "\n", "plugin_search_path": [], "save_with_newline": true,
+                    "scroll_past_end": false, "surrounding_pairs": [["\"", "\""], ["'", "'"], ["{", "}"], ["[", "]"]],
+                    "tab_size": 4, "translate_tabs_to_spaces": true, "use_tab_stops": true, "word_wrap": false,
+                    "wrap_width": 0,
+                },
+                "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            ("available_plugins", json!({
+                "plugins": [{ "name": "syntect", "running": true }],
+                "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            ("update_cmds", json!({
+                "cmds": [{
+                    "args": [{ "arg_type": "Bool", "description": "Sort in reverse", "key": "reverse", "title": "Reverse" }],
+                    "description": "Sorts the selected lines",
+                    "rpc_cmd": { "method": "sort", "params": { "reverse": "$reverse" }, "rpc_type": "notification" },
+                    "title": "Sort lines",
+                }],
+                "plugin": "sorter",
+                "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            ("add_status_item", json!({
+                "alignment": "left", "key": "lint", "source": "linter", "value": "2 warnings", "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            // Queries without a search have null chars and flags
+            ("find_status", json!({
+                "queries": [{
+                    "case_sensitive": null, "chars": null, "id": 1, "is_regex": null, "lines": [], "matches": 1,
+                    "whole_words": null,
+                }],
+                "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            ("find_status", json!({
+                "queries": [{
+                    "case_sensitive": true, "chars": "foo", "id": 1, "is_regex": true, "lines": [2], "matches": 1,
+                    "whole_words": false,
+                }],
+                "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            ("replace_status", json!({
+                "status": { "chars": "baz", "preserve_case": false },
+                "view_id": "view-id-1",
+            }), Some("view-id-1")),
+            ("show_hover", json!({ "request_id": 2, "result": "fn main()", "view_id": "view-id-1" }), None),
+            ("alert", json!({ "msg": "unable to load the syntax plugin" }), None),
+        ]
+    }
+
+    #[test]
+    fn decodes_what_xi_sends() {
+        for (method, params, view_id) in payloads() {
+            match CoreNotification::decode(method, &params) {
+                Ok(notification) => assert_eq!(notification.view_id(), view_id, "{}", method),
+                Err(err) => panic!("unable to decode {}: {}", method, err),
+            }
+        }
+    }
+
+    #[test]
+    fn find_status_without_a_search() {
+        let params = &payloads().into_iter().find(|(method, ..)| *method == "find_status").unwrap().1;
+        match CoreNotification::decode("find_status", params).unwrap() {
+            CoreNotification::FindStatus(status) => {
+                let query = &status.queries[0];
+                assert_eq!(query.chars, None);
+                assert_eq!((query.case_sensitive, query.is_regex, query.whole_words), (None, None, None));
+                assert_eq!(query.matches, 1);
+            },
+            notification => panic!("decoded as {:?}", notification),
+        }
+    }
+
+    #[test]
+    fn decodes_measure_width() {
+        let params = json!([{ "id": 0, "strings": ["hello", "w"] }]);
+        match CoreRequest::decode("measure_width", &params).unwrap() {
+            CoreRequest::MeasureWidth(measurements) => {
+                assert_eq!(measurements.len(), 1);
+                assert_eq!(measurements[0].strings, vec!["hello", "w"]);
+            },
+        }
+    }
+
+    #[test]
+    fn unknown_methods_fail_to_decode() {
+        assert!(CoreNotification::decode("frobnicate", &json!({})).is_err());
+        assert!(CoreNotification::decode("scroll_to", &json!({ "line": 1 })).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::time::Duration;
+
+use rpc::SplitDirection;
+use render::Backend;
+
+const USAGE: &str = "\
+Usage: editor [options] [file ..]
+
+A file named - reads standard input into a new buffer, ie. `git log | editor -`
+
+Options:
+  -o                  Open each file in a horizontal split
+  -O                  Open each file in a vertical split
+  -p                  Open each file in its own tab page
+  +N                  Start at line N
+  +                   Start at the last line
+  +/pattern           Start at the first match of pattern
+  -c <command>        Run an Ex command once the first file has loaded, may be repeated
+  -R                  Read-only, files cannot be written
+  --headless          Run the -c commands and script without a window, then quit as :qa does,
+                      failing if a buffer has unsaved changes
+  -s, --script <file> Run the Ex commands in file once the first file has loaded, one per line
+  --session <file>    Restore a session saved with :mksession
+  --server            Listen for remote clients on the socket
+  --socket <path>     Socket used by --server and the --remote options
+  --remote            Open the files, as file[:line], in the editor listening on the socket
+  --remote-wait       As --remote, then wait for the files to be closed, ie. for $EDITOR
+  --remote-send <cmd> Run an Ex command in the editor listening on the socket, may be repeated
+  --config-dir <dir>  Read preferences and themes from dir instead of the xi config directory
+  --theme <name>      Start with the named theme
+  --font-size <size>  Start with the given font size
+  --cursor-blink <ms> Blink the cursor every ms milliseconds, 0 keeps it solid, defaults to 530
+  --autosave <secs>   Save modified files once typing has paused for secs seconds
+  --core <path>       Run the xi-core binary at path over stdio rather than in process, or the
+                      editor's own buffer engine when path is native, defaults to $XI_CORE when set
+  --record <file>     Log every message to and from the core, with timestamps, as JSON lines
+  --replay <file>     Play back the core side of a recording instead of starting a core, with
+                      --headless the editor exits once everything has been played back, with an
+                      error if the frontend did not send what was recorded
+  --renderer <name>   Draw with auto, vulkan or cpu, auto falls back to cpu without a Vulkan device
+  --version           Print the version and exit
+  --help              Print this help and exit
+
+Without -o, -O or -p only the first file is opened.";
+
+/// The file name which reads standard input into a new buffer
+pub const STDIN_FILE: &str = "-";
+
+// The interval xterm and most desktops blink the cursor at
+const DEFAULT_CURSOR_BLINK_MS: u64 = 530;
+
+/// How multiple files given on the command line are opened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenMode {
+    First,
+    Split(SplitDirection),
+    Tabs,
+}
+
+/// Where the cursor starts in the first file
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartPosition {
+    Line(usize),
+    LastLine,
+    Pattern(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub files: Vec<String>,
+    pub open_mode: OpenMode,
+    pub position: Option<StartPosition>,
+    pub commands: Vec<String>,
+    pub session: Option<String>,
+    pub config_dir: Option<String>,
+    pub theme: Option<String>,
+    pub font_size: f32,
+    pub cursor_blink: Option<Duration>,
+    pub autosave: Option<Duration>,
+    pub renderer: Backend,
+    pub read_only: bool,
+    pub headless: bool,
+    pub script: Option<String>,
+    pub server: bool,
+    pub socket: Option<String>,
+    pub remote: Option<Remote>,
+    pub remote_commands: Vec<String>,
+    pub core: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    /// Standard input, read before the editor starts when a file is named -
+    pub stdin: Option<String>,
+}
+
+/// Whether files are sent to a running editor rather than opened by this one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Remote {
+    Open,
+    Wait,
+}
+
+impl Options {
+    pub fn is_remote(&self) -> bool {
+        self.remote.is_some() || self.remote_commands.len() > 0
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            files: vec![],
+            open_mode: OpenMode::First,
+            position: None,
+            commands: vec![],
+            session: None,
+            config_dir: None,
+            theme: None,
+            font_size: 20.0,
+            cursor_blink: Some(Duration::from_millis(DEFAULT_CURSOR_BLINK_MS)),
+            autosave: None,
+            renderer: Backend::Auto,
+            read_only: false,
+            headless: false,
+            script: None,
+            server: false,
+            socket: None,
+            remote: None,
+            remote_commands: vec![],
+            core: None,
+            record: None,
+            replay: None,
+            stdin: None,
+        }
+    }
+}
+
+pub enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+pub fn version() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+fn value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+    args.next().ok_or(format!("{} requires a value", option))
+}
+
+// An interval in the given unit, where 0 turns off what it times
+fn interval(args: &mut impl Iterator<Item = String>, option: &str, unit: fn(u64) -> Duration)
+    -> Result<Option<Duration>, String> {
+    let value = value(args, option)?;
+    match value.parse::<u64>() {
+        Ok(0) => Ok(None),
+        Ok(count) => Ok(Some(unit(count))),
+        Err(_) => Err(format!("invalid {} interval: {}", option, value)),
+    }
+}
+
+/// Parses the arguments following the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    let mut files_only = false;
+
+    while let Some(arg) = args.next() {
+        if files_only {
+            options.files.push(arg);
+            continue;
+        }
+
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--version" | "-v" => return Ok(Command::Version),
+            "--" => files_only = true,
+            "-o" => options.open_mode = OpenMode::Split(SplitDirection::Horizontal),
+            "-O" => options.open_mode = OpenMode::Split(SplitDirection::Vertical),
+            "-p" => options.open_mode = OpenMode::Tabs,
+            "-R" => options.read_only = true,
+            "--headless" => options.headless = true,
+            "-s" | "--script" => options.script = Some(value(&mut args, &arg)?),
+            "-c" => options.commands.push(value(&mut args, &arg)?),
+            "--session" => options.session = Some(value(&mut args, &arg)?),
+            "--server" => options.server = true,
+            "--socket" => options.socket = Some(value(&mut args, &arg)?),
+            "--remote" => options.remote = Some(Remote::Open),
+            "--remote-wait" => options.remote = Some(Remote::Wait),
+            "--remote-send" => options.remote_commands.push(value(&mut args, &arg)?),
+            "--core" => options.core = Some(value(&mut args, &arg)?),
+            "--record" => options.record = Some(value(&mut args, &arg)?),
+            "--replay" => options.replay = Some(value(&mut args, &arg)?),
+            "--config-dir" => options.config_dir = Some(value(&mut args, &arg)?),
+            "--theme" => options.theme = Some(value(&mut args, &arg)?),
+            "--font-size" => {
+                let size = value(&mut args, &arg)?;
+                options.font_size = match size.parse::<f32>() {
+                    Ok(size) if size > 0.0 => size,
+                    _ => return Err(format!("invalid font size: {}", size)),
+                };
+            },
+            "--cursor-blink" => options.cursor_blink = interval(&mut args, &arg, Duration::from_millis)?,
+            "--autosave" => options.autosave = interval(&mut args, &arg, Duration::from_secs)?,
+            "--renderer" => {
+                let name = value(&mut args, &arg)?;
+                options.renderer = Backend::from_name(&name)
+                    .ok_or(format!("unknown renderer: {}", name))?;
+            },
+            "+" => options.position = Some(StartPosition::LastLine),
+            _ if arg.starts_with("+/") => {
+                options.position = Some(StartPosition::Pattern(arg[2..].to_string()));
+            },
+            _ if arg.starts_with('+') => match arg[1..].parse::<usize>() {
+                Ok(line) => options.position = Some(StartPosition::Line(line)),
+                Err(_) => return Err(format!("invalid line: {}", arg)),
+            },
+            _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("unknown option: {}", arg)),
+            _ => options.files.push(arg),
+        }
+    }
+
+    Ok(Command::Run(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Options {
+        match parse(args.iter().map(|arg| arg.to_string())) {
+            Ok(Command::Run(options)) => options,
+            Ok(_) => panic!("{:?} did not parse to a run", args),
+            Err(error) => panic!("{:?} failed to parse: {}", args, error),
+        }
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(args.iter().map(|arg| arg.to_string())) {
+            Err(error) => error,
+            Ok(_) => panic!("{:?} parsed", args),
+        }
+    }
+
+    #[test]
+    fn open_modes() {
+        assert_eq!(options(&["a", "b"]).open_mode, OpenMode::First);
+        assert_eq!(options(&["-o", "a", "b"]).open_mode, OpenMode::Split(SplitDirection::Horizontal));
+        assert_eq!(options(&["-O", "a", "b"]).open_mode, OpenMode::Split(SplitDirection::Vertical));
+        assert_eq!(options(&["-p", "a", "b"]).open_mode, OpenMode::Tabs);
+        assert_eq!(options(&["-o", "a", "b"]).files, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn start_positions() {
+        assert_eq!(options(&["+12", "a"]).position, Some(StartPosition::Line(12)));
+        assert_eq!(options(&["+", "a"]).position, Some(StartPosition::LastLine));
+        assert_eq!(options(&["+/fn main", "a"]).position,
+            Some(StartPosition::Pattern(String::from("fn main"))));
+        assert_eq!(options(&["a"]).position, None);
+        assert_eq!(error(&["+12x"]), "invalid line: +12x");
+    }
+
+    #[test]
+    fn file_line_arguments_are_left_to_the_opener() {
+        let options = options(&["src/main.rs:10", "b"]);
+        assert_eq!(options.files, vec!["src/main.rs:10", "b"]);
+        assert_eq!(options.position, None);
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let options = options(&["-R", "--", "-o", "+3", "-"]);
+        assert!(options.read_only);
+        assert_eq!(options.open_mode, OpenMode::First);
+        assert_eq!(options.position, None);
+        assert_eq!(options.files, vec!["-o", "+3", "-"]);
+    }
+
+    #[test]
+    fn repeated_and_valued_options() {
+        let options = options(&["-c", ":set ts=4", "-c", ":w", "--cursor-blink", "0",
+            "--autosave", "5", "--font-size", "14.5", "--renderer", "cpu"]);
+        assert_eq!(options.commands, vec![":set ts=4", ":w"]);
+        assert_eq!(options.cursor_blink, None);
+        assert_eq!(options.autosave, Some(Duration::from_secs(5)));
+        assert_eq!(options.font_size, 14.5);
+        assert_eq!(options.renderer, Backend::Cpu);
+        assert!(options.files.is_empty());
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert_eq!(error(&["-x"]), "unknown option: -x");
+        assert_eq!(error(&["--frobnicate"]), "unknown option: --frobnicate");
+        assert_eq!(error(&["-c"]), "-c requires a value");
+        assert_eq!(error(&["--font-size", "0"]), "invalid font size: 0");
+        assert_eq!(error(&["--autosave", "soon"]), "invalid --autosave interval: soon");
+        assert_eq!(error(&["--renderer", "opengl"]), "unknown renderer: opengl");
+    }
+
+    #[test]
+    fn help_and_version_stop_parsing() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        assert!(matches!(parse(args(&["a", "--help", "-x"])), Ok(Command::Help)));
+        assert!(matches!(parse(args(&["a", "--version"])), Ok(Command::Version)));
+    }
+}
@@ -4,6 +4,16 @@ mod xi_thread;
 mod plugins;
 mod view_resources;
 mod commands;
+mod layout;
+mod session;
+mod recording;
+mod core_process;
+mod native_core;
+mod timers;
+#[cfg(test)]
+mod fake_core;
+#[cfg(test)]
+mod tests;
 
 pub mod ui;
 pub mod state;
@@ -14,17 +24,23 @@ pub mod view_commands;
 use std::cell::RefCell;
 use std::sync::{Arc, Mutex};
 use std::str;
+use std::fs;
+use std::sync::mpsc;
+use std::time::{
+    Duration,
+    Instant,
+};
+use std::process;
+use std::env;
 
 use super::render::Renderer;
 use super::events::{
     EditorEvent,
+    EditorEventLoopProxy,
     self,
 };
 
-use winit::event_loop::{
-    ControlFlow,
-    EventLoopProxy,
-};
+use winit::event_loop::ControlFlow;
 use winit::event::{
     WindowEvent,
     Event,
@@ -32,10 +48,8 @@ use winit::event::{
 use serde_json::{
     Value,
     json,
-    from_value,
 };
 use plugins::PluginState;
-use xi_core_lib::plugins::Command;
 
 use ui::view::{
     EditView,
@@ -43,56 +57,199 @@ use ui::view::{
 use ui::widget::Widget;
 use state::EditorState;
 use rpc::{ 
-    Config, 
-    Theme,
-    Style,
     Action,
-    PluginAction,
-    FindStatus,
+    CoreNotification,
+    CoreRequest,
+    SplitDirection,
+    WindowAction,
+    TabAction,
+    Motion,
+    Quantity,
+    Mode,
 };
 use editor_rpc::{
     Core,
     Handler,
+    Responder,
+    Response,
+    Transport,
+};
+use core_process::CoreProcess;
+use native_core::NativeCore;
+use recording::{
+    Recorder,
+    ReplayCore,
 };
 use view_commands::EditViewCommands;
+use layout::Placement;
+use timers::{
+    Timer,
+    Timers,
+    MESSAGE_TIMEOUT,
+    KEY_SEQUENCE_TIMEOUT,
+    AUTO_SCROLL_INTERVAL,
+};
+use session::{
+    Session,
+    DEFAULT_SESSION_FILE,
+};
+use crate::cli::{
+    OpenMode,
+    Options,
+    StartPosition,
+    STDIN_FILE,
+};
+use crate::remote::{
+    RemoteServer,
+    default_socket_path,
+};
 use super::events::{
     state::InputState,
 };
 
+// Views are laid out as if in a window of this size when headless
+const HEADLESS_SCREEN_SIZE: [f32; 2] = [1280.0, 720.0];
+// How long a headless run waits for the core to write its saves before exiting
+const HEADLESS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+// How long a headless replay waits for the frontend to send what it was recorded sending
+const REPLAY_IDLE_TIMEOUT: Duration = Duration::from_secs(2);
 
-#[derive(Clone)]
+const STDIN_BUFFER_NAME: &str = "[stdin]";
+
+/// What the event loop should do after an action has been handled
+enum ActionFlow {
+    Continue,
+    Redraw,
+    Exit,
+    /// Only when headless, exits with an error status
+    Failed,
+}
+
+/// The editor, owned by the thread running the event loop
+///
+/// Nothing else touches the state or input, whatever the core sends reaches the app as a
+/// `CoreEvent` through the event loop.
 struct App {
     core: Arc<Mutex<Core>>,
-    state: Arc<Mutex<EditorState>>,
-    input: Arc<Mutex<InputState>>,
+    state: EditorState,
+    input: InputState,
+    launch: Option<CoreLaunch>,
+    timers: Timers,
+    remote: Option<RemoteServer>,
+    // The exit status of a core which crashed, told to the user once it has disconnected
+    crashed: Option<String>,
 }
 
+/// What the core sent, decoded on the thread receiving from it
+pub enum CoreEvent {
+    Notification(CoreNotification),
+    Request(CoreRequest, Responder),
+    /// The response to `new_view`, with the view it was made for
+    ViewOpened(NewView, Response),
+    /// Something the core sent which could not be understood
+    Error(String),
+    /// The transport could not go on standing in for the core, a headless run exits with an error
+    Failed(String),
+    /// The core exited with this status, it disconnects straight after
+    Crashed(String),
+    Disconnected,
+}
+
+/// A view waiting for the core to open its buffer
+pub struct NewView {
+    filename: Option<String>,
+    placement: Placement,
+    screen_size: [f32; 2],
+    font_size: f32,
+    // Poked into the view once its buffer has loaded
+    on_load: Vec<EditViewCommands>,
+}
+
+/// Which core to run
+#[derive(Clone)]
+enum CoreKind {
+    /// xi-core on a thread of this process
+    Thread,
+    /// The editor's own buffer engine
+    Native,
+    /// An xi-core binary at this path
+    Process(String),
+}
+
+impl CoreKind {
+    fn from_arg(arg: Option<String>) -> Self {
+        match arg {
+            Some(ref arg) if arg == "native" => CoreKind::Native,
+            Some(path) => CoreKind::Process(path),
+            None => CoreKind::Thread,
+        }
+    }
+}
+
+/// How the core was started, so that it can be started again after it stops
+#[derive(Clone)]
+struct CoreLaunch {
+    kind: CoreKind,
+    config_dir: String,
+}
+
+impl CoreLaunch {
+    fn start(&self, handler: AppDispatcher, record: &Option<String>) -> Result<Core, String> {
+        match &self.kind {
+            CoreKind::Process(path) => {
+                let (process, rx) = CoreProcess::spawn(path, handler.clone())?;
+                Ok(connect(process, rx, handler, record))
+            },
+            CoreKind::Native => {
+                let (native, rx) = NativeCore::new();
+                Ok(connect(native, rx, handler, record))
+            },
+            CoreKind::Thread => {
+                let (xi_peer, rx) = xi_thread::start_xi_thread();
+                Ok(connect(xi_peer, rx, handler, record))
+            },
+        }
+    }
+}
+
+/// Passes what the core sends on to the event loop
 #[derive(Clone)]
 struct AppDispatcher {
-    app: Arc<Mutex<Option<App>>>,
+    proxy: EditorEventLoopProxy,
 }
 
 impl AppDispatcher {
-    fn new() -> Self {
+    fn new(proxy: EditorEventLoopProxy) -> Self {
         Self {
-            app: Default::default(),
+            proxy,
         }
     }
-    
-    fn set_app(&self, app: &App) {
-        *self.app.lock().unwrap() = Some(app.clone());
+
+    fn post(&self, event: CoreEvent) {
+        if self.proxy.send_event(EditorEvent::Core(event)).is_err() {
+            println!("unable to send core event, the event loop has closed");
+        }
     }
 }
 
 impl App {
-    fn new(core: Core, event_loop_proxy: EventLoopProxy<EditorEvent>) -> Self { 
+    fn new(core: Core, event_loop_proxy: EditorEventLoopProxy) -> Self { 
         Self {
             core: Arc::new(Mutex::new(core)),
-            state: Arc::new(Mutex::new(EditorState::new(event_loop_proxy))),
-            input: Arc::new(Mutex::new(InputState::new())),
+            state: EditorState::new(event_loop_proxy),
+            input: InputState::new(),
+            launch: None,
+            timers: Timers::default(),
+            remote: None,
+            crashed: None,
         }
     }
 
+    fn with_launch(mut self, launch: CoreLaunch) -> Self {
+        self.launch = Some(launch);
+        self
+    }
+
     fn get_core(&self) -> std::sync::MutexGuard<'_, Core, > {
         self.core.lock().unwrap()
     }
@@ -101,221 +258,625 @@ impl App {
         self.get_core().send_notification(method, params);
     }
 
-    fn send_view_cmd(&self, command: EditViewCommands) {
-        let mut state = self.state.lock().unwrap();
-        let view_state = state.get_focused_view();
+    fn send_view_cmd(&mut self, view_id: &str, command: EditViewCommands) {
+        self.state.send_view_cmd(view_id, command);
+    }
 
-        view_state.poke(command);
+    pub fn open_new_view(&mut self, filename: Option<String>, placement: Placement, screen_size: [f32; 2], font_size: f32) {
+        self.new_view(filename, placement, screen_size, font_size, vec![]);
     }
 
-    pub fn open_new_view(&self, filename: Option<String>, screen_size: [f32; 2], font_size: f32) {
+    // Opens a view, poking it with the given commands once its buffer has loaded
+    fn new_view(&mut self, filename: Option<String>, placement: Placement, screen_size: [f32; 2], font_size: f32,
+        on_load: Vec<EditViewCommands>) {
+        // Standard input was read at startup, reading it again would block until it is closed
+        if filename.as_deref() == Some(STDIN_FILE) {
+            self.remote_view_opened(&filename, None);
+            self.state.show_message("Standard input can only be opened from the command line");
+            return;
+        }
+
         let mut params = json!({});
         if filename.is_some() {
             params["file_path"] = json!(filename);
         };
 
-        let core = Arc::downgrade(&self.core);
-        let state = self.state.clone();
+        let view = NewView {
+            filename,
+            placement,
+            screen_size,
+            font_size,
+            on_load,
+        };
+        self.state.view_opening();
+        let dispatcher = AppDispatcher::new(self.state.get_event_proxy());
+        self.get_core().send_request("new_view", &params, move |response| {
+            dispatcher.post(CoreEvent::ViewOpened(view, response));
+        });
+    }
+
+    // Adds the view once the core has opened its buffer
+    fn view_opened(&mut self, view: NewView, response: Response) -> ActionFlow {
+        let NewView { filename, placement, screen_size, font_size, on_load } = view;
+        let view_id = match response.as_ref().map(|value| value.as_str()) {
+            Ok(Some(view_id)) => view_id.to_string(),
+            Ok(None) => {
+                self.state.view_open_finished(None);
+                self.view_failed(&placement, screen_size);
+                self.remote_view_opened(&filename, None);
+                return self.failed(&format!("Unable to open {}: unexpected response {}",
+                    filename.as_deref().unwrap_or("a new view"), response.as_ref().unwrap()));
+            },
+            Err(err) => {
+                self.state.view_open_finished(None);
+                self.view_failed(&placement, screen_size);
+                self.remote_view_opened(&filename, None);
+                return self.failed(&format!("Unable to open {}: {}",
+                    filename.as_deref().unwrap_or("a new view"), err));
+            },
+        };
 
-        self.get_core().send_request("new_view", &params, move |value| {
-            let view_id = value.clone().as_str().unwrap().to_string();
+        self.remote_view_opened(&filename, Some(&view_id));
+        // The view opened in place of another closes it, as :q would
+        let replaced = self.state.add_view(view_id.clone(), EditView::new(0, font_size, filename), placement);
+        if let Some(replaced) = replaced {
+            self.send_notification("close_view", &json!({ "view_id": replaced }));
+        }
+        let state = &mut self.state;
 
-            if let Ok(ref mut state) = state.try_lock() {
-                state.focused = Some(view_id.clone());
-                state.views.insert(view_id.clone(), EditView::new(0, font_size, filename));
+        let styles = state.get_styles();
+        let proxy = state.get_event_proxy().clone();
+        let theme = state.get_theme();
+        let read_only = state.read_only();
+        let edit_view = state.views.get_mut(&view_id).unwrap();
+        edit_view.poke(EditViewCommands::Core(Arc::downgrade(&self.core)));
+        edit_view.poke(EditViewCommands::Proxy(proxy));
+        edit_view.poke(EditViewCommands::ViewId(view_id.clone()));
+        edit_view.poke(EditViewCommands::SetStyles(styles));
+        if let Some(theme) = theme {
+            edit_view.poke(EditViewCommands::ThemeChanged(theme));
+        }
+        if read_only {
+            edit_view.poke(EditViewCommands::ReadOnly(true));
+        }
+        if on_load.len() > 0 {
+            edit_view.poke(EditViewCommands::WhenLoaded(on_load));
+        }
+        state.view_open_finished(Some(&view_id));
 
-                let styles = state.get_styles();
-                let proxy = state.get_event_proxy().clone();
-                let edit_view = state.get_focused_view();
-                edit_view.poke(EditViewCommands::Core(core));
-                edit_view.poke(EditViewCommands::Proxy(proxy));
-                edit_view.poke(EditViewCommands::ViewId(view_id));
-                edit_view.poke(EditViewCommands::SetStyles(styles));
+        state.layout_views(screen_size);
+        ActionFlow::Redraw
+    }
 
-                state.align_views_horizontally(screen_size);
-            } else {
-                println!("unable to lock state to set focused view_id with new EditView widget");
+    // Remote clients wait on the view opened for their file, rather than the file itself
+    fn remote_view_opened(&self, filename: &Option<String>, view_id: Option<&str>) {
+        if let (Some(remote), Some(filename)) = (&self.remote, filename) {
+            remote.view_opened(filename, view_id);
+        }
+    }
+
+    // Remote clients waiting on a view are told once it has closed
+    fn remote_views_closed(&self) {
+        if let Some(remote) = &self.remote {
+            let open_views: Vec<String> = self.state.views.keys().cloned().collect();
+            remote.views_closed(&open_views);
+        }
+    }
+
+    // A headless run would wait forever for a view which never opens or a quit which was refused,
+    // so it gives up
+    fn failed(&mut self, message: &str) -> ActionFlow {
+        self.report_error(message);
+        match self.state.get_event_proxy() {
+            EditorEventLoopProxy::Headless(_) => ActionFlow::Failed,
+            EditorEventLoopProxy::Window(_) => ActionFlow::Redraw,
+        }
+    }
+
+    // Errors go to stderr without a window to show them on
+    fn report_error(&mut self, message: &str) {
+        match self.state.get_event_proxy() {
+            EditorEventLoopProxy::Headless(_) => eprintln!("{}", message),
+            EditorEventLoopProxy::Window(_) => self.state.show_message(message),
+        }
+    }
+
+    /// Removes the placeholder a view failed to open in, so the session's layout holds no view
+    /// which will never be added
+    fn view_failed(&mut self, placement: &Placement, screen_size: [f32; 2]) {
+        if let Placement::Replace(placeholder) = placement {
+            if !self.state.views.contains_key(placeholder) {
+                self.state.remove_view(placeholder);
+                self.state.layout_views(screen_size);
             }
-            
-        });
-        
-        self.set_default_theme();
-    }
-
-    pub fn close_view(&self, view_id: String) -> bool {
-        if let Ok(ref mut state) = self.state.clone().try_lock() {
-            state.views.remove(&view_id);
-            if state.views.len() > 0 {
-                state.focused = Some(state.views.iter().nth(0).unwrap().0.clone());
-                return false;
-            } else {
-                return true;
+        }
+    }
+
+    /// Replaces the tab pages with those of the session, and opens each of its views. The startup
+    /// commands are given to the focused view of the active tab page.
+    pub fn restore_session(&mut self, session: Session, screen_size: [f32; 2], font_size: f32,
+        mut startup: Vec<EditViewCommands>, stdin: Option<&str>) {
+        if let Some(theme_name) = &session.theme {
+            self.set_theme(theme_name);
+        }
+        self.state.restore_tabs(&session);
+
+        let active_tab = session.active_tab;
+        for (tab_ix, tab) in session.tabs.into_iter().enumerate() {
+            for session_id in tab.layout.view_ids() {
+                let view_session = tab.views.get(&session_id).cloned().unwrap_or_default();
+                let mut filename = view_session.file_path.clone();
+
+                let mut on_load = vec![EditViewCommands::Restore(view_session)];
+                if let (Some(STDIN_FILE), Some(text)) = (filename.as_deref(), stdin) {
+                    filename = None;
+                    on_load.extend(stdin_commands(text));
+                }
+                if tab_ix == active_tab && tab.focused.as_ref() == Some(&session_id) {
+                    on_load.extend(startup.drain(..));
+                }
+                self.new_view(filename, Placement::Replace(session_id), screen_size, font_size, on_load);
             }
         }
+    }
 
-        false
+    /// Runs an Ex command in the focused view
+    pub fn run_command(&mut self, command: String) {
+        if let Some(view) = self.state.get_focused_view() {
+            view.poke(EditViewCommands::Command(command));
+        }
     }
 
-    // TODO: Derive from config somewhere?
-    fn set_default_theme(&self) {
-        self.send_notification("set_theme", &json!({ "theme_name": "Solarized (dark)" }));
-    }
-
-    // TODO: RPC this crap in structs, this is dirty
-    fn handle_cmd(&self, method: &str, params: &Value) {
-        match method {
-            "update" => self.send_view_cmd(EditViewCommands::ApplyUpdate(params["update"].clone())),
-            "scroll_to" => self.send_view_cmd(EditViewCommands::ScrollTo(params["line"].as_u64().unwrap() as usize)),
-            "config_changed" => {
-                let config = from_value::<Config>(params["changes"].clone()).unwrap();
-                self.send_view_cmd(EditViewCommands::ConfigChanged(config));
-            },
-            "available_themes" => {
-                if let Ok(ref mut state) = self.state.clone().try_lock() {
-                    let raw_themes = params["themes"].as_array();
-                    if let Some(themes) = raw_themes {
-                        let mut available_themes: Vec<String> = vec!();
-                        for t in themes.iter() {
-                            available_themes.push(t.as_str().unwrap().to_string());
-                        }
-
-                        state.set_available_themes(available_themes);
-                    }
-                }
-            },
-            "theme_changed" => {
-                let theme = from_value::<Theme>(params["theme"].clone()).unwrap();
-                self.send_view_cmd(EditViewCommands::ThemeChanged(theme.clone()));
+    pub fn save_session(&self, filename: Option<String>) {
+        let filename = filename.unwrap_or(DEFAULT_SESSION_FILE.to_string());
+        if let Err(err) = self.state.session().save(&filename) {
+            println!("{}", err);
+        }
+    }
+
+    pub fn close_view(&mut self, view_id: String) -> bool {
+        self.state.remove_view(&view_id)
+    }
+
+    fn focused_view_id(&self) -> Option<String> {
+        self.state.focused()
+    }
+
+    fn handle_window_action(&mut self, action: WindowAction, screen_size: [f32; 2]) {
+        match action {
+            WindowAction::Resize((direction, steps)) => {
+                self.state.resize_focused(direction, steps);
             },
-            "def_style" => {
-                if let Ok(style) = from_value::<Style>(params.clone()) { 
-                    if let Ok(ref mut state) = self.state.clone().try_lock() {
-                        state.define_style(style);
-                    }
+            WindowAction::Focus(motion) => self.state.focus_direction(&motion),
+            WindowAction::FocusNext => self.state.focus_cycle(true),
+            WindowAction::FocusPrevious => self.state.focus_cycle(false),
+            WindowAction::Exchange => self.state.exchange_focused(),
+            WindowAction::Rotate(motion) => self.state.rotate_focused(&motion),
+            WindowAction::Equalize => self.state.equalize_views(),
+            WindowAction::Only => {
+                for view_id in self.state.only_focused() {
+                    self.send_notification("close_view", &json!({ "view_id": view_id }));
                 }
             },
-            "available_languages" => {
-                let mut available_languages: Vec<String> = vec!();
-                let raw_languages = params["languages"].as_array();
+        }
+        self.state.layout_views(screen_size);
+    }
 
-                if let Ok(ref mut state) = self.state.clone().try_lock() {
-                    if let Some(languages) = raw_languages {
-                        for l in languages.iter() {
-                            available_languages.push(l.as_str().unwrap().to_string());
-                        }
+    fn layout_views(&mut self, screen_size: [f32; 2]) {
+        self.state.layout_views(screen_size);
+    }
 
-                        state.set_available_languages(available_languages);
-                    }
-                }
+    // Queues the active tab page to be drawn when any of it has changed, returns true if a redraw is needed
+    fn queue_draw(&mut self, renderer: &mut Renderer) -> bool {
+        let redraw = self.state.queue_draw();
+        if redraw {
+            renderer.submit(self.state.display_list());
+        }
+        redraw
+    }
+
+    /// Handles events sent to the event loop, shared by the window and headless modes
+    fn handle_event(&mut self, event: EditorEvent, screen_size: [f32; 2], font_size: f32) -> ActionFlow {
+        let flow = match event {
+            EditorEvent::Action(action) => self.handle_action(action, screen_size, font_size),
+            EditorEvent::Core(event) => self.handle_core_event(event),
+        };
+        self.update_timers(Instant::now(), false);
+        self.remote_views_closed();
+
+        flow
+    }
+
+    fn handle_core_event(&mut self, event: CoreEvent) -> ActionFlow {
+        match event {
+            CoreEvent::Notification(notification) => self.handle_notification(notification),
+            CoreEvent::Request(request, responder) => {
+                responder.respond(self.handle_request(request));
+                return ActionFlow::Continue;
+            },
+            CoreEvent::ViewOpened(view, response) => return self.view_opened(view, response),
+            CoreEvent::Error(message) => self.report_error(&message),
+            CoreEvent::Failed(message) => return self.failed(&message),
+            CoreEvent::Crashed(status) => {
+                self.crashed = Some(status);
+                return ActionFlow::Continue;
             },
-            "language_changed" => {
-                let view_id = params["view_id"].as_str().unwrap();
-                let language_id = params["language_id"].as_str().unwrap().to_string();
+            CoreEvent::Disconnected => return self.core_disconnected(),
+        }
 
-                if let Ok(ref mut state) = self.state.clone().try_lock() {
-                    if let Some(edit_view) = state.views.get_mut(view_id) {
-                        edit_view.poke(EditViewCommands::LanguageChanged(language_id));
-                    }
-                }
-            }
-            "available_plugins" => {
-                let mut available_plugins: Vec<PluginState> = vec!();
-                let raw_plugins = params["plugins"].as_array();
-
-                if let Ok(ref mut state) = self.state.clone().try_lock() {
-                    if let Some(plugins) = raw_plugins {
-                        for p in plugins.iter() {
-                            let name = p["name"].as_str().unwrap().to_string();
-                            let active = p["running"].as_bool().unwrap();
-                            
-                            available_plugins.push(PluginState {
-                                name,
-                                active,
-                                commands: vec![],
-                            });
-                        }
-
-                        state.set_available_plugins(available_plugins);
-                    }
+        ActionFlow::Redraw
+    }
+
+    fn handle_action(&mut self, action: Action, screen_size: [f32; 2], font_size: f32) -> ActionFlow {
+        match action {
+            Action::Open(filename) => {
+                let placement = match self.focused_view_id() {
+                    Some(view_id) => Placement::Replace(view_id),
+                    None => Placement::Split(SplitDirection::Horizontal),
+                };
+                self.open_new_view(filename, placement, screen_size, font_size);  
+            },
+            Action::OpenAt((filename, line)) => {
+                let placement = match self.focused_view_id() {
+                    Some(view_id) => Placement::Replace(view_id),
+                    None => Placement::Split(SplitDirection::Horizontal),
+                };
+                let go_to_line = EditViewCommands::Action(Action::Motion((Motion::First, Some(Quantity::Line(line)))));
+                self.new_view(Some(filename), placement, screen_size, font_size, vec![go_to_line]);
+            },
+            Action::ExCommand(command) => {
+                self.run_command(command);
+                return ActionFlow::Redraw;
+            },
+            Action::SaveSession(filename) => self.save_session(filename),
+            Action::Split(filename) => {
+                self.open_new_view(filename, Placement::Split(SplitDirection::Horizontal), screen_size, font_size);
+            },
+            Action::VerticalSplit(filename) => {
+                self.open_new_view(filename, Placement::Split(SplitDirection::Vertical), screen_size, font_size);
+            },
+            Action::Window(window_action) => {
+                self.handle_window_action(window_action, screen_size);
+                return ActionFlow::Redraw;
+            },
+            Action::Tab(TabAction::New(filename)) => {
+                self.open_new_view(filename, Placement::Tab, screen_size, font_size);
+            },
+            Action::Tab(tab_action) => {
+                self.handle_tab_action(tab_action, screen_size);
+                return ActionFlow::Redraw;
+            },
+            Action::Close => {
+                let view_id = self.focused_view_id();
+                if view_id.is_some() && self.close_view(view_id.unwrap()) {
+                    return ActionFlow::Exit;
                 }
+                self.layout_views(screen_size);
+                return ActionFlow::Redraw;
             },
-            "plugin_started" => {
-                let view_id = params["view_id"].as_str().unwrap().to_string();
-                let plugin = params["plugin"].as_str().unwrap().to_string();
+            Action::QuitAll => return self.quit_all(),
+            Action::ForceQuitAll => return ActionFlow::Exit,
+            Action::RestartCore => {
+                self.restart_core(screen_size, font_size);
+                return ActionFlow::Redraw;
+            },
+            _ => (),
+        }
+
+        ActionFlow::Continue
+    }
+
+    // As `:q` does for its view, quitting is refused while any buffer has changes
+    fn quit_all(&mut self) -> ActionFlow {
+        let mut modified: Vec<String> = self.state.views.values()
+            .filter(|view| !view.is_pristine())
+            .map(|view| view.name().unwrap_or_else(|| String::from("[No Name]")))
+            .collect();
+        if modified.is_empty() {
+            return ActionFlow::Exit;
+        }
+
+        modified.sort();
+        modified.dedup();
+        self.failed(&format!("No write since last change for {}, add ! to override",
+            modified.join(", ")))
+    }
 
-                if let Ok(ref mut state) = self.state.clone().try_lock() {
-                    let plugin = state.get_plugin(plugin.clone()).unwrap_or(PluginState {
-                        name: plugin,
-                        active: true,
+    fn handle_tab_action(&mut self, action: TabAction, screen_size: [f32; 2]) {
+        for view_id in self.state.handle_tab_action(action) {
+            self.send_notification("close_view", &json!({ "view_id": view_id }));
+        }
+        self.state.layout_views(screen_size);
+    }
+
+    // TODO: Derive from config somewhere?
+    fn set_default_theme(&self) {
+        self.set_theme("Solarized (dark)");
+    }
+
+    fn set_theme(&self, theme_name: &str) {
+        self.send_notification("set_theme", &json!({ "theme_name": theme_name }));
+    }
+
+    fn handle_notification(&mut self, notification: CoreNotification) {
+        let view_id = notification.view_id().map(|view_id| view_id.to_string());
+
+        match notification {
+            CoreNotification::Update(update) => {
+                self.send_view_cmd(&update.view_id, EditViewCommands::ApplyUpdate(update.update));
+            },
+            CoreNotification::ScrollTo(scroll_to) => {
+                self.send_view_cmd(&scroll_to.view_id, EditViewCommands::ScrollTo(scroll_to.line));
+            },
+            CoreNotification::ConfigChanged(config) => {
+                self.send_view_cmd(&config.view_id, EditViewCommands::ConfigChanged(config.changes));
+            },
+            CoreNotification::AvailableThemes(available) => self.state.set_available_themes(available.themes),
+            CoreNotification::ThemeChanged(theme_changed) => {
+                self.state.set_theme(theme_changed.name, theme_changed.theme);
+            },
+            CoreNotification::DefStyle(style) => self.state.define_style(style),
+            CoreNotification::AvailableLanguages(available) => {
+                self.state.set_available_languages(available.languages);
+            },
+            CoreNotification::LanguageChanged(language) => {
+                self.send_view_cmd(&language.view_id, EditViewCommands::LanguageChanged(language.language_id));
+            },
+            CoreNotification::AvailablePlugins(available) => {
+                let plugins = available.plugins.into_iter()
+                    .map(|plugin| PluginState {
+                        name: plugin.name,
+                        active: plugin.running,
                         commands: vec![],
-                    });
-                    if let Some(edit_view) = state.views.get_mut(&view_id) {
-                        edit_view.poke(EditViewCommands::PluginChanged(plugin));
-                    }
-                }
+                    })
+                    .collect();
+                self.state.set_available_plugins(plugins);
             },
-            "plugin_stopped" => {
-                let view_id = params["view_id"].as_str().unwrap().to_string();
-                let plugin = params["plugin"].as_str().unwrap().to_string();
-
-                if let Ok(ref mut state) = self.state.clone().try_lock() {
-                    if let Some(edit_view) = state.views.get_mut(&view_id) {
-                        edit_view.poke(EditViewCommands::Action(
-                                Action::Plugin(PluginAction::Stop(plugin))));
-                    }
+            CoreNotification::PluginStarted(started) => {
+                let plugin = self.state.get_plugin(started.plugin.clone()).unwrap_or(PluginState {
+                    name: started.plugin,
+                    active: true,
+                    commands: vec![],
+                });
+                self.send_view_cmd(&started.view_id, EditViewCommands::PluginChanged(plugin));
+            },
+            CoreNotification::PluginStopped(stopped) => {
+                self.send_view_cmd(&stopped.view_id, EditViewCommands::PluginStopped(stopped.plugin));
+            },
+            CoreNotification::UpdateCmds(update) => {
+                for cmd in update.cmds.iter() {
+                    println!("Command needs to be mapped to actions: {:?}", cmd);
                 }
+                self.state.set_plugin_commands(update.plugin, update.cmds);
+            },
+            CoreNotification::FindStatus(find_status) => {
+                self.send_view_cmd(&find_status.view_id, EditViewCommands::Queries(find_status.queries));
             },
-            "update_cmds" => {
-                let _view_id = params["view_id"].as_str().unwrap().to_string();
-                let _plugin = params["plugin"].as_str().unwrap().to_string();
-                let cmds = params["cmds"].as_array().unwrap();
+            CoreNotification::ReplaceStatus(replace_status) => {
+                let message = format!("Replace with: {}", replace_status.status.chars);
+                self.send_view_cmd(&replace_status.view_id, EditViewCommands::Message(message));
+            },
+            CoreNotification::ShowHover(hover) => self.state.show_message(&hover.result),
+            CoreNotification::Alert(alert) => self.state.show_message(&alert.msg),
+            CoreNotification::AddStatusItem(item) => {
+                self.send_view_cmd(&item.view_id, EditViewCommands::StatusItem(item.key, item.value));
+            },
+            CoreNotification::UpdateStatusItem(item) => {
+                self.send_view_cmd(&item.view_id, EditViewCommands::StatusItem(item.key, item.value));
+            },
+            CoreNotification::RemoveStatusItem(item) => {
+                self.send_view_cmd(&item.view_id, EditViewCommands::RemoveStatusItem(item.key));
+            },
+        }
 
-                for raw_cmd in cmds.iter() {
-                    if let Ok(cmd) = from_value::<Command>(raw_cmd.clone()) {
-                        println!("Command needs to be mapped to actions: {:?}", cmd);
-                    }
-                }
-                
+        self.state.mark_dirty(view_id.as_deref());
+    }
+
+    fn handle_request(&self, request: CoreRequest) -> Value {
+        match request {
+            CoreRequest::MeasureWidth(measurements) => {
+                let widths: Vec<Vec<f32>> = measurements.iter()
+                    .map(|measure| measure.strings.iter()
+                        .map(|text| self.state.display_list().text_width(text))
+                        .collect())
+                    .collect();
+                json!(widths)
             },
-            "find_status" => {
-                if let Ok(find_status) = from_value::<FindStatus>(params.clone()) {
-                    if let Ok(ref mut state) = self.state.clone().try_lock() {
-                        if let Some(ref mut view) = state.views.get_mut(&find_status.view_id) {
-                            view.poke(EditViewCommands::Queries(find_status.queries));
-                        }
-                    }
-                }
+        }
+    }
+
+    // Once the core has gone a headless run has nothing left to do, ie. at the end of a replay,
+    // failing if the core crashed, a window offers to start it again
+    fn core_disconnected(&mut self) -> ActionFlow {
+        let crashed = self.crashed.take();
+        let stopped = match &crashed {
+            Some(status) => format!("The core crashed, {}", status),
+            None => String::from("The core has stopped"),
+        };
+        match self.state.get_event_proxy() {
+            EditorEventLoopProxy::Headless(_) if crashed.is_some() => return self.failed(&stopped),
+            EditorEventLoopProxy::Headless(_) => return ActionFlow::Exit,
+            EditorEventLoopProxy::Window(_) if self.launch.is_some() => {
+                self.state.show_message(&format!("{}, :restart-core starts it again and reopens the files, \
+                    unsaved changes are lost", stopped));
             },
-            _ => println!("unhandled core->fe method: {}", method),
+            EditorEventLoopProxy::Window(_) => self.state.show_message(&stopped),
         }
+
+        ActionFlow::Redraw
     }
 
-    fn update_input(&self, event: WindowEvent, window_dimensions: [f32; 2]) -> bool {
-        let processed: bool;
-        if let Ok(ref mut input) = self.input.try_lock() {
-            processed = input.update(event, window_dimensions);
-        } else {
-            println!("unable to update_input from mutex lock");
-            return false
+    /// Starts a new core in place of one which has stopped, reopening the files of every view
+    fn restart_core(&mut self, screen_size: [f32; 2], font_size: f32) {
+        let launch = match &self.launch {
+            Some(_) if self.get_core().is_connected() => {
+                self.state.show_message("The core is still running");
+                return;
+            },
+            Some(launch) => launch.clone(),
+            None => {
+                self.state.show_message("The core cannot be restarted");
+                return;
+            },
+        };
+
+        let handler = AppDispatcher::new(self.state.get_event_proxy());
+        match launch.start(handler, &None) {
+            Ok(core) => *self.get_core() = core,
+            Err(err) => {
+                self.state.show_message(&err);
+                return;
+            },
         }
-        if !processed {
+
+        self.send_notification("client_started", &json!({ "config_dir": launch.config_dir }));
+        let session = self.state.session();
+        if session.theme.is_none() {
+            self.set_default_theme();
+        }
+        self.restore_session(session, screen_size, font_size, vec![], None);
+    }
+
+    fn update_input(&mut self, event: WindowEvent, window_dimensions: [f32; 2]) -> bool {
+        let typed = match event {
+            WindowEvent::KeyboardInput { .. } => true,
+            _ => false,
+        };
+        if !self.input.update(event, window_dimensions) {
             return false;
         }
-        if let Ok(ref mut state) = self.state.try_lock() {
-            state.update_from_input(self.input.clone())
-        } else {
-            println!("unable to lock state in update_input");
-            false
+        let handled = self.state.update_from_input(&self.input);
+
+        let now = Instant::now();
+        let moved_cursor = typed || self.input.mouse.pressed.is_some();
+        if self.input.window_focus_changed || (moved_cursor && self.input.window_focused) {
+            self.restart_blink(now);
+        }
+        self.update_timers(now, typed);
+
+        handled
+    }
+
+    /// When the event loop should next wake for a timer
+    fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    // Starts or stops the timers following what has just changed, typing puts off autosaving
+    // and gives a motion longer to be finished, a drag past the edge of a view scrolls on
+    fn update_timers(&mut self, now: Instant, typed: bool) {
+        if typed {
+            if let Some(delay) = self.timers.autosave() {
+                self.timers.schedule(Timer::Autosave, now, delay);
+            }
+        }
+        if !self.state.auto_scrolling() {
+            self.timers.cancel(&Timer::AutoScroll);
+        } else if !self.timers.is_scheduled(&Timer::AutoScroll) {
+            self.timers.schedule(Timer::AutoScroll, now, AUTO_SCROLL_INTERVAL);
+        }
+
+        for (view_id, view) in self.state.views.iter_mut() {
+            if view.take_new_message() {
+                self.timers.schedule(Timer::Message(view_id.clone()), now, MESSAGE_TIMEOUT);
+            }
+
+            let key_sequence = Timer::KeySequence(view_id.clone());
+            if view.mode() != Mode::Motion {
+                self.timers.cancel(&key_sequence);
+            } else if typed || !self.timers.is_scheduled(&key_sequence) {
+                self.timers.schedule(key_sequence, now, KEY_SEQUENCE_TIMEOUT);
+            }
         }
     }
+
+    // Shows the cursor, blinking again after a full interval so that it is solid while typing.
+    // It stays solid while the window is in the background.
+    fn restart_blink(&mut self, now: Instant) {
+        for view in self.state.views.values_mut() {
+            view.poke(EditViewCommands::CursorVisible(true));
+        }
+        match self.timers.cursor_blink() {
+            Some(interval) if self.input.window_focused => {
+                self.timers.schedule(Timer::CursorBlink, now, interval);
+            },
+            _ => self.timers.cancel(&Timer::CursorBlink),
+        }
+    }
+
+    /// Fires every timer whose deadline has passed, returns true if a redraw is needed
+    fn fire_timers(&mut self, now: Instant) -> bool {
+        let expired = self.timers.expired(now);
+        for timer in expired.iter() {
+            match timer {
+                Timer::CursorBlink => {
+                    if let Some(view) = self.state.get_focused_view() {
+                        let visible = !view.cursor_visible();
+                        view.poke(EditViewCommands::CursorVisible(visible));
+                    }
+                    if let Some(interval) = self.timers.cursor_blink() {
+                        self.timers.schedule(Timer::CursorBlink, now, interval);
+                    }
+                },
+                // Views closed since the timer was started are skipped, rather than held
+                // for a view which will never be added
+                Timer::Message(view_id) => if self.state.views.contains_key(view_id) {
+                    self.send_view_cmd(view_id, EditViewCommands::ClearMessage);
+                },
+                Timer::KeySequence(view_id) => if self.state.views.contains_key(view_id) {
+                    self.send_view_cmd(view_id, EditViewCommands::Action(Action::SetMode(Mode::Normal)));
+                },
+                Timer::Autosave => {
+                    for view in self.state.views.values_mut().filter(|view| view.unsaved()) {
+                        view.poke(EditViewCommands::Action(Action::Save(None)));
+                    }
+                },
+                // The pointer has not moved, so the drag goes on to the next line
+                Timer::AutoScroll => {
+                    if self.state.drag(&self.input) && self.state.auto_scrolling() {
+                        self.timers.schedule(Timer::AutoScroll, now, AUTO_SCROLL_INTERVAL);
+                    }
+                },
+            }
+        }
+
+        expired.len() > 0
+    }
 }
 
 impl Handler for AppDispatcher {
     fn notification(&self, method: &str, params: &Value) {
-        if let Some(ref app) = *self.app.lock().unwrap() {
-            app.handle_cmd(method, params);
+        match CoreNotification::decode(method, params) {
+            Ok(notification) => self.post(CoreEvent::Notification(notification)),
+            Err(err) => self.post(CoreEvent::Error(format!("unable to decode core->fe {}: {}", method, err))),
         }
     }
+
+    fn request(&self, method: &str, params: &Value, responder: Responder) {
+        match CoreRequest::decode(method, params) {
+            Ok(request) => self.post(CoreEvent::Request(request, responder)),
+            Err(err) => {
+                self.post(CoreEvent::Error(format!("unable to decode core->fe request {}: {}", method, err)));
+                responder.respond(Value::Null);
+            },
+        }
+    }
+
+    fn disconnected(&self) {
+        self.post(CoreEvent::Disconnected);
+    }
+
+    fn failed(&self, message: String) {
+        self.post(CoreEvent::Failed(message));
+    }
+
+    fn error(&self, message: String) {
+        self.post(CoreEvent::Error(message));
+    }
+
+    fn crashed(&self, status: String) {
+        self.post(CoreEvent::Crashed(status));
+    }
 }
 
 fn get_xi_dir() -> String {
@@ -325,70 +886,193 @@ fn get_xi_dir() -> String {
     xi_dir.to_str().unwrap().to_string()
 }
 
-pub fn run(title: &str, filename: Option<String>) {
+// Fills the unnamed buffer of a new view with what was read from standard input
+fn stdin_commands(text: &str) -> Vec<EditViewCommands> {
+    vec![
+        EditViewCommands::Name(STDIN_BUFFER_NAME.to_string()),
+        EditViewCommands::InsertText(text.to_string()),
+        EditViewCommands::Action(Action::Motion((Motion::First, Some(Quantity::Line(1))))),
+    ]
+}
+
+// Commands run in the first view once it has loaded, placing the cursor then running Ex commands
+fn startup_commands(options: &Options) -> Vec<EditViewCommands> {
+    let mut commands = vec![];
+    // Only the first file is opened unless they are split or tabbed
+    let ignored = options.files.len().saturating_sub(1);
+    if options.open_mode == OpenMode::First && ignored > 0 {
+        let message = format!("{} more file{} not opened, use -o, -O or -p to open every file",
+            ignored, if ignored == 1 { " was" } else { "s were" });
+        println!("{}", message);
+        commands.push(EditViewCommands::Message(message));
+    }
+    match &options.position {
+        Some(StartPosition::Line(line)) => commands.push(EditViewCommands::Action(
+            Action::Motion((Motion::First, Some(Quantity::Line(*line)))))),
+        Some(StartPosition::LastLine) => commands.push(EditViewCommands::Action(
+            Action::Motion((Motion::Last, Some(Quantity::Line(0)))))),
+        Some(StartPosition::Pattern(pattern)) => commands.push(EditViewCommands::Action(
+            Action::Search(pattern.clone()))),
+        None => (),
+    }
+    for command in options.commands.iter() {
+        commands.push(EditViewCommands::Command(command.clone()));
+    }
+    if let Some(script) = &options.script {
+        match fs::read_to_string(script) {
+            Ok(contents) => commands.extend(contents.lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('"'))
+                .map(|line| EditViewCommands::Command(line.to_string()))),
+            Err(err) => println!("unable to read script {}: {}", script, err),
+        }
+    }
+    // Headless runs quit once the commands have been run, failing like `:qa` if a buffer was left
+    // with unsaved changes. A replay ends once it has been played back.
+    if options.headless && options.replay.is_none() {
+        commands.push(EditViewCommands::Action(Action::QuitAll));
+    }
+
+    commands
+}
+
+// Connects to the core through the transport, recording the traffic when asked to
+fn connect<T: Transport + 'static>(transport: T, rx: mpsc::Receiver<Value>, handler: AppDispatcher,
+    record: &Option<String>) -> Core {
+    match record.as_ref().map(|path| Recorder::create(path, handler.clone())) {
+        Some(Ok(recorder)) => {
+            let (transport, rx) = recorder.wrap(transport, rx);
+            Core::new(transport, rx, handler)
+        },
+        Some(Err(err)) => {
+            println!("{}", err);
+            Core::new(transport, rx, handler)
+        },
+        None => Core::new(transport, rx, handler),
+    }
+}
+
+// Starts the core, opening the session, files or an empty view given on the command line
+fn start_app(options: &Options, event_proxy: EditorEventLoopProxy, screen_size: [f32; 2]) -> App {
+    let handler = AppDispatcher::new(event_proxy.clone());
+    let launch = CoreLaunch {
+        kind: CoreKind::from_arg(options.core.clone().or_else(|| env::var("XI_CORE").ok())),
+        config_dir: options.config_dir.clone().unwrap_or_else(get_xi_dir),
+    };
+    let mut app = match &options.replay {
+        Some(path) => match ReplayCore::load(path, handler.clone()) {
+            Ok((replay, rx)) => App::new(connect(replay, rx, handler.clone(), &options.record), event_proxy),
+            Err(err) => {
+                println!("{}", err);
+                process::exit(1);
+            },
+        },
+        None => match launch.start(handler.clone(), &options.record) {
+            Ok(core) => App::new(core, event_proxy).with_launch(launch.clone()),
+            Err(err) => {
+                println!("{}", err);
+                process::exit(1);
+            },
+        },
+    };
+
+    let font_size = options.font_size;
+    app.send_notification("client_started", &json!({ "config_dir": launch.config_dir }));
+    match &options.theme {
+        Some(theme_name) => app.set_theme(theme_name),
+        None => app.set_default_theme(),
+    }
+    app.state.set_read_only(options.read_only);
+    app.timers = Timers::new(options.cursor_blink, options.autosave);
+
+    let session = options.session.as_ref().and_then(|path| match Session::load(path) {
+        Ok(session) => Some(session),
+        Err(err) => {
+            println!("{}", err);
+            None
+        },
+    });
+    let startup = startup_commands(options);
+    match session {
+        Some(session) => app.restore_session(session, screen_size, font_size, startup, None),
+        None if options.files.len() > 0 => app.restore_session(
+            Session::from_files(&options.files, options.open_mode), screen_size, font_size, startup,
+            options.stdin.as_deref()),
+        None => app.new_view(None, Placement::Split(SplitDirection::Horizontal), screen_size, font_size, startup),
+    }
+
+    app
+}
+
+pub fn run(title: &str, options: Options) {
     let events_loop = events::create_event_loop();
-    let renderer = RefCell::new(Renderer::new(&events_loop, title));
+    let renderer = match Renderer::new(&events_loop, title, options.renderer) {
+        Ok(renderer) => RefCell::new(renderer),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        },
+    };
     let mut screen_dimensions: [f32; 2] = renderer.borrow().get_screen_dimensions();
+    let font_size = options.font_size;
 
-    let handler = AppDispatcher::new();
-    let (xi_peer, rx) = xi_thread::start_xi_thread();
-    let core = Core::new(xi_peer, rx, handler.clone());
+    let event_proxy = EditorEventLoopProxy::Window(events_loop.create_proxy());
+    let mut app = start_app(&options, event_proxy.clone(), screen_dimensions);
+    // Set by anything which may have changed the screen, drawn once the waiting events are handled
+    let mut draw_pending = true;
 
-    let event_proxy = events_loop.create_proxy();
-    let app = App::new(core, event_proxy);
-
-    handler.set_app(&app);
-    app.send_notification("client_started", &json!({
-        "config_dir": get_xi_dir(),
-    }));
-    app.open_new_view(filename, screen_dimensions, 20.0);
+    app.remote = if options.server {
+        let socket = options.socket.clone().unwrap_or_else(default_socket_path);
+        match RemoteServer::listen(&socket, event_proxy) {
+            Ok(server) => Some(server),
+            Err(err) => {
+                println!("{}", err);
+                None
+            },
+        }
+    } else {
+        None
+    };
 
     events_loop.run(move |event: Event<'_, EditorEvent>, _, control_flow: &mut ControlFlow| {
-        *control_flow = ControlFlow::Wait;
+        *control_flow = match app.next_deadline() {
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
+        };
 
         match event {
-            Event::UserEvent(event) => match event {
-                EditorEvent::Action(Action::Open(filename)) => {
-                    let view_id = if let Ok(state) = app.state.clone().try_lock() {
-                        state.focused.clone()
-                    } else {
-                        None
-                    };
-                    app.close_view(view_id.unwrap());
-                    app.open_new_view(filename, screen_dimensions, 20.0);  
-                },
-                EditorEvent::Action(Action::Split(filename)) => {
-                    app.open_new_view(filename, screen_dimensions, 20.0);
-                },
-                EditorEvent::Action(Action::Close) => {
-                    let view_id = if let Ok(state) = app.state.clone().try_lock() {
-                        state.focused.clone()
-                    } else {
-                        None
-                    };
-                    if view_id.is_some() && app.close_view(view_id.unwrap()) {
-                        *control_flow = ControlFlow::Exit;
-                    }
-                    if let Ok(ref mut state) = &mut app.state.clone().try_lock() {
-                        state.align_views_horizontally(screen_dimensions);
-                    }
+            // Woken for a timer, or by events arriving after a deadline has passed
+            Event::NewEvents(_) => if app.fire_timers(Instant::now()) {
+                draw_pending = true;
+            },
+            Event::UserEvent(event) => match app.handle_event(event, screen_dimensions, font_size) {
+                ActionFlow::Exit => {
+                    *control_flow = ControlFlow::Exit;
+                    return;
                 },
-                _ => (),
+                ActionFlow::Redraw | ActionFlow::Failed => draw_pending = true,
+                ActionFlow::Continue => (),
             },
             Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
                 *control_flow = ControlFlow::Exit;
             },
-            Event::MainEventsCleared => {
+            Event::LoopDestroyed => if let Some(server) = &app.remote {
+                server.shutdown();
+            },
+            // However many events changed the screen since the last frame, it is drawn once
+            Event::MainEventsCleared => if draw_pending {
+                draw_pending = false;
+                if app.queue_draw(&mut renderer.borrow_mut()) {
+                    renderer.borrow().request_redraw();
+                }
             },
             Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
                 renderer.borrow_mut().recreate_swap_chain_next_frame();
                 screen_dimensions[0] = size.width as f32;
                 screen_dimensions[1] = size.height as f32;
                 
-                if let Ok(ref mut state) = app.state.clone().try_lock() {
-                    state.align_views_horizontally(screen_dimensions);
-                    renderer.borrow().request_redraw();
-                }
+                app.layout_views(screen_dimensions);
+                draw_pending = true;
             },
             Event::RedrawRequested(_window_id) => {
                 renderer.borrow_mut().draw_frame();
@@ -401,27 +1085,7 @@ pub fn run(title: &str, filename: Option<String>) {
                 | WindowEvent::ModifiersChanged(_)
                 | WindowEvent::Focused(_) => {
                     app.update_input(event, screen_dimensions);
-
-                    if let Ok(ref mut state) = app.state.clone().try_lock() {
-                        if state.focused.is_some() {
-                            let focused = state.focused.clone().unwrap();
-                            let mut needs_redraw = false;
-                            for (id, view) in state.views.iter_mut() {
-                                let view_id = id.as_str().clone();
-                                if view.dirty() {
-                                    view.set_focused(view_id == focused); 
-                                    view.queue_draw(&mut renderer.borrow_mut());
-                                    view.set_dirty(false);
-                                    needs_redraw = true;
-                                }
-                            }
-                            if needs_redraw {
-                                renderer.borrow().request_redraw();
-                            }
-                        }
-                    } else {
-                        println!("Unable to obtain state lock to queue_draw after input update");
-                    }
+                    draw_pending = true;
                 },
                 _ => (),
             },
@@ -429,3 +1093,52 @@ pub fn run(title: &str, filename: Option<String>) {
         }
     });
 }
+
+/// Runs the `-c` commands and script against the files without a window, exiting once every view
+/// has closed or they have all been run. Exits with an error if a buffer is left unsaved.
+pub fn run_headless(options: Options) {
+    let (sender, receiver) = mpsc::channel();
+    let mut app = start_app(&options, EditorEventLoopProxy::Headless(sender), HEADLESS_SCREEN_SIZE);
+
+    loop {
+        // A replay which has gone quiet is left waiting for messages the frontend will not send,
+        // shutting down reports them
+        let event = match receiver.recv_timeout(REPLAY_IDLE_TIMEOUT) {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) if options.replay.is_none() => continue,
+            Err(_) => break,
+        };
+        match app.handle_event(event, HEADLESS_SCREEN_SIZE, options.font_size) {
+            ActionFlow::Exit => break,
+            ActionFlow::Failed => process::exit(1),
+            _ => (),
+        }
+    }
+
+    // The core handles messages in order and only exits after the last, so once it has
+    // disconnected every save has been written, and a replay has compared the last message
+    if !app.get_core().shutdown(HEADLESS_SHUTDOWN_TIMEOUT) {
+        println!("timed out waiting for the core to finish");
+    }
+    // The quit was checked before the updates for the last edits arrived, they may have left an
+    // open buffer unsaved
+    let open_views = !app.state.views.is_empty();
+    for event in receiver.try_iter() {
+        match event {
+            EditorEvent::Core(event @ CoreEvent::Failed(_)) => {
+                app.handle_core_event(event);
+                process::exit(1);
+            },
+            EditorEvent::Core(event @ CoreEvent::Notification(_)) if open_views => {
+                app.handle_core_event(event);
+            },
+            _ => (),
+        }
+    }
+    if options.replay.is_some() {
+        return;
+    }
+    if let ActionFlow::Failed = app.quit_all() {
+        process::exit(1);
+    }
+}
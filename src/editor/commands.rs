@@ -2,12 +2,19 @@ use rpc::{
     Action,
     PluginId,
     PluginAction,
+    WindowAction,
+    TabAction,
 };
 use super::ui::view::EditView;
 
 // Translates text commands into Actions
 pub fn command_to_actions(view: &EditView, command_text: String) -> Vec<Action> {
     let mut actions: Vec<Action> = vec!(); 
+    let command_text = command_text.trim_start_matches(':').to_string();
+    if let Some(substitute) = parse_substitute(&command_text) {
+        actions.push(substitute);
+        return actions;
+    }
     let args: Vec<String> = command_text.split(" ").map(|a| a.to_string()).collect();
 
     let filename = if args.len() > 1 {
@@ -21,8 +28,17 @@ pub fn command_to_actions(view: &EditView, command_text: String) -> Vec<Action>
         "e" => actions.push(Action::Open(filename)),
         "w" => actions.push(Action::Save(filename)),
         "q" => actions.push(Action::Close),
+        "q!" => actions.push(Action::ForceClose),
+        "qa" => actions.push(Action::QuitAll),
+        "qa!" => actions.push(Action::ForceQuitAll),
+        "restart-core" => actions.push(Action::RestartCore),
         "wq" => actions.extend(vec![Action::Save(filename), Action::Close]),
         "sp" => actions.push(Action::Split(filename)),
+        "mksession" => actions.push(Action::SaveSession(args.get(1).cloned())),
+        "vsp" => actions.push(Action::VerticalSplit(filename)),
+        "only" => actions.push(Action::Window(WindowAction::Only)),
+        "tabnew" => actions.push(Action::Tab(TabAction::New(args.get(1).cloned()))),
+        "tabclose" => actions.push(Action::Tab(TabAction::Close)),
         "plug" => {
             if args.len() < 3 {
                 println!("usage: plug [start|stop] <plugin_name>");
@@ -44,3 +60,22 @@ pub fn command_to_actions(view: &EditView, command_text: String) -> Vec<Action>
 
     actions
 }
+
+// Parses `%s/pattern/replacement/[flags]`, replacing every match of the regex in the buffer.
+// Only whole buffer ranges are supported, and `g` is implied as the core replaces every match.
+fn parse_substitute(command_text: &str) -> Option<Action> {
+    let rest = command_text.strip_prefix("%s")?;
+    let delimiter = rest.chars().next()?;
+    if delimiter.is_alphanumeric() || delimiter.is_whitespace() {
+        return None;
+    }
+
+    let parts: Vec<&str> = rest[delimiter.len_utf8()..].splitn(3, delimiter).collect();
+    if parts.len() < 2 || parts[0].is_empty() {
+        println!("usage: %s/pattern/replacement/[flags]");
+        return None;
+    }
+    let case_sensitive = !parts.get(2).map(|flags| flags.contains('i')).unwrap_or(false);
+
+    Some(Action::Substitute((parts[0].to_string(), parts[1].to_string(), case_sensitive)))
+}
@@ -0,0 +1,179 @@
+use std::io::{
+    BufRead,
+    BufReader,
+    Write,
+};
+use std::process::{
+    Child,
+    ChildStdin,
+    Command,
+    Stdio,
+};
+use std::sync::Mutex;
+use std::sync::mpsc::{
+    channel,
+    Receiver,
+};
+use std::thread;
+
+use serde_json::Value;
+
+use super::editor_rpc::{
+    Handler,
+    Transport,
+};
+
+/// xi-core running as a child process, speaking JSON-RPC over its stdin and stdout
+///
+/// The receiver returned by `spawn` is closed once the process has exited, whether it was asked
+/// to or crashed. A crash is reported to the handler first, as is anything the core wrote which
+/// is not JSON.
+pub struct CoreProcess {
+    stdin: Mutex<ChildStdin>,
+}
+
+impl CoreProcess {
+    pub fn spawn<H: Handler + Send + 'static>(path: &str, handler: H) -> Result<(Self, Receiver<Value>), String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| format!("unable to start core {}: {}", path, err))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(err) => {
+                        handler.error(format!("unable to read from core: {}", err));
+                        break;
+                    },
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match serde_json::from_str::<Value>(&line) {
+                    Ok(message) => if tx.send(message).is_err() {
+                        break;
+                    },
+                    Err(err) => handler.error(format!("invalid message from core: {}: {}", err, line)),
+                }
+            }
+            report_exit(child, &handler);
+        });
+
+        Ok((Self { stdin: Mutex::new(stdin) }, rx))
+    }
+}
+
+// A core which exited cleanly was asked to, the disconnect which follows says enough
+fn report_exit<H: Handler>(mut child: Child, handler: &H) {
+    match child.wait() {
+        Ok(status) if status.success() => (),
+        Ok(status) => handler.crashed(status.to_string()),
+        Err(err) => handler.error(format!("unable to wait for core: {}", err)),
+    }
+}
+
+impl Transport for CoreProcess {
+    fn send(&self, message: &Value) {
+        let mut stdin = self.stdin.lock().unwrap();
+        let line = serde_json::to_string(message).unwrap();
+        if let Err(err) = writeln!(stdin, "{}", line).and_then(|_| stdin.flush()) {
+            println!("unable to write to core: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process;
+    use std::sync::mpsc::{
+        self,
+        Sender,
+    };
+
+    use serde_json::json;
+
+    use crate::editor::tests::TIMEOUT;
+
+    // Passes on what the process reports, besides the messages it sends
+    struct Reports(Sender<String>);
+
+    impl Handler for Reports {
+        fn notification(&self, _method: &str, _params: &Value) {}
+
+        fn error(&self, message: String) {
+            self.0.send(message).unwrap();
+        }
+
+        fn crashed(&self, status: String) {
+            self.0.send(format!("crashed, {}", status)).unwrap();
+        }
+    }
+
+    fn spawn(path: &str) -> (CoreProcess, Receiver<Value>, Receiver<String>) {
+        let (tx, reports) = channel();
+        let (process, rx) = CoreProcess::spawn(path, Reports(tx)).unwrap();
+        (process, rx, reports)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn core_process_speaks_json_lines() {
+        // `cat` echoes every message back, as a core answering with what it was sent
+        let (process, rx, reports) = spawn("cat");
+        process.send(&json!({ "method": "client_started", "params": {} }));
+
+        assert_eq!(rx.recv_timeout(TIMEOUT), Ok(json!({ "method": "client_started", "params": {} })));
+        assert!(reports.try_recv().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn core_process_exit_disconnects() {
+        let (_process, rx, reports) = spawn("true");
+
+        assert_eq!(rx.recv_timeout(TIMEOUT), Err(mpsc::RecvTimeoutError::Disconnected));
+        assert!(reports.try_recv().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn core_process_crash_is_reported_before_disconnecting() {
+        let (_process, rx, reports) = spawn("false");
+
+        assert_eq!(rx.recv_timeout(TIMEOUT), Err(mpsc::RecvTimeoutError::Disconnected));
+        assert_eq!(reports.try_recv(), Ok(String::from("crashed, exit status: 1")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lines_which_are_not_json_are_reported() {
+        let path = env::temp_dir().join(format!("editor-test-{}-core.sh", process::id()));
+        fs::write(&path, "#!/bin/sh\necho not json\necho '{}'\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let (_process, rx, reports) = spawn(path.to_str().unwrap());
+
+        assert_eq!(rx.recv_timeout(TIMEOUT), Ok(json!({})));
+        let report = reports.recv_timeout(TIMEOUT).unwrap();
+        assert!(report.starts_with("invalid message from core") && report.ends_with("not json"), "{}", report);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_core_binary_is_an_error() {
+        let (tx, _reports) = channel();
+        assert!(CoreProcess::spawn("/nonexistent/xi-core", Reports(tx)).is_err());
+    }
+}
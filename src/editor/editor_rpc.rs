@@ -1,106 +1,583 @@
 pub use super::view_commands::EditViewCommands;
 
-use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
-use std::sync::mpsc::Receiver;
+use std::cmp::Reverse;
+use std::collections::{
+    BTreeMap,
+    BinaryHeap,
+};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+use std::sync::mpsc::{
+    channel,
+    Receiver,
+    RecvTimeoutError,
+    Sender,
+};
+use std::task::{
+    Context,
+    Poll,
+    Waker,
+};
 use std::thread;
+use std::time::{
+    Duration,
+    Instant,
+};
 
+use serde::Deserialize;
 use serde_json::Value;
 use serde_json::json;
 
 use super::xi_thread::XiPeer;
 
+/// How long a request waits for its response unless it is given a timeout of its own
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The frontend's connection to the core
+///
+/// Outgoing messages are queued on a channel and written by a dedicated thread, so sending never
+/// blocks on or fails because of the thread receiving from the core.
 #[derive(Clone)]
 pub struct Core {
-    state: Arc<Mutex<CoreState>>,
+    state: Arc<CoreState>,
 }
 
 struct CoreState {
-    xi_peer: XiPeer,
-    id: u64,
-    pending: BTreeMap<u64, Box<dyn Callback>>,
+    // Taken once the connection is shut down, which ends the sending thread
+    tx: Mutex<Option<Sender<Value>>>,
+    id: AtomicU64,
+    requests: Mutex<Requests>,
+    // Signalled once the core has disconnected
+    disconnected: Condvar,
+    timer: Mutex<Sender<TimerMessage>>,
+}
+
+// Requests are failed on the timer thread, which sleeps until the nearest deadline
+enum TimerMessage {
+    // A request was sent which times out at the deadline
+    Deadline(Instant),
+    // The request failed, whoever failed it may be holding locks the callback needs
+    Fail(Box<dyn Callback>, RequestError),
+}
+
+#[derive(Default)]
+struct Requests {
+    pending: BTreeMap<u64, PendingRequest>,
+    // Set once the core has disconnected, later requests fail straight away
+    closed: bool,
+}
+
+struct PendingRequest {
+    deadline: Option<Instant>,
+    callback: Box<dyn Callback>,
+}
+
+/// Why a request has no result
+#[derive(Clone, Debug, PartialEq)]
+pub enum RequestError {
+    /// The core answered with an error
+    Remote(RemoteError),
+    TimedOut,
+    Cancelled,
+    Disconnected,
+}
+
+/// The `error` object of a response
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RemoteError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+pub type Response = Result<Value, RequestError>;
+
+impl RemoteError {
+    fn from_value(error: &Value) -> Self {
+        serde_json::from_value(error.clone()).unwrap_or_else(|_| Self {
+            code: 0,
+            message: error.to_string(),
+            data: None,
+        })
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::Remote(error) => match &error.data {
+                Some(Value::String(data)) => write!(f, "{}: {}", error.message, data),
+                _ => write!(f, "{}", error.message),
+            },
+            RequestError::TimedOut => write!(f, "the core did not respond in time"),
+            RequestError::Cancelled => write!(f, "the request was cancelled"),
+            RequestError::Disconnected => write!(f, "the core has disconnected"),
+        }
+    }
 }
 
 trait Callback: Send {
-    fn call(self: Box<Self>, result: &Value);
+    fn call(self: Box<Self>, response: Response);
 }
 
 pub trait Handler {
     fn notification(&self, method: &str, params: &Value);
+
+    /// Answers a request made by the core, straight away or later through the responder
+    fn request(&self, _method: &str, _params: &Value, responder: Responder) {
+        responder.respond(Value::Null);
+    }
+
+    /// Called once the core has closed its side of the connection
+    fn disconnected(&self) {}
+
+    /// Called by a transport for what it could not pass on, such as a line which is not JSON
+    fn error(&self, _message: String) {}
+
+    /// Called by a transport when the core has crashed, before it disconnects
+    fn crashed(&self, _status: String) {}
+
+    /// Called by a transport which can no longer stand in for the core faithfully, such as a
+    /// replay which has diverged from its recording
+    fn failed(&self, _message: String) {}
+}
+
+/// Carries messages from the frontend to a core, whatever the core sends back arrives on the
+/// receiver given to `Core::new`
+pub trait Transport: Send {
+    fn send(&self, message: &Value);
+}
+
+impl Transport for XiPeer {
+    fn send(&self, message: &Value) {
+        self.send_json(message);
+    }
+}
+
+impl CoreState {
+    fn send_timer(&self, message: TimerMessage) {
+        // The timer thread only ends once the state has gone
+        let _ = self.timer.lock().unwrap().send(message);
+    }
+
+    // Calls the callback from the timer thread like any response
+    fn fail_later(&self, callback: Box<dyn Callback>, error: RequestError) {
+        self.send_timer(TimerMessage::Fail(callback, error));
+    }
+
+    fn expire_requests(&self, now: Instant) {
+        let expired: Vec<PendingRequest> = {
+            let mut requests = self.requests.lock().unwrap();
+            let ids: Vec<u64> = requests.pending.iter()
+                .filter(|(_, request)| request.deadline.map_or(false, |deadline| deadline <= now))
+                .map(|(id, _)| *id)
+                .collect();
+            ids.iter().filter_map(|id| requests.pending.remove(id)).collect()
+        };
+
+        for request in expired {
+            request.callback.call(Err(RequestError::TimedOut));
+        }
+    }
+}
+
+// Fails requests as they time out or are failed by another thread, until the core has gone
+fn run_timer(state: Weak<CoreState>, messages: Receiver<TimerMessage>) {
+    // Deadlines of answered requests are left in, expiring them finds nothing to do
+    let mut deadlines: BinaryHeap<Reverse<Instant>> = BinaryHeap::new();
+    loop {
+        let message = match deadlines.peek() {
+            Some(&Reverse(deadline)) => messages.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => messages.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match message {
+            Ok(TimerMessage::Deadline(deadline)) => deadlines.push(Reverse(deadline)),
+            Ok(TimerMessage::Fail(callback, error)) => callback.call(Err(error)),
+            Err(RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                while let Some(&Reverse(deadline)) = deadlines.peek() {
+                    if deadline > now {
+                        break;
+                    }
+                    deadlines.pop();
+                }
+                match state.upgrade() {
+                    Some(state) => state.expire_requests(now),
+                    None => break,
+                }
+            },
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+impl<F: FnOnce(Response) + Send> Callback for F {
+    fn call(self: Box<F>, response: Response) {
+        (*self)(response)
+    }
+}
+
+/// Sends the result of a request made by the core, from whichever thread has it
+pub struct Responder {
+    id: u64,
+    core: Core,
+}
+
+impl Responder {
+    pub fn respond(self, result: Value) {
+        self.core.send(json!({ "id": self.id, "result": result }));
+    }
+}
+
+/// Cancels a request which has not been answered yet
+#[derive(Clone)]
+pub struct RequestHandle {
+    id: u64,
+    state: Weak<CoreState>,
+}
+
+impl RequestHandle {
+    /// The callback is called with `RequestError::Cancelled`, false if the request was already
+    /// answered
+    pub fn cancel(&self) -> bool {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return false,
+        };
+        let request = state.requests.lock().unwrap().pending.remove(&self.id);
+
+        match request {
+            Some(request) => {
+                state.fail_later(request.callback, RequestError::Cancelled);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// A response which is yet to arrive, to be awaited or waited on
+pub struct ResponseFuture {
+    shared: Arc<(Mutex<FutureState>, Condvar)>,
+    handle: RequestHandle,
+}
+
+#[derive(Default)]
+struct FutureState {
+    response: Option<Response>,
+    waker: Option<Waker>,
+}
+
+impl ResponseFuture {
+    pub fn handle(&self) -> &RequestHandle {
+        &self.handle
+    }
+
+    /// Blocks the thread until the response arrives
+    pub fn wait(self) -> Response {
+        let (state, ready) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        loop {
+            if let Some(response) = state.response.take() {
+                return response;
+            }
+            state = ready.wait(state).unwrap();
+        }
+    }
 }
 
-impl<F: FnOnce(&Value) + Send> Callback for F {
-    fn call(self: Box<F>, result: &Value) {
-        (*self)(result)
+impl Future for ResponseFuture {
+    type Output = Response;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Response> {
+        let mut state = self.shared.0.lock().unwrap();
+        match state.response.take() {
+            Some(response) => Poll::Ready(response),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
     }
 }
 
 impl Core {
     /// Sets up a new RPC connection, also starting a thread to receive
-    /// responses.
+    /// responses, one to send messages and one to fail requests.
     ///
     /// The handler is invoked for incoming RPC notifications. Note that
     /// it must be `Send` because it is called from a dedicated thread.
-    pub fn new<H>(xi_peer: XiPeer, rx: Receiver<Value>, handler: H) -> Core
-        where H: Handler + Send + 'static
+    pub fn new<T, H>(transport: T, rx: Receiver<Value>, handler: H) -> Core
+        where T: Transport + 'static, H: Handler + Send + 'static
     {
+        let (tx, outgoing) = channel::<Value>();
+        thread::spawn(move || {
+            for message in outgoing.iter() {
+                transport.send(&message);
+            }
+        });
+
+        let (timer, timer_messages) = channel();
         let state = CoreState {
-            xi_peer,
-            id: 0,
-            pending: BTreeMap::new(),
+            tx: Mutex::new(Some(tx)),
+            id: AtomicU64::new(0),
+            requests: Default::default(),
+            disconnected: Condvar::new(),
+            timer: Mutex::new(timer),
         };
-        let core = Core { state: Arc::new(Mutex::new(state)) };
+        let core = Core { state: Arc::new(state) };
+        let timer_state = Arc::downgrade(&core.state);
+        thread::spawn(move || run_timer(timer_state, timer_messages));
+
         let rx_core_handle = core.clone();
         thread::spawn(move || {
-            while let Ok(msg) = rx.recv() {
-                if let Value::String(ref method) = msg["method"] {
+            for msg in rx.iter() {
+                if let (Value::String(ref method), Some(id)) = (&msg["method"], msg["id"].as_u64()) {
+                    let responder = Responder { id, core: rx_core_handle.clone() };
+                    handler.request(&method, &msg["params"], responder);
+                } else if let Value::String(ref method) = msg["method"] {
                     handler.notification(&method, &msg["params"]);
                 } else if let Some(id) = msg["id"].as_u64() {
-                    let mut state = rx_core_handle.state.lock().unwrap();
-                    if let Some(callback) = state.pending.remove(&id) {
-                        callback.call(&msg["result"]);
-                    } else {
-                        println!("unexpected result")
-                    }
+                    let response = match msg.get("error") {
+                        Some(error) => Err(RequestError::Remote(RemoteError::from_value(error))),
+                        None => Ok(msg["result"].clone()),
+                    };
+                    rx_core_handle.respond(id, response);
                 } else {
                     //println!("got {:?} at rpc level", msg);
                 }
             }
+            rx_core_handle.close();
+            handler.disconnected();
         });
         core
     }
 
+    // Queues a message for the core, false once the core has gone
+    fn send(&self, message: Value) -> bool {
+        let sent = match &*self.state.tx.lock().unwrap() {
+            Some(tx) => tx.send(message).is_ok(),
+            None => false,
+        };
+        if !sent {
+            println!("unable to send to the core, it has disconnected");
+        }
+        sent
+    }
+
+    fn respond(&self, id: u64, response: Response) {
+        // Taken out before calling, the callback may well send another request
+        let request = self.state.requests.lock().unwrap().pending.remove(&id);
+        match request {
+            Some(request) => request.callback.call(response),
+            None => println!("unexpected result for request {}, it may have timed out", id),
+        }
+    }
+
+    fn close(&self) {
+        let pending = {
+            let mut requests = self.state.requests.lock().unwrap();
+            requests.closed = true;
+            self.state.disconnected.notify_all();
+            std::mem::replace(&mut requests.pending, BTreeMap::new())
+        };
+
+        for (_, request) in pending {
+            request.callback.call(Err(RequestError::Disconnected));
+        }
+    }
+
+    /// Closes the connection once everything queued has been written to the core, then waits up
+    /// to `timeout` for the core to handle it and disconnect. False if it is still connected.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        // The sending thread drops the transport once the queue is empty, the core sees the end
+        // of its input after the last message
+        self.state.tx.lock().unwrap().take();
+
+        let deadline = Instant::now() + timeout;
+        let mut requests = self.state.requests.lock().unwrap();
+        while !requests.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            requests = self.state.disconnected.wait_timeout(requests, deadline - now).unwrap().0;
+        }
+        true
+    }
+
+    /// False once the core has closed its side of the connection
+    pub fn is_connected(&self) -> bool {
+        !self.state.requests.lock().unwrap().closed
+    }
+
     pub fn send_notification(&self, method: &str, params: &Value) -> bool {
-        let cmd = json!({
+        self.send(json!({
             "method": method,
             "params": params,
-        });
-        if let Ok(ref state) = self.state.try_lock() {
-            state.xi_peer.send_json(&cmd);
-            true
-        } else {
-            false
+        }))
+    }
+
+    /// Calls the callback with the response (from a different thread), giving up after
+    /// `REQUEST_TIMEOUT`.
+    pub fn send_request<F>(&self, method: &str, params: &Value, callback: F) -> RequestHandle
+        where F: FnOnce(Response) + Send + 'static
+    {
+        self.send_request_timeout(method, params, Some(REQUEST_TIMEOUT), callback)
+    }
+
+    /// Like `send_request`, a timeout of `None` waits for as long as the core is connected.
+    pub fn send_request_timeout<F>(&self, method: &str, params: &Value, timeout: Option<Duration>,
+        callback: F) -> RequestHandle
+        where F: FnOnce(Response) + Send + 'static
+    {
+        let id = self.state.id.fetch_add(1, Ordering::SeqCst);
+        let handle = RequestHandle { id, state: Arc::downgrade(&self.state) };
+        let callback: Box<dyn Callback> = Box::new(callback);
+
+        {
+            let mut requests = self.state.requests.lock().unwrap();
+            if requests.closed {
+                self.state.fail_later(callback, RequestError::Disconnected);
+                return handle;
+            }
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            requests.pending.insert(id, PendingRequest { deadline, callback });
+            if let Some(deadline) = deadline {
+                self.state.send_timer(TimerMessage::Deadline(deadline));
+            }
         }
+
+        let sent = self.send(json!({
+            "method": method,
+            "params": params,
+            "id": id,
+        }));
+        if !sent {
+            let request = self.state.requests.lock().unwrap().pending.remove(&id);
+            if let Some(request) = request {
+                self.state.fail_later(request.callback, RequestError::Disconnected);
+            }
+        }
+        handle
     }
 
-    /// Calls the callback with the result (from a different thread).
-    pub fn send_request<F>(&mut self, method: &str, params: &Value, callback: F) -> bool
-        where F: FnOnce(&Value) + Send + 'static
+    /// Sends a request whose response is awaited rather than passed to a callback
+    pub fn send_request_future(&self, method: &str, params: &Value, timeout: Option<Duration>)
+        -> ResponseFuture
     {
-        //println!("core > send_request, method = {}", method);
-        if let Ok(ref mut state) = &mut self.state.try_lock() {
-            let id = state.id;
-            let cmd = json!({
-                "method": method,
-                "params": params,
-                "id": id,
-            });
-            state.xi_peer.send_json(&cmd);
-            state.pending.insert(id, Box::new(callback));
-            state.id += 1;
-            true
-        } else {
-            false
+        let shared = Arc::new((Mutex::new(FutureState::default()), Condvar::new()));
+        let response_shared = shared.clone();
+        let handle = self.send_request_timeout(method, params, timeout, move |response| {
+            let (state, ready) = &*response_shared;
+            let mut state = state.lock().unwrap();
+            state.response = Some(response);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            ready.notify_all();
+        });
+
+        ResponseFuture { shared, handle }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
+    use serde_json::json;
+
+    use crate::editor::tests::{
+        TIMEOUT,
+        start,
+        start_native,
+        native_edit,
+    };
+
+    #[test]
+    fn request_errors_are_returned() {
+        let (fake, app, _events) = start();
+        fake.error_for("new_view", json!({ "code": 1, "message": "Permission denied" }));
+
+        let response = app.core.lock().unwrap()
+            .send_request_future("new_view", &json!({ "file_path": "/root/secret" }), Some(TIMEOUT));
+        match response.wait() {
+            Err(RequestError::Remote(error)) => assert_eq!(error.message, "Permission denied"),
+            other => panic!("expected an error, got {:?}", other),
         }
     }
+
+    #[test]
+    fn shutting_down_waits_for_the_core_to_handle_everything_queued() {
+        let path = env::temp_dir().join(format!("editor-test-{}-shutdown.txt", process::id()));
+        let (mut app, events) = start_native();
+        native_edit(&mut app, &events, "insert", json!({ "chars": "saved" }));
+        app.send_notification("save", &json!({ "view_id": "view-id-1", "file_path": path }));
+
+        assert!(app.get_core().shutdown(TIMEOUT));
+        assert!(!app.get_core().is_connected());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "saved");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shutting_down_gives_up_on_a_core_which_stays_connected() {
+        // The fake's end of the connection is held open by the test
+        let (_fake, app, _events) = start();
+
+        assert!(!app.get_core().shutdown(Duration::from_millis(50)));
+        assert!(!app.get_core().send_notification("save", &json!({})));
+    }
+
+    #[test]
+    fn unanswered_requests_time_out() {
+        let (fake, app, _events) = start();
+        fake.ignore("slow");
+
+        let response = app.core.lock().unwrap()
+            .send_request_future("slow", &json!({}), Some(Duration::from_millis(20)));
+        assert_eq!(response.wait(), Err(RequestError::TimedOut));
+    }
+
+    #[test]
+    fn requests_time_out_at_their_own_deadline() {
+        let (fake, app, _events) = start();
+        fake.ignore("slow");
+
+        let core = app.core.lock().unwrap();
+        let patient = core.send_request_future("slow", &json!({}), Some(TIMEOUT));
+        let started = Instant::now();
+        let impatient = core.send_request_future("slow", &json!({}), Some(Duration::from_millis(20)));
+        assert_eq!(impatient.wait(), Err(RequestError::TimedOut));
+        assert!(started.elapsed() < TIMEOUT / 2);
+        assert!(patient.handle().cancel());
+    }
+
+    #[test]
+    fn requests_can_be_cancelled() {
+        let (fake, app, _events) = start();
+        fake.ignore("slow");
+
+        let response = app.core.lock().unwrap().send_request_future("slow", &json!({}), None);
+        assert!(response.handle().cancel());
+        assert!(!response.handle().cancel());
+        assert_eq!(response.wait(), Err(RequestError::Cancelled));
+    }
 }
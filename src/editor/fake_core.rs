@@ -0,0 +1,122 @@
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::sync::mpsc::{
+    channel,
+    Receiver,
+    Sender,
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+
+use super::editor_rpc::Transport;
+
+/// A scripted stand-in for xi-core, recording everything the frontend sends
+///
+/// Requests are answered with the result or error set for their method, or not at all when
+/// ignored. Otherwise `new_view` hands out `view-id-1`, `view-id-2`, .. and anything else gets
+/// `null`. Notifications set with
+/// `reply_to` are sent back after a message with that method, for `edit` the method inside the
+/// edit is used, ie. `insert` or `scroll`.
+#[derive(Clone)]
+pub struct FakeCore {
+    state: Arc<Mutex<FakeState>>,
+    tx: Sender<Value>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    sent: Vec<Value>,
+    results: HashMap<String, Value>,
+    errors: HashMap<String, Value>,
+    ignored: HashSet<String>,
+    replies: HashMap<String, Vec<Value>>,
+    view_count: usize,
+}
+
+impl FakeCore {
+    /// The fake core and the receiver to hand to `Core::new` with it
+    pub fn new() -> (Self, Receiver<Value>) {
+        let (tx, rx) = channel();
+        let core = Self {
+            state: Default::default(),
+            tx,
+        };
+
+        (core, rx)
+    }
+
+    pub fn result_for(&self, method: &str, result: Value) {
+        self.state.lock().unwrap().results.insert(method.to_string(), result);
+    }
+
+    pub fn error_for(&self, method: &str, error: Value) {
+        self.state.lock().unwrap().errors.insert(method.to_string(), error);
+    }
+
+    /// Leaves requests with this method unanswered
+    pub fn ignore(&self, method: &str) {
+        self.state.lock().unwrap().ignored.insert(method.to_string());
+    }
+
+    pub fn reply_to(&self, method: &str, notifications: Vec<Value>) {
+        self.state.lock().unwrap().replies.insert(method.to_string(), notifications);
+    }
+
+    /// Sends a notification to the frontend as if the core had sent it unprompted
+    pub fn notify(&self, method: &str, params: Value) {
+        let _ = self.tx.send(json!({ "method": method, "params": params }));
+    }
+
+    /// Sends a request to the frontend, its response shows up in `sent`
+    pub fn request(&self, id: u64, method: &str, params: Value) {
+        let _ = self.tx.send(json!({ "method": method, "params": params, "id": id }));
+    }
+
+    pub fn sent(&self) -> Vec<Value> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// Everything sent since the last call, so a test can assert on the traffic of one step
+    pub fn take_sent(&self) -> Vec<Value> {
+        std::mem::replace(&mut self.state.lock().unwrap().sent, vec![])
+    }
+}
+
+impl Transport for FakeCore {
+    fn send(&self, message: &Value) {
+        let mut state = self.state.lock().unwrap();
+        state.sent.push(message.clone());
+
+        let method = message["method"].as_str().unwrap_or_default().to_string();
+        let id = message["id"].as_u64().filter(|_| !state.ignored.contains(&method));
+        if let Some(id) = id {
+            let response = match (state.results.get(&method), state.errors.get(&method)) {
+                (_, Some(error)) => json!({ "id": id, "error": error }),
+                (Some(result), _) => json!({ "id": id, "result": result }),
+                (None, None) if method == "new_view" => {
+                    state.view_count += 1;
+                    json!({ "id": id, "result": format!("view-id-{}", state.view_count) })
+                },
+                (None, None) => json!({ "id": id, "result": null }),
+            };
+            let _ = self.tx.send(response);
+        }
+
+        let method = match method.as_str() {
+            "edit" => message["params"]["method"].as_str().unwrap_or_default().to_string(),
+            _ => method,
+        };
+        for notification in state.replies.get(&method).cloned().unwrap_or_default() {
+            let _ = self.tx.send(notification);
+        }
+    }
+}
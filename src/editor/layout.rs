@@ -0,0 +1,527 @@
+use serde::{
+    Serialize,
+    Deserialize,
+};
+use rpc::{
+    Motion,
+    SplitDirection,
+};
+
+use super::state::ViewId;
+
+// Smallest share of a container a single child can be resized down to
+const MIN_RATIO: f32 = 0.1;
+// Share of a container moved by a single resize step
+const RESIZE_STEP: f32 = 0.05;
+
+/// Describes where a newly opened view should be placed in the layout
+#[derive(Debug, Clone)]
+pub enum Placement {
+    Split(SplitDirection),
+    Replace(ViewId),
+    Tab,
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewRect {
+    pub view_id: ViewId,
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Container {
+    direction: SplitDirection,
+    children: Vec<Layout>,
+    ratios: Vec<f32>,
+}
+
+/// Tiling layout tree, leaves are views and branches split their area between children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Layout {
+    View(ViewId),
+    Container(Container),
+}
+
+impl Container {
+    fn new(direction: SplitDirection, children: Vec<Layout>) -> Self {
+        let ratio = 1.0 / children.len() as f32;
+        Self {
+            direction,
+            ratios: vec![ratio; children.len()],
+            children,
+        }
+    }
+
+    fn normalize(&mut self) {
+        let total: f32 = self.ratios.iter().sum();
+        if total > 0.0 {
+            for ratio in self.ratios.iter_mut() {
+                *ratio /= total;
+            }
+        }
+    }
+
+    fn remove_child(&mut self, index: usize) {
+        let ratio = self.ratios.remove(index);
+        self.children.remove(index);
+
+        // Give the space back to the neighbouring view
+        if self.ratios.len() > 0 {
+            let neighbour = if index > 0 { index - 1 } else { 0 };
+            self.ratios[neighbour] += ratio;
+        }
+    }
+
+    fn grow_child(&mut self, index: usize, delta: f32) -> bool {
+        let count = self.children.len();
+        if count < 2 {
+            return false;
+        }
+        self.normalize();
+
+        let old_ratio = self.ratios[index];
+        let max_ratio = 1.0 - MIN_RATIO * (count - 1) as f32;
+        let new_ratio = (old_ratio + delta).max(MIN_RATIO).min(max_ratio);
+        if new_ratio == old_ratio {
+            return false;
+        }
+
+        // Siblings give up (or take) space in proportion to their current size
+        let scale = (1.0 - new_ratio) / (1.0 - old_ratio);
+        for (ix, ratio) in self.ratios.iter_mut().enumerate() {
+            if ix == index {
+                *ratio = new_ratio;
+            } else {
+                *ratio *= scale;
+            }
+        }
+
+        true
+    }
+}
+
+impl Layout {
+    /// Splits the area evenly between the views in the given direction
+    pub fn from_views(direction: SplitDirection, view_ids: Vec<ViewId>) -> Self {
+        if view_ids.len() == 1 {
+            Layout::View(view_ids[0].clone())
+        } else {
+            Layout::Container(Container::new(direction,
+                view_ids.into_iter().map(Layout::View).collect()))
+        }
+    }
+
+    pub fn is_view(&self, view_id: &ViewId) -> bool {
+        match self {
+            Layout::View(id) => id == view_id,
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, view_id: &ViewId) -> bool {
+        match self {
+            Layout::View(id) => id == view_id,
+            Layout::Container(container) => container.children.iter().any(|c| c.contains(view_id)),
+        }
+    }
+
+    /// Returns the view ids in layout order, top to bottom and left to right
+    pub fn view_ids(&self) -> Vec<ViewId> {
+        let mut ids = vec!();
+        self.collect_view_ids(&mut ids);
+
+        ids
+    }
+
+    fn collect_view_ids(&self, ids: &mut Vec<ViewId>) {
+        match self {
+            Layout::View(id) => ids.push(id.clone()),
+            Layout::Container(container) => {
+                for child in container.children.iter() {
+                    child.collect_view_ids(ids);
+                }
+            },
+        }
+    }
+
+    /// Splits the target view, placing the new view after it in the given direction
+    pub fn split(&mut self, target: &ViewId, view_id: ViewId, direction: SplitDirection) -> bool {
+        match self {
+            Layout::View(id) => {
+                if *id != *target {
+                    return false;
+                }
+                *self = Layout::Container(Container::new(direction, vec![
+                    Layout::View(target.clone()),
+                    Layout::View(view_id),
+                ]));
+                true
+            },
+            Layout::Container(container) => {
+                if let Some(ix) = container.children.iter().position(|c| c.is_view(target)) {
+                    if container.direction == direction {
+                        let ratio = container.ratios[ix] / 2.0;
+                        container.ratios[ix] = ratio;
+                        container.ratios.insert(ix + 1, ratio);
+                        container.children.insert(ix + 1, Layout::View(view_id));
+                        return true;
+                    }
+                    return container.children[ix].split(target, view_id, direction);
+                }
+
+                container.children.iter_mut()
+                    .any(|c| c.split(target, view_id.clone(), direction))
+            },
+        }
+    }
+
+    pub fn replace(&mut self, target: &ViewId, view_id: ViewId) -> bool {
+        match self {
+            Layout::View(id) => {
+                if *id != *target {
+                    return false;
+                }
+                *id = view_id;
+                true
+            },
+            Layout::Container(container) => container.children.iter_mut()
+                .any(|c| c.replace(target, view_id.clone())),
+        }
+    }
+
+    /// Removes a view from a container, collapsing containers left with a single child.
+    /// A root view cannot remove itself, this returns false.
+    pub fn remove(&mut self, view_id: &ViewId) -> bool {
+        if let Layout::Container(container) = self {
+            if let Some(ix) = container.children.iter().position(|c| c.is_view(view_id)) {
+                container.remove_child(ix);
+            } else if !container.children.iter_mut().any(|c| c.remove(view_id)) {
+                return false;
+            }
+
+            if container.children.len() == 1 {
+                let child = container.children.pop().unwrap();
+                *self = child;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Grows (or shrinks with a negative delta) the closest split containing the view in the
+    /// given direction.
+    pub fn resize(&mut self, view_id: &ViewId, direction: SplitDirection, steps: isize) -> bool {
+        if let Layout::Container(container) = self {
+            let ix = match container.children.iter().position(|c| c.contains(view_id)) {
+                Some(ix) => ix,
+                None => return false,
+            };
+            if container.children[ix].resize(view_id, direction, steps) {
+                return true;
+            }
+            if container.direction != direction {
+                return false;
+            }
+
+            container.grow_child(ix, steps as f32 * RESIZE_STEP)
+        } else {
+            false
+        }
+    }
+
+    /// Swaps the view with its next sibling, or previous when it is the last in the container.
+    /// Returns the view it was exchanged with, or the first view of a sibling split.
+    pub fn exchange(&mut self, view_id: &ViewId) -> Option<ViewId> {
+        if let Layout::Container(container) = self {
+            if let Some(ix) = container.children.iter().position(|c| c.is_view(view_id)) {
+                let other = if ix + 1 < container.children.len() { ix + 1 } else { ix - 1 };
+                let other_id = container.children[other].view_ids().into_iter().next()?;
+                container.children.swap(ix, other);
+                return Some(other_id);
+            }
+
+            container.children.iter_mut().find_map(|c| c.exchange(view_id))
+        } else {
+            None
+        }
+    }
+
+    /// Rotates the views in the container of the given view, downwards or rightwards with
+    /// `Motion::Down`, and upwards or leftwards otherwise. Only containers made up entirely of
+    /// views are rotated.
+    pub fn rotate(&mut self, view_id: &ViewId, motion: &Motion) -> bool {
+        if let Layout::Container(container) = self {
+            if container.children.iter().any(|c| c.is_view(view_id)) {
+                if container.children.iter().any(|c| !c.is_leaf()) {
+                    return false;
+                }
+                // Views keep their size as they move
+                match motion {
+                    Motion::Down | Motion::Right => {
+                        container.children.rotate_right(1);
+                        container.ratios.rotate_right(1);
+                    },
+                    _ => {
+                        container.children.rotate_left(1);
+                        container.ratios.rotate_left(1);
+                    },
+                }
+                return true;
+            }
+
+            container.children.iter_mut().any(|c| c.rotate(view_id, motion))
+        } else {
+            false
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        match self {
+            Layout::View(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn equalize(&mut self) {
+        if let Layout::Container(container) = self {
+            let ratio = 1.0 / container.children.len() as f32;
+            for r in container.ratios.iter_mut() {
+                *r = ratio;
+            }
+            for child in container.children.iter_mut() {
+                child.equalize();
+            }
+        }
+    }
+
+    /// Calculates the position and size of every view within the given area
+    pub fn arrange(&self, position: [f32; 2], size: [f32; 2]) -> Vec<ViewRect> {
+        let mut rects = vec!();
+        self.arrange_into(position, size, &mut rects);
+
+        rects
+    }
+
+    fn arrange_into(&self, position: [f32; 2], size: [f32; 2], rects: &mut Vec<ViewRect>) {
+        match self {
+            Layout::View(id) => rects.push(ViewRect {
+                view_id: id.clone(),
+                position,
+                size,
+            }),
+            Layout::Container(container) => {
+                let total: f32 = container.ratios.iter().sum();
+                let last = container.children.len() - 1;
+                let mut offset = 0.0;
+
+                for (ix, (child, ratio)) in container.children.iter().zip(container.ratios.iter()).enumerate() {
+                    let share = ratio / total;
+                    // Shares are rounded down to whole pixels, the last child takes what is left over
+                    match container.direction {
+                        SplitDirection::Horizontal => {
+                            let height = if ix == last { size[1] - offset } else { (size[1] * share).floor() };
+                            child.arrange_into([position[0], position[1] + offset], [size[0], height], rects);
+                            offset += height;
+                        },
+                        SplitDirection::Vertical => {
+                            let width = if ix == last { size[0] - offset } else { (size[0] * share).floor() };
+                            child.arrange_into([position[0] + offset, position[1]], [width, size[1]], rects);
+                            offset += width;
+                        },
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Finds the closest view in the direction of the motion, preferring the view sharing the
+/// longest edge with the current view.
+pub fn neighbour(rects: &[ViewRect], view_id: &ViewId, motion: &Motion) -> Option<ViewId> {
+    let current = rects.iter().find(|r| r.view_id == *view_id)?;
+    let (cx0, cy0) = (current.position[0], current.position[1]);
+    let (cx1, cy1) = (cx0 + current.size[0], cy0 + current.size[1]);
+
+    rects.iter()
+        .filter(|r| r.view_id != *view_id)
+        .filter_map(|r| {
+            let (x0, y0) = (r.position[0], r.position[1]);
+            let (x1, y1) = (x0 + r.size[0], y0 + r.size[1]);
+            let overlap_x = x1.min(cx1) - x0.max(cx0);
+            let overlap_y = y1.min(cy1) - y0.max(cy0);
+
+            let (distance, overlap) = match motion {
+                Motion::Left => (cx0 - x1, overlap_y),
+                Motion::Right => (x0 - cx1, overlap_y),
+                Motion::Up => (cy0 - y1, overlap_x),
+                Motion::Down => (y0 - cy1, overlap_x),
+                _ => return None,
+            };
+            // Allow for a pixel of rounding between neighbouring views
+            if overlap <= 0.0 || distance < -1.0 {
+                None
+            } else {
+                Some((distance, overlap, r))
+            }
+        })
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap()
+            .then(b.1.partial_cmp(&a.1).unwrap()))
+        .map(|(_, _, r)| r.view_id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(name: &str) -> ViewId {
+        name.to_string()
+    }
+
+    fn views(names: &[&str]) -> Vec<ViewId> {
+        names.iter().map(|name| id(name)).collect()
+    }
+
+    // The position and size of each view, in layout order
+    fn rects(layout: &Layout, size: [f32; 2]) -> Vec<(String, [f32; 4])> {
+        layout.arrange([0.0, 0.0], size).into_iter()
+            .map(|rect| (rect.view_id, [rect.position[0], rect.position[1], rect.size[0], rect.size[1]]))
+            .collect()
+    }
+
+    // The views one after the other in the given direction
+    fn splits(direction: SplitDirection, names: &[&str]) -> Layout {
+        let mut layout = Layout::View(id(names[0]));
+        for pair in names.windows(2) {
+            assert!(layout.split(&id(pair[0]), id(pair[1]), direction));
+        }
+        layout.equalize();
+        layout
+    }
+
+    // Stacks a on top of b and c side by side
+    fn nested() -> Layout {
+        let mut layout = splits(SplitDirection::Horizontal, &["a", "b"]);
+        assert!(layout.split(&id("b"), id("c"), SplitDirection::Vertical));
+        layout
+    }
+
+    #[test]
+    fn splitting_in_the_same_direction_halves_the_view() {
+        let mut layout = Layout::View(id("a"));
+        assert!(layout.split(&id("a"), id("b"), SplitDirection::Horizontal));
+        assert!(layout.split(&id("a"), id("c"), SplitDirection::Horizontal));
+        assert!(!layout.split(&id("d"), id("e"), SplitDirection::Horizontal));
+
+        assert_eq!(rects(&layout, [100.0, 400.0]), vec![
+            (id("a"), [0.0, 0.0, 100.0, 100.0]),
+            (id("c"), [0.0, 100.0, 100.0, 100.0]),
+            (id("b"), [0.0, 200.0, 100.0, 200.0]),
+        ]);
+    }
+
+    #[test]
+    fn splitting_across_nests_a_container() {
+        assert_eq!(rects(&nested(), [400.0, 400.0]), vec![
+            (id("a"), [0.0, 0.0, 400.0, 200.0]),
+            (id("b"), [0.0, 200.0, 200.0, 200.0]),
+            (id("c"), [200.0, 200.0, 200.0, 200.0]),
+        ]);
+    }
+
+    #[test]
+    fn closing_gives_the_space_to_a_neighbour_and_collapses_single_children() {
+        let mut layout = splits(SplitDirection::Horizontal, &["a", "b", "c"]);
+        assert!(layout.remove(&id("b")));
+        assert_eq!(rects(&layout, [100.0, 300.0]), vec![
+            (id("a"), [0.0, 0.0, 100.0, 200.0]),
+            (id("c"), [0.0, 200.0, 100.0, 100.0]),
+        ]);
+
+        assert!(layout.remove(&id("c")));
+        assert!(layout.is_view(&id("a")));
+        assert!(!layout.remove(&id("a")));
+
+        let mut layout = nested();
+        assert!(layout.remove(&id("c")));
+        assert_eq!(layout.view_ids(), views(&["a", "b"]));
+        assert_eq!(rects(&layout, [400.0, 400.0])[1], (id("b"), [0.0, 200.0, 400.0, 200.0]));
+    }
+
+    #[test]
+    fn nested_ratios_resize_the_closest_split() {
+        let mut layout = nested();
+        assert!(layout.resize(&id("b"), SplitDirection::Vertical, 2));
+        assert!(layout.resize(&id("c"), SplitDirection::Horizontal, -2));
+        assert!(!layout.resize(&id("a"), SplitDirection::Vertical, 1));
+
+        assert_eq!(rects(&layout, [1000.0, 1000.0]), vec![
+            (id("a"), [0.0, 0.0, 1000.0, 600.0]),
+            (id("b"), [0.0, 600.0, 600.0, 400.0]),
+            (id("c"), [600.0, 600.0, 400.0, 400.0]),
+        ]);
+
+        layout.equalize();
+        assert_eq!(rects(&layout, [400.0, 400.0]), rects(&nested(), [400.0, 400.0]));
+    }
+
+    #[test]
+    fn arranging_gives_the_remainder_to_the_last_child() {
+        let layout = splits(SplitDirection::Vertical, &["a", "b", "c"]);
+        assert_eq!(rects(&layout, [100.0, 50.0]), vec![
+            (id("a"), [0.0, 0.0, 33.0, 50.0]),
+            (id("b"), [33.0, 0.0, 33.0, 50.0]),
+            (id("c"), [66.0, 0.0, 34.0, 50.0]),
+        ]);
+    }
+
+    #[test]
+    fn rotating_moves_views_with_their_size() {
+        let mut layout = splits(SplitDirection::Horizontal, &["a", "b", "c"]);
+        layout.resize(&id("a"), SplitDirection::Horizontal, 4);
+        let heights = |layout: &Layout| -> Vec<(String, f32)> {
+            rects(layout, [100.0, 1000.0]).into_iter().map(|(id, rect)| (id, rect[3])).collect()
+        };
+        assert_eq!(heights(&layout)[0], (id("a"), 533.0));
+
+        assert!(layout.rotate(&id("a"), &Motion::Down));
+        assert_eq!(layout.view_ids(), views(&["c", "a", "b"]));
+        assert_eq!(heights(&layout)[1], (id("a"), 533.0));
+
+        assert!(layout.rotate(&id("a"), &Motion::Up));
+        assert_eq!(layout.view_ids(), views(&["a", "b", "c"]));
+        assert!(!nested().rotate(&id("a"), &Motion::Down));
+    }
+
+    #[test]
+    fn exchanging_swaps_with_the_next_view_or_split() {
+        let mut layout = splits(SplitDirection::Vertical, &["a", "b", "c"]);
+        assert_eq!(layout.exchange(&id("a")), Some(id("b")));
+        assert_eq!(layout.exchange(&id("c")), Some(id("a")));
+        assert_eq!(layout.view_ids(), views(&["b", "c", "a"]));
+
+        let mut layout = nested();
+        assert_eq!(layout.exchange(&id("a")), Some(id("b")));
+        assert_eq!(layout.view_ids(), views(&["b", "c", "a"]));
+        assert_eq!(rects(&layout, [400.0, 400.0])[2], (id("a"), [0.0, 200.0, 400.0, 200.0]));
+    }
+
+    #[test]
+    fn neighbours_share_the_longest_edge() {
+        // c is beneath b, with d beside it
+        let mut layout = splits(SplitDirection::Vertical, &["a", "b"]);
+        assert!(layout.split(&id("b"), id("c"), SplitDirection::Horizontal));
+        assert!(layout.split(&id("c"), id("d"), SplitDirection::Vertical));
+        let rects = layout.arrange([0.0, 0.0], [400.0, 400.0]);
+
+        let moves = [
+            ("a", Motion::Right, Some("b")), ("a", Motion::Left, None), ("b", Motion::Down, Some("c")),
+            ("c", Motion::Right, Some("d")), ("d", Motion::Up, Some("b")), ("d", Motion::Left, Some("c")),
+            ("c", Motion::Left, Some("a")), ("d", Motion::Down, None),
+        ];
+        for (from, motion, to) in moves.iter() {
+            assert_eq!(neighbour(&rects, &id(from), motion), to.map(id), "{} {:?}", from, motion);
+        }
+    }
+}
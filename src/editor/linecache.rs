@@ -1,11 +1,12 @@
 use std::mem;
 use std::ops::Range;
-use serde_json::Value;
-use serde::Deserialize;
 
 use rpc::{
     Annotation,
     AnnotationType,
+    UpdateLine,
+    UpdateOp,
+    ViewUpdate,
 };
 
 pub struct LineCache {
@@ -35,52 +36,44 @@ pub struct Selection {
 }
 
 impl Line {
-    pub fn from_json(v: &Value) -> Line {
-        let text = v["text"].as_str().to_owned();
-        let line_num = v["ln"].as_u64();
-        let mut cursor = Vec::new();
-        if let Some(arr) = v["cursor"].as_array() {
-            for c in arr {
-                let offset_utf8 = c.as_u64().unwrap() as usize;
-                if let Some(text) = text {
-                    cursor.push(count_utf16(&text[..offset_utf8]));
-                } else {
-                    cursor.push(offset_utf8);
-                }
-            }
-        }
+    pub fn from_update(line: &UpdateLine) -> Line {
+        let text = line.text.as_deref();
+
+        // Offsets that fall outside the text or inside a character are dropped rather than sliced
+        let cursor = line.cursor.iter()
+            .filter_map(|&offset| match text {
+                Some(text) => utf16_offset(text, offset),
+                None => Some(offset),
+            })
+            .collect();
 
         let mut styles = Vec::new();
-        if let Some(arr) = v["styles"].as_array() {
-            let mut ix: i64 = 0;
-            for triple in arr.chunks(3) {
-                let start = ix + triple[0].as_i64().unwrap();
-                let end = start + triple[1].as_i64().unwrap();
-                // TODO: count utf from last end, if <=
-                let start_utf16 = if let Some(text) = text {
-                    count_utf16(&text[..start as usize])
-                } else {
-                    start as usize
-                };
-                let end_utf16 = start_utf16 + if let Some(text) = text {
-                    count_utf16(&text[start as usize .. end as usize])
-                } else {
-                    end as usize
-                };
-                let style_id = triple[2].as_u64().unwrap() as usize;
-                let style_span = StyleSpan {
-                    style_id,
-                    range: start_utf16..end_utf16,
-                };
-                styles.push(style_span);
-                ix = end;
+        let mut ix: i64 = 0;
+        for triple in line.styles.chunks_exact(3) {
+            let start = ix + triple[0];
+            let end = start + triple[1];
+            ix = end;
+            if start < 0 || end < start || triple[2] < 0 {
+                continue;
             }
+
+            let range = match text {
+                Some(text) => match (utf16_offset(text, start as usize), utf16_offset(text, end as usize)) {
+                    (Some(start), Some(end)) => start..end,
+                    _ => continue,
+                },
+                None => start as usize..end as usize,
+            };
+            styles.push(StyleSpan {
+                style_id: triple[2] as usize,
+                range,
+            });
         }
 
-        Line { 
-            text: text.unwrap_or_default().to_string(), 
-            line_num,
-            cursor, 
+        Line {
+            text: line.text.clone().unwrap_or_default(),
+            line_num: line.ln,
+            cursor,
             styles,
         }
     }
@@ -115,85 +108,78 @@ impl LineCache {
         self.lines.push(line);
     }
 
-    pub fn apply_update(&mut self, update: &Value) {
+    pub fn apply_update(&mut self, update: &ViewUpdate) {
         let old_cache = mem::replace(self, LineCache::new());
         let mut old_iter = old_cache.lines.into_iter();
 
-        for op in update["ops"].as_array().unwrap() {
-            if let Some(op_type) = op["op"].as_str() {
-                match op_type {
-                    "ins" => {
-                        for line in op["lines"].as_array().unwrap() {
-                            let line = Line::from_json(line);
-                            self.push_opt_line(Some(line));
-                        }
-                    },
-                    "copy" => {
-                        let n = op["n"].as_u64().unwrap();
-                        for _ in 0..n {
-                            self.push_opt_line(old_iter.next().unwrap_or_default());
-                        }
-                    },
-                    "skip" => {
-                        let n = op["n"].as_u64().unwrap();
-                        for _ in 0..n {
-                            let _ = old_iter.next();
-                        }
-                    },
-                    "invalidate" => {
-                        let n = op["n"].as_u64().unwrap();
-                        for _ in 0..n {
+        for op in update.ops.iter() {
+            match op {
+                UpdateOp::Ins { lines } => {
+                    for line in lines {
+                        self.push_opt_line(Some(Line::from_update(line)));
+                    }
+                },
+                UpdateOp::Copy { n } => {
+                    for _ in 0..*n {
+                        self.push_opt_line(old_iter.next().unwrap_or_default());
+                    }
+                },
+                UpdateOp::Skip { n } => {
+                    for _ in 0..*n {
+                        let _ = old_iter.next();
+                    }
+                },
+                UpdateOp::Invalidate { n } => {
+                    for _ in 0..*n {
+                        self.push_opt_line(None);
+                    }
+                },
+                UpdateOp::Update { lines } => {
+                    for line in lines {
+                        let line = Line::from_update(line);
+                        if let Some(mut new_line) = old_iter.next().unwrap_or_default() {
+                            new_line.cursor = line.cursor;
+                            self.push_opt_line(Some(new_line));
+                        } else {
                             self.push_opt_line(None);
                         }
-                    },
-                    "update" => {
-                        for line in op["lines"].as_array().unwrap() {
-                            let line = Line::from_json(line);
-                            if let Some(mut new_line) = old_iter.next().unwrap_or_default() {
-                                new_line.cursor = line.cursor;
-                                self.push_opt_line(Some(new_line));
-                            } else {
-                                self.push_opt_line(None);
-                            }
-                        }
-                        //println!("update received: {}", update);
                     }
-                    _ => println!("unhandled update operation: {:?}", op_type)
-                }
+                },
             }
         }
 
-        for raw_anno in update["annotations"].as_array().unwrap() {
-            let mut anno = <Annotation>::deserialize(raw_anno)
-                .expect("unable to deserialize annotation");
+        for anno in update.annotations.iter() {
+            let mut anno = anno.clone();
 
             match anno.annotation_type {
                 AnnotationType::Selection => {
                     for range in anno.ranges.iter_mut() {
                         for line_num in range.start_line..range.end_line+1 {
-                            if let Some(line) = &self.lines.get(line_num) {
-                                if let Some(line) = line {
-                                    let len = line.text.len();
-                                    let (start_col, end_col) = if range.start_col > range.end_col { 
-                                        (range.end_col, range.start_col)
-                                    } else { 
-                                        (range.start_col, range.end_col)
-                                    };
-                                    let start_col = if start_col >= len { len } else { start_col };
-                                    let end_col = if end_col >= len { len } else { end_col };
-
-                                    let left_utf16 = count_utf16(&line.text[..start_col]);
-                                    let width_utf16 = count_utf16(&line.text[start_col..end_col]);
-
-                                    range.start_col = left_utf16;
-                                    range.end_col = left_utf16 + width_utf16;
-
-                                    self.selections.push(Selection {
-                                        line_num,
-                                        start_col,
-                                        end_col,
-                                    });
-                                }
+                            if let Some(Some(line)) = self.lines.get(line_num) {
+                                let len = line.text.len();
+                                let (start_col, end_col) = if range.start_col > range.end_col {
+                                    (range.end_col, range.start_col)
+                                } else {
+                                    (range.start_col, range.end_col)
+                                };
+                                let start_col = if start_col >= len { len } else { start_col };
+                                let end_col = if end_col >= len { len } else { end_col };
+
+                                let (left_utf16, right_utf16) = match (utf16_offset(&line.text, start_col),
+                                    utf16_offset(&line.text, end_col))
+                                {
+                                    (Some(left), Some(right)) => (left, right),
+                                    _ => continue,
+                                };
+
+                                range.start_col = left_utf16;
+                                range.end_col = right_utf16;
+
+                                self.selections.push(Selection {
+                                    line_num,
+                                    start_col,
+                                    end_col,
+                                });
                             }
                         }
                     }
@@ -226,6 +212,23 @@ impl LineCache {
     }
 }
 
+// The utf-16 offset of a utf-8 offset into the text, None when it is out of range or inside a character
+fn utf16_offset(text: &str, offset: usize) -> Option<usize> {
+    text.get(..offset).map(count_utf16)
+}
+
+/// The utf-8 offset of a utf-16 offset into the text, as xi expects them, clamped to its end
+pub fn utf8_offset(text: &str, offset: usize) -> usize {
+    let mut utf16_count = 0;
+    for (ix, ch) in text.char_indices() {
+        if utf16_count >= offset {
+            return ix;
+        }
+        utf16_count += ch.len_utf16();
+    }
+    text.len()
+}
+
 /// Counts the number of utf-16 code units in the given string.
 pub fn count_utf16(s: &str) -> usize {
     let mut utf16_count = 0;
@@ -235,3 +238,59 @@ pub fn count_utf16(s: &str) -> usize {
     }
     utf16_count
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::editor::tests::{
+        start,
+        attach_view,
+        notify,
+        settle,
+        line_texts,
+        update,
+    };
+
+    #[test]
+    fn update_fills_line_cache() {
+        let (_fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+
+        notify(&mut app, "update", update("view-id-1", &["fn main() {", "}"]));
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["fn main() {\n", "}\n"]);
+    }
+
+    #[test]
+    fn malformed_updates_are_dropped() {
+        let (fake, mut app, events) = start();
+        attach_view(&mut app, "view-id-1");
+        fake.notify("update", update("view-id-1", &["kept"]));
+
+        fake.notify("update", json!({ "view_id": "view-id-1", "update": { "ops": "ins" } }));
+        fake.notify("update", json!({ "view_id": "view-id-1", "update": { "ops": [{ "op": "copy" }] } }));
+        fake.notify("update", json!({ "view_id": "view-id-1", "update": { "ops": [{ "op": "rewind", "n": 1 }] } }));
+        fake.notify("update", json!({
+            "view_id": "view-id-1",
+            "update": { "ops": [], "annotations": [{ "type": "selection", "ranges": [[0, 1]] }] },
+        }));
+        settle(&mut app, &events);
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["kept\n"]);
+
+        // Offsets past the end of the line or inside a character are ignored, not sliced
+        fake.notify("update", json!({
+            "view_id": "view-id-1",
+            "update": {
+                "ops": [{ "op": "ins", "n": 1, "lines": [{ "text": "é\n", "cursor": [1, 40], "styles": [0, 1, 2, 0, 90] }] }],
+                "annotations": [{ "type": "selection", "ranges": [[0, 1, 0, 2]] }],
+            },
+        }));
+        settle(&mut app, &events);
+        let line_cache = app.state.views["view-id-1"].line_cache();
+        let line = line_cache.get_line(0).unwrap();
+        assert_eq!(line.text(), "é\n");
+        assert!(line.cursor().is_empty());
+        assert!(line.styles().is_empty());
+        assert!(line_cache.get_selections(0).is_empty());
+    }
+}
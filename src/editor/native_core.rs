@@ -0,0 +1,403 @@
+mod rope;
+mod buffer;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::sync::mpsc::{
+    channel,
+    Receiver,
+    Sender,
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+
+use rpc::{
+    GestureType,
+    ModifySelection,
+};
+use super::editor_rpc::Transport;
+use buffer::Buffer;
+
+const THEME_NAME: &str = "Native (dark)";
+
+/// A core written in Rust inside the editor, holding each view's text in a rope
+///
+/// It speaks the same protocol as xi-core for the parts the frontend uses, editing, find and
+/// replace, undo and saving, without syntax highlighting or plugins.
+#[derive(Clone)]
+pub struct NativeCore {
+    state: Arc<Mutex<NativeState>>,
+    tx: Sender<Value>,
+}
+
+#[derive(Default)]
+struct NativeState {
+    views: HashMap<String, NativeView>,
+    view_count: usize,
+    find_count: usize,
+}
+
+struct NativeView {
+    buffer: Buffer,
+    path: Option<String>,
+}
+
+impl NativeCore {
+    /// The core and the receiver to hand to `Core::new` with it
+    pub fn new() -> (Self, Receiver<Value>) {
+        let (tx, rx) = channel();
+        let core = Self {
+            state: Default::default(),
+            tx,
+        };
+
+        (core, rx)
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let _ = self.tx.send(json!({ "method": method, "params": params }));
+    }
+
+    fn respond(&self, id: &Value, response: Result<Value, String>) {
+        if id.is_null() {
+            return;
+        }
+        let _ = self.tx.send(match response {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(message) => json!({ "id": id, "error": { "code": 1, "message": message } }),
+        });
+    }
+
+    // The id of the view opened, and a warning for the user when its file was not UTF-8
+    fn new_view(&self, params: &Value) -> Result<(String, Option<String>), String> {
+        let path = params["file_path"].as_str().map(|path| path.to_string());
+        let mut warning = None;
+        let text = match &path {
+            Some(path) => match fs::read(path) {
+                Ok(bytes) => match String::from_utf8(bytes) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        warning = Some(format!("{} is not UTF-8, invalid bytes were replaced", path));
+                        String::from_utf8_lossy(err.as_bytes()).into_owned()
+                    },
+                },
+                // Like xi, a missing file is created on the first save
+                Err(ref err) if err.kind() == ErrorKind::NotFound => String::new(),
+                Err(err) => return Err(format!("unable to read {}: {}", path, err)),
+            },
+            None => String::new(),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state.view_count += 1;
+        let view_id = format!("view-id-{}", state.view_count);
+        state.views.insert(view_id.clone(), NativeView {
+            buffer: Buffer::new(&text),
+            path,
+        });
+
+        Ok((view_id, warning))
+    }
+
+    fn save(&self, params: &Value) {
+        let view_id = params["view_id"].as_str().unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        let view = match state.views.get_mut(view_id) {
+            Some(view) => view,
+            None => return,
+        };
+        if let Some(path) = params["file_path"].as_str() {
+            view.path = Some(path.to_string());
+        }
+        let path = match &view.path {
+            Some(path) => path.clone(),
+            None => return self.notify("alert", json!({ "msg": "no file name to save to" })),
+        };
+
+        match fs::write(&path, view.buffer.text().to_string()) {
+            Ok(()) => {
+                view.buffer.set_saved();
+                self.send_update(view_id, view, false);
+            },
+            Err(err) => self.notify("alert", json!({ "msg": format!("unable to save {}: {}", path, err) })),
+        }
+    }
+
+    fn edit(&self, params: &Value) {
+        let view_id = params["view_id"].as_str().unwrap_or_default();
+        let method = params["method"].as_str().unwrap_or_default();
+        let params = &params["params"];
+
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let view = match state.views.get_mut(view_id) {
+            Some(view) => view,
+            None => return println!("edit for unknown view {}", view_id),
+        };
+        let buffer = &mut view.buffer;
+
+        match method {
+            "insert" => buffer.insert(params["chars"].as_str().unwrap_or_default()),
+            "insert_newline" => buffer.insert_newline(),
+            "insert_tab" => buffer.insert_tab(),
+            "delete_backward" => buffer.delete_backward(),
+            "delete_forward" => buffer.delete_forward(),
+            "delete_word_backward" => buffer.delete_word_backward(),
+            "delete_word_forward" => buffer.delete_word_forward(),
+            "delete_to_beginning_of_line" => buffer.delete_to_beginning_of_line(),
+            "delete_to_end_of_paragraph" => buffer.delete_to_end_of_paragraph(),
+            "undo" => buffer.undo(),
+            "redo" => buffer.redo(),
+            "yank" => buffer.yank(),
+            "transpose" => buffer.transpose(),
+            "uppercase" => buffer.uppercase(),
+            "lowercase" => buffer.lowercase(),
+            "indent" => buffer.indent(),
+            "outdent" => buffer.outdent(),
+            "duplicate_line" => buffer.duplicate_line(),
+            "select_all" => buffer.select_all(),
+            "collapse_selections" => buffer.collapse_selections(),
+            "cancel_operation" => buffer.cancel_operation(),
+            "add_selection_above" => buffer.add_selection(-1),
+            "add_selection_below" => buffer.add_selection(1),
+            "selection_into_lines" => buffer.selection_into_lines(),
+            "goto_line" => buffer.goto_line(usize_param(&params["line"])),
+            "gesture" => match serde_json::from_value::<GestureType>(params["ty"].clone()) {
+                Ok(ty) => buffer.gesture(usize_param(&params["line"]), usize_param(&params["col"]), ty),
+                Err(err) => return println!("invalid gesture {}: {}", params, err),
+            },
+            // Lines scrolled into view are sent, as xi does
+            "scroll" => {
+                let first = usize_param(&params[0]);
+                buffer.set_viewport(first..std::cmp::max(first, usize_param(&params[1])));
+                return self.send_update(view_id, view, false);
+            },
+            "find" => {
+                state.find_count += 1;
+                buffer.find(
+                    state.find_count,
+                    params["chars"].as_str().unwrap_or_default(),
+                    params["case_sensitive"].as_bool().unwrap_or(false),
+                    params["regex"].as_bool().unwrap_or(false),
+                    params["whole_words"].as_bool().unwrap_or(false));
+            },
+            "find_next" | "find_previous" => buffer.find_next(
+                params["wrap_around"].as_bool().unwrap_or(false),
+                params["allow_same"].as_bool().unwrap_or(false),
+                serde_json::from_value(params["modify_selection"].clone()).unwrap_or(ModifySelection::Set),
+                method == "find_previous"),
+            "find_all" => buffer.find_all(),
+            "selection_for_find" => {
+                let chars = buffer.selected_text();
+                state.find_count += 1;
+                let case_sensitive = params["case_sensitive"].as_bool().unwrap_or(false);
+                buffer.find(state.find_count, &chars, case_sensitive, false, false);
+            },
+            "replace" => {
+                let chars = params["chars"].as_str().unwrap_or_default();
+                buffer.set_replacement(chars);
+                self.notify("replace_status", json!({
+                    "view_id": view_id,
+                    "status": { "chars": chars, "preserve_case": false },
+                }));
+            },
+            "selection_for_replace" => {
+                let chars = buffer.selected_text();
+                buffer.set_replacement(&chars);
+                self.notify("replace_status", json!({
+                    "view_id": view_id,
+                    "status": { "chars": chars, "preserve_case": false },
+                }));
+            },
+            "replace_next" => buffer.replace_next(),
+            "replace_all" => buffer.replace_all(),
+            // The clipboard is the frontend's, and the view size comes from its scroll messages
+            "copy" | "cut" | "paste" | "resize" => return,
+            method => if !buffer.movement(method) {
+                return println!("unsupported edit {}", method);
+            },
+        }
+
+        self.send_update(view_id, view, true);
+    }
+
+    // Sends what changed in the view, scrolling to the last cursor when it may have moved
+    fn send_update(&self, view_id: &str, view: &mut NativeView, scroll: bool) {
+        let update = view.buffer.update();
+        self.notify("update", json!({ "view_id": view_id, "update": update }));
+
+        if let Some(find) = view.buffer.find_query() {
+            let matches = view.buffer.matches();
+            let mut lines: Vec<usize> = matches.iter()
+                .map(|m| view.buffer.text().line_of_offset(m.start) + 1)
+                .collect();
+            lines.dedup();
+            self.notify("find_status", json!({
+                "view_id": view_id,
+                "queries": [{
+                    "id": find.id,
+                    "chars": find.chars,
+                    "case_sensitive": find.case_sensitive,
+                    "is_regex": find.is_regex,
+                    "whole_words": find.whole_words,
+                    "matches": matches.len(),
+                    "lines": lines,
+                }],
+            }));
+        }
+
+        if scroll {
+            let text = view.buffer.text();
+            let cursor = view.buffer.selections().last().unwrap().cursor;
+            let line = text.line_of_offset(cursor);
+            let col = cursor - text.offset_of_line(line);
+            self.notify("scroll_to", json!({ "view_id": view_id, "line": line, "col": col }));
+        }
+    }
+}
+
+fn usize_param(value: &Value) -> usize {
+    value.as_u64().unwrap_or(0) as usize
+}
+
+fn theme() -> Value {
+    let colour = |r: u8, g: u8, b: u8| json!({ "r": r, "g": g, "b": b, "a": 255 });
+    json!({
+        "foreground": colour(0xd8, 0xd8, 0xd8),
+        "background": colour(0x1e, 0x1e, 0x1e),
+        "caret": colour(0xf0, 0xf0, 0xf0),
+        "line_highlight": colour(0x28, 0x28, 0x28),
+        "selection": colour(0x3a, 0x4a, 0x6a),
+        "find_highlight": colour(0x8a, 0x6a, 0x20),
+        "gutter": colour(0x1e, 0x1e, 0x1e),
+        "gutter_foreground": colour(0x70, 0x70, 0x70),
+    })
+}
+
+impl Transport for NativeCore {
+    fn send(&self, message: &Value) {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = &message["id"];
+
+        match method {
+            "client_started" => {
+                self.notify("available_themes", json!({ "themes": [THEME_NAME] }));
+                self.notify("available_languages", json!({ "languages": [] }));
+            },
+            "set_theme" => self.notify("theme_changed", json!({ "name": THEME_NAME, "theme": theme() })),
+            "new_view" => {
+                match self.new_view(params) {
+                    Ok((view_id, warning)) => {
+                        self.respond(id, Ok(json!(view_id)));
+                        if let Some(warning) = warning {
+                            self.notify("alert", json!({ "msg": warning }));
+                        }
+                        let mut state = self.state.lock().unwrap();
+                        if let Some(view) = state.views.get_mut(&view_id) {
+                            self.send_update(&view_id, view, false);
+                        }
+                    },
+                    Err(err) => self.respond(id, Err(err)),
+                }
+                return;
+            },
+            "close_view" => {
+                if let Some(view_id) = params["view_id"].as_str() {
+                    self.state.lock().unwrap().views.remove(view_id);
+                }
+            },
+            "save" => self.save(params),
+            "edit" => self.edit(params),
+            _ => (),
+        }
+
+        self.respond(id, Ok(Value::Null));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use serde_json::{
+        json,
+        Value,
+    };
+
+    use crate::editor::tests::{
+        start_native,
+        native_edit,
+        line_texts,
+    };
+
+    #[test]
+    fn native_core_edits_and_undoes() {
+        let (mut app, events) = start_native();
+
+        native_edit(&mut app, &events, "insert", json!({ "chars": "hello" }));
+        native_edit(&mut app, &events, "insert_newline", json!([]));
+        native_edit(&mut app, &events, "insert", json!({ "chars": "world" }));
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["hello\n", "world"]);
+
+        native_edit(&mut app, &events, "undo", json!([]));
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["hello\n", ""]);
+
+        native_edit(&mut app, &events, "redo", json!([]));
+        native_edit(&mut app, &events, "move_to_beginning_of_document", json!([]));
+        native_edit(&mut app, &events, "delete_forward", json!([]));
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["ello\n", "world"]);
+    }
+
+    #[test]
+    fn native_core_opens_files_which_are_not_utf8() {
+        let path = env::temp_dir().join(format!("editor-test-{}-latin1.txt", process::id()));
+        fs::write(&path, b"caf\xe9\n").unwrap();
+        let (native, rx) = NativeCore::new();
+        native.send(&json!({ "id": 0, "method": "new_view", "params": { "file_path": path } }));
+        native.send(&json!({ "method": "edit", "params": { "view_id": "view-id-1", "method": "scroll", "params": [0, 10] } }));
+        fs::remove_file(&path).unwrap();
+
+        let messages: Vec<Value> = rx.try_iter().collect();
+        assert_eq!(messages[0], json!({ "id": 0, "result": "view-id-1" }));
+        assert_eq!(messages[1]["method"], json!("alert"));
+        let update = messages.iter().filter(|message| message["method"] == json!("update")).last().unwrap();
+        assert_eq!(update["params"]["update"]["ops"][0]["lines"][0]["text"], json!("caf\u{fffd}\n"));
+    }
+
+    #[test]
+    fn native_core_replaces_every_match() {
+        let (mut app, events) = start_native();
+
+        native_edit(&mut app, &events, "insert", json!({ "chars": "one two\nthree one" }));
+        native_edit(&mut app, &events, "find", json!({ "chars": "one", "case_sensitive": true, "regex": false, "whole_words": true }));
+        native_edit(&mut app, &events, "replace", json!({ "chars": "1", "preserve_case": false }));
+        native_edit(&mut app, &events, "replace_all", json!([]));
+
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["1 two\n", "three 1"]);
+    }
+
+    #[test]
+    fn native_core_edits_with_several_cursors() {
+        let (mut app, events) = start_native();
+
+        native_edit(&mut app, &events, "insert", json!({ "chars": "ab\ncd" }));
+        native_edit(&mut app, &events, "move_to_beginning_of_document", json!([]));
+        native_edit(&mut app, &events, "add_selection_below", json!([]));
+        native_edit(&mut app, &events, "insert", json!({ "chars": "> " }));
+
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["> ab\n", "> cd"]);
+    }
+}
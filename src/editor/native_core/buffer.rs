@@ -0,0 +1,1207 @@
+use std::cmp;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use regex::{
+    Regex,
+    RegexBuilder,
+};
+use serde_json::{
+    json,
+    Value,
+};
+
+use rpc::{
+    GestureType,
+    ModifySelection,
+    SelectionGranularity,
+};
+use super::rope::Rope;
+
+const TAB_SIZE: usize = 4;
+
+/// A caret when the anchor and cursor are at the same offset
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    pub anchor: usize,
+    pub cursor: usize,
+    // The column vertical movement aims for, kept while passing over shorter lines
+    column: Option<usize>,
+}
+
+impl Selection {
+    pub fn new(anchor: usize, cursor: usize) -> Self {
+        Self { anchor, cursor, column: None }
+    }
+
+    pub fn caret(offset: usize) -> Self {
+        Self::new(offset, offset)
+    }
+
+    pub fn start(&self) -> usize {
+        cmp::min(self.anchor, self.cursor)
+    }
+
+    pub fn end(&self) -> usize {
+        cmp::max(self.anchor, self.cursor)
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start()..self.end()
+    }
+
+    pub fn is_caret(&self) -> bool {
+        self.anchor == self.cursor
+    }
+}
+
+#[derive(Clone)]
+struct Revision {
+    text: Rope,
+    selections: Vec<Selection>,
+    revision: u64,
+}
+
+// Consecutive edits of the same kind are undone together, ie. a typed word
+#[derive(Clone, Copy, PartialEq)]
+enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn char_class(ch: char) -> CharClass {
+    if ch.is_whitespace() {
+        CharClass::Whitespace
+    } else if ch.is_alphanumeric() || ch == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// The query of a find, matching with a regex whether or not it was given as one
+pub struct Find {
+    pub id: usize,
+    pub chars: String,
+    pub case_sensitive: bool,
+    pub is_regex: bool,
+    pub whole_words: bool,
+    regex: Option<Regex>,
+}
+
+/// The text of a view with its selections, undo history and find state
+///
+/// Changes are gathered until `update` is called, which describes them in xi's update format
+/// relative to what was described before.
+pub struct Buffer {
+    text: Rope,
+    selections: Vec<Selection>,
+    undo: Vec<Revision>,
+    redo: Vec<Revision>,
+    last_edit: EditKind,
+    revision: u64,
+    next_revision: u64,
+    saved_revision: u64,
+    find: Option<Find>,
+    replacement: Option<String>,
+    kill_ring: String,
+    // The granularity and range of the selection a drag started from
+    drag: Option<(SelectionGranularity, Range<usize>)>,
+    viewport: Range<usize>,
+    // What the frontend's line cache holds, and which of its lines have their text
+    sent_lines: usize,
+    sent_valid: Range<usize>,
+    sent_cursor_lines: BTreeSet<usize>,
+    // The lines left untouched at the start and the end of the text since the last update
+    unchanged: Option<(usize, usize)>,
+}
+
+impl Buffer {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: Rope::from(text),
+            selections: vec![Selection::caret(0)],
+            undo: vec![],
+            redo: vec![],
+            last_edit: EditKind::Other,
+            revision: 0,
+            next_revision: 1,
+            saved_revision: 0,
+            find: None,
+            replacement: None,
+            kill_ring: String::new(),
+            drag: None,
+            viewport: 0..0,
+            sent_lines: 0,
+            sent_valid: 0..0,
+            sent_cursor_lines: BTreeSet::new(),
+            unchanged: Some((0, 0)),
+        }
+    }
+
+    pub fn text(&self) -> &Rope {
+        &self.text
+    }
+
+    pub fn selections(&self) -> &[Selection] {
+        &self.selections
+    }
+
+    pub fn is_pristine(&self) -> bool {
+        self.revision == self.saved_revision
+    }
+
+    pub fn set_saved(&mut self) {
+        self.saved_revision = self.revision;
+    }
+
+    pub fn find_query(&self) -> Option<&Find> {
+        self.find.as_ref()
+    }
+
+    pub fn set_viewport(&mut self, viewport: Range<usize>) {
+        self.viewport = viewport;
+    }
+
+    fn mark_changed(&mut self, first_line: usize, lines_after: usize) {
+        self.unchanged = Some(match self.unchanged {
+            Some((before, after)) => (cmp::min(before, first_line), cmp::min(after, lines_after)),
+            None => (first_line, lines_after),
+        });
+    }
+
+    // Replaces the selections, merging any which overlap
+    fn set_selections(&mut self, mut selections: Vec<Selection>) {
+        let len = self.text.len();
+        for selection in selections.iter_mut() {
+            selection.anchor = cmp::min(selection.anchor, len);
+            selection.cursor = cmp::min(selection.cursor, len);
+        }
+        selections.sort_by_key(|selection| (selection.start(), selection.end()));
+
+        let mut merged: Vec<Selection> = Vec::with_capacity(selections.len());
+        for selection in selections {
+            match merged.last_mut() {
+                Some(last) if selection.start() < last.end()
+                    || (selection.start() == last.start() && selection.end() == last.end()) => {
+                    let (start, end) = (last.start(), cmp::max(last.end(), selection.end()));
+                    *last = if last.anchor <= last.cursor {
+                        Selection::new(start, end)
+                    } else {
+                        Selection::new(end, start)
+                    };
+                },
+                _ => merged.push(selection),
+            }
+        }
+        if merged.is_empty() {
+            merged.push(Selection::caret(0));
+        }
+
+        self.selections = merged;
+    }
+
+    fn snapshot(&self) -> Revision {
+        Revision {
+            text: self.text.clone(),
+            selections: self.selections.clone(),
+            revision: self.revision,
+        }
+    }
+
+    fn restore(&mut self, revision: Revision) {
+        self.text = revision.text;
+        self.selections = revision.selections;
+        self.revision = revision.revision;
+        self.last_edit = EditKind::Other;
+        self.mark_changed(0, 0);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(revision) = self.undo.pop() {
+            self.redo.push(self.snapshot());
+            self.restore(revision);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(revision) = self.redo.pop() {
+            self.undo.push(self.snapshot());
+            self.restore(revision);
+        }
+    }
+
+    /// Replaces each range with its text, leaving a caret after each insertion unless the
+    /// selections are kept, in which case they are moved along with the text around them
+    fn edit(&mut self, kind: EditKind, mut edits: Vec<(Range<usize>, String)>, keep_selections: bool) {
+        edits.sort_by_key(|(range, _)| range.start);
+        let mut merged: Vec<(Range<usize>, String)> = Vec::with_capacity(edits.len());
+        for (range, text) in edits {
+            match merged.last_mut() {
+                Some((last, last_text)) if range.start < last.end => {
+                    last.end = cmp::max(last.end, range.end);
+                    last_text.push_str(&text);
+                },
+                _ => merged.push((range, text)),
+            }
+        }
+        if merged.iter().all(|(range, text)| range.start == range.end && text.is_empty()) {
+            return;
+        }
+
+        if kind == EditKind::Other || kind != self.last_edit {
+            self.undo.push(self.snapshot());
+        }
+        self.redo.clear();
+
+        let first_line = self.text.line_of_offset(merged[0].0.start);
+        let last_line = self.text.line_of_offset(merged[merged.len() - 1].0.end);
+        let lines_after = self.text.line_count() - last_line - 1;
+
+        let mut text = self.text.clone();
+        for (range, insert) in merged.iter().rev() {
+            text = text.edit(range.clone(), insert);
+        }
+
+        let selections = if keep_selections {
+            self.selections.iter()
+                .map(|selection| Selection::new(
+                    map_offset(selection.anchor, &merged), map_offset(selection.cursor, &merged)))
+                .collect()
+        } else {
+            let mut delta: isize = 0;
+            merged.iter()
+                .map(|(range, insert)| {
+                    let start = (range.start as isize + delta) as usize;
+                    delta += insert.len() as isize - (range.end - range.start) as isize;
+                    Selection::caret(start + insert.len())
+                })
+                .collect()
+        };
+
+        self.text = text;
+        self.revision = self.next_revision;
+        self.next_revision += 1;
+        self.mark_changed(first_line, lines_after);
+        self.set_selections(selections);
+        self.last_edit = kind;
+    }
+
+    fn replace_selections<F: Fn(&Self, &Selection) -> Option<(Range<usize>, String)>>(&mut self, kind: EditKind, f: F) {
+        let edits = self.selections.iter().filter_map(|selection| f(self, selection)).collect();
+        self.edit(kind, edits, false);
+    }
+
+    pub fn insert(&mut self, chars: &str) {
+        let chars = chars.to_string();
+        self.replace_selections(EditKind::Insert, |_, selection| Some((selection.range(), chars.clone())));
+    }
+
+    pub fn insert_newline(&mut self) {
+        self.replace_selections(EditKind::Other, |_, selection| Some((selection.range(), "\n".to_string())));
+    }
+
+    pub fn insert_tab(&mut self) {
+        let multi_line = self.selections.iter()
+            .any(|selection| self.text.line_of_offset(selection.start()) != self.text.line_of_offset(selection.end()));
+        if multi_line {
+            return self.indent();
+        }
+
+        self.replace_selections(EditKind::Insert, |buffer, selection| {
+            let column = buffer.column(selection.start());
+            Some((selection.range(), " ".repeat(TAB_SIZE - column % TAB_SIZE)))
+        });
+    }
+
+    pub fn yank(&mut self) {
+        let chars = self.kill_ring.clone();
+        self.replace_selections(EditKind::Other, |_, selection| Some((selection.range(), chars.clone())));
+    }
+
+    fn delete_to<F: Fn(&Self, usize) -> usize>(&mut self, target: F) {
+        self.replace_selections(EditKind::Delete, |buffer, selection| {
+            if !selection.is_caret() {
+                return Some((selection.range(), String::new()));
+            }
+            let target = target(buffer, selection.cursor);
+            let range = cmp::min(target, selection.cursor)..cmp::max(target, selection.cursor);
+            Some((range, String::new()))
+        });
+    }
+
+    pub fn delete_backward(&mut self) {
+        self.delete_to(|buffer, offset| buffer.text.prev_offset(offset).unwrap_or(0));
+    }
+
+    pub fn delete_forward(&mut self) {
+        self.delete_to(|buffer, offset| buffer.text.next_offset(offset).unwrap_or(offset));
+    }
+
+    pub fn delete_word_backward(&mut self) {
+        self.delete_to(|buffer, offset| buffer.word_left(offset));
+    }
+
+    pub fn delete_word_forward(&mut self) {
+        self.delete_to(|buffer, offset| buffer.word_right(offset));
+    }
+
+    pub fn delete_to_beginning_of_line(&mut self) {
+        self.delete_to(|buffer, offset| buffer.line_start(offset));
+    }
+
+    /// Deletes to the end of the line, or the newline when already there, into the kill ring
+    pub fn delete_to_end_of_paragraph(&mut self) {
+        let killed: Vec<String> = self.selections.iter()
+            .map(|selection| {
+                let end = self.line_end(selection.cursor);
+                let end = if end == selection.cursor { self.text.next_offset(end).unwrap_or(end) } else { end };
+                self.text.slice(selection.cursor..end)
+            })
+            .collect();
+        self.kill_ring = killed.concat();
+
+        self.delete_to(|buffer, offset| {
+            let end = buffer.line_end(offset);
+            if end == offset { buffer.text.next_offset(end).unwrap_or(end) } else { end }
+        });
+    }
+
+    // The lines covered by the selections, a selection ending at the start of a line leaves it out
+    fn selected_lines(&self) -> BTreeSet<usize> {
+        let mut lines = BTreeSet::new();
+        for selection in self.selections.iter() {
+            let first = self.text.line_of_offset(selection.start());
+            let mut last = self.text.line_of_offset(selection.end());
+            if last > first && self.text.offset_of_line(last) == selection.end() {
+                last -= 1;
+            }
+            lines.extend(first..=last);
+        }
+        lines
+    }
+
+    pub fn indent(&mut self) {
+        let edits = self.selected_lines().into_iter()
+            .map(|line| {
+                let start = self.text.offset_of_line(line);
+                (start..start, " ".repeat(TAB_SIZE))
+            })
+            .collect();
+        self.edit(EditKind::Other, edits, true);
+    }
+
+    pub fn outdent(&mut self) {
+        let edits = self.selected_lines().into_iter()
+            .filter_map(|line| {
+                let range = self.text.line_range(line);
+                let text = self.text.slice(range.clone());
+                let width = if text.starts_with('\t') {
+                    1
+                } else {
+                    text.chars().take(TAB_SIZE).take_while(|ch| *ch == ' ').count()
+                };
+                match width {
+                    0 => None,
+                    _ => Some((range.start..range.start + width, String::new())),
+                }
+            })
+            .collect();
+        self.edit(EditKind::Other, edits, true);
+    }
+
+    pub fn duplicate_line(&mut self) {
+        let edits = self.selected_lines().into_iter()
+            .map(|line| {
+                let text = self.text.line(line);
+                let next = self.text.offset_of_line(line + 1);
+                if text.ends_with('\n') {
+                    (next..next, text)
+                } else {
+                    (next..next, format!("\n{}", text))
+                }
+            })
+            .collect();
+        self.edit(EditKind::Other, edits, true);
+    }
+
+    pub fn transpose(&mut self) {
+        self.replace_selections(EditKind::Other, |buffer, selection| {
+            if !selection.is_caret() {
+                return None;
+            }
+            let at_end = match buffer.text.char_at(selection.cursor) {
+                None | Some('\n') => true,
+                _ => false,
+            };
+            let middle = if at_end { buffer.text.prev_offset(selection.cursor)? } else { selection.cursor };
+            let start = buffer.text.prev_offset(middle)?;
+            let end = buffer.text.next_offset(middle)?;
+            let first = buffer.text.slice(start..middle);
+            let second = buffer.text.slice(middle..end);
+            if first == "\n" || second == "\n" {
+                return None;
+            }
+            Some((start..end, format!("{}{}", second, first)))
+        });
+    }
+
+    fn change_case(&mut self, upper: bool) {
+        let edits = self.selections.iter()
+            .filter(|selection| !selection.is_caret())
+            .map(|selection| {
+                let text = self.text.slice(selection.range());
+                let text = if upper { text.to_uppercase() } else { text.to_lowercase() };
+                (selection.range(), text)
+            })
+            .collect();
+        self.edit(EditKind::Other, edits, true);
+    }
+
+    pub fn uppercase(&mut self) {
+        self.change_case(true);
+    }
+
+    pub fn lowercase(&mut self) {
+        self.change_case(false);
+    }
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.text.offset_of_line(self.text.line_of_offset(offset))
+    }
+
+    fn line_end(&self, offset: usize) -> usize {
+        self.text.line_range(self.text.line_of_offset(offset)).end
+    }
+
+    // Columns are counted in chars
+    fn column(&self, offset: usize) -> usize {
+        self.text.slice(self.line_start(offset)..offset).chars().count()
+    }
+
+    fn offset_at_column(&self, line: usize, column: usize) -> usize {
+        let range = self.text.line_range(line);
+        let text = self.text.slice(range.clone());
+        range.start + text.char_indices().nth(column).map(|(ix, _)| ix).unwrap_or(text.len())
+    }
+
+    /// The offset of a line and a byte column, moved back onto a char boundary
+    pub fn offset_of_position(&self, line: usize, col: usize) -> usize {
+        let line = cmp::min(line, self.text.line_count() - 1);
+        let range = self.text.line_range(line);
+        let text = self.text.slice(range.clone());
+        let mut col = cmp::min(col, text.len());
+        while !text.is_char_boundary(col) {
+            col -= 1;
+        }
+        range.start + col
+    }
+
+    fn word_left(&self, offset: usize) -> usize {
+        let mut offset = offset;
+        while let Some(ch) = self.text.char_before(offset) {
+            if ch == '\n' || !ch.is_whitespace() {
+                break;
+            }
+            offset -= ch.len_utf8();
+        }
+        match self.text.char_before(offset) {
+            Some('\n') => return offset - 1,
+            None => return offset,
+            Some(ch) => {
+                let class = char_class(ch);
+                while let Some(ch) = self.text.char_before(offset) {
+                    if ch == '\n' || char_class(ch) != class {
+                        break;
+                    }
+                    offset -= ch.len_utf8();
+                }
+            },
+        }
+        offset
+    }
+
+    fn word_right(&self, offset: usize) -> usize {
+        let mut offset = offset;
+        while let Some(ch) = self.text.char_at(offset) {
+            if ch == '\n' || !ch.is_whitespace() {
+                break;
+            }
+            offset += ch.len_utf8();
+        }
+        match self.text.char_at(offset) {
+            Some('\n') => return offset + 1,
+            None => return offset,
+            Some(ch) => {
+                let class = char_class(ch);
+                while let Some(ch) = self.text.char_at(offset) {
+                    if ch == '\n' || char_class(ch) != class {
+                        break;
+                    }
+                    offset += ch.len_utf8();
+                }
+            },
+        }
+        offset
+    }
+
+    fn word_range(&self, offset: usize) -> Range<usize> {
+        let class = match self.text.char_at(offset).or_else(|| self.text.char_before(offset)) {
+            Some('\n') | None => return offset..offset,
+            Some(ch) => char_class(ch),
+        };
+        let same = |ch: char| ch != '\n' && char_class(ch) == class;
+
+        let mut start = offset;
+        while let Some(ch) = self.text.char_before(start).filter(|ch| same(*ch)) {
+            start -= ch.len_utf8();
+        }
+        let mut end = offset;
+        while let Some(ch) = self.text.char_at(end).filter(|ch| same(*ch)) {
+            end += ch.len_utf8();
+        }
+        start..end
+    }
+
+    fn unit_range(&self, offset: usize, granularity: SelectionGranularity) -> Range<usize> {
+        match granularity {
+            SelectionGranularity::Point => offset..offset,
+            SelectionGranularity::Word => self.word_range(offset),
+            SelectionGranularity::Line => {
+                let line = self.text.line_of_offset(offset);
+                self.text.offset_of_line(line)..self.text.offset_of_line(line + 1)
+            },
+        }
+    }
+
+    // Where a vertical move of `lines` lands, with the column it was aiming for
+    fn vertical(&self, selection: &Selection, lines: isize) -> (usize, usize) {
+        let column = selection.column.unwrap_or_else(|| self.column(selection.cursor));
+        let line = self.text.line_of_offset(selection.cursor) as isize + lines;
+        if line < 0 {
+            return (0, column);
+        }
+        if line as usize >= self.text.line_count() {
+            return (self.text.len(), column);
+        }
+        (self.offset_at_column(line as usize, column), column)
+    }
+
+    fn move_cursors<F: Fn(&Self, &Selection) -> usize>(&mut self, modify: bool, target: F) {
+        let selections = self.selections.iter()
+            .map(|selection| {
+                let cursor = target(self, selection);
+                Selection::new(if modify { selection.anchor } else { cursor }, cursor)
+            })
+            .collect();
+        self.set_selections(selections);
+        self.last_edit = EditKind::Other;
+    }
+
+    fn move_vertical(&mut self, lines: isize, modify: bool) {
+        let selections = self.selections.iter()
+            .map(|selection| {
+                let (cursor, column) = self.vertical(selection, lines);
+                Selection {
+                    anchor: if modify { selection.anchor } else { cursor },
+                    cursor,
+                    column: Some(column),
+                }
+            })
+            .collect();
+        self.set_selections(selections);
+        self.last_edit = EditKind::Other;
+    }
+
+    fn page_lines(&self) -> isize {
+        cmp::max(self.viewport.end.saturating_sub(self.viewport.start), 2) as isize - 1
+    }
+
+    /// Applies a movement or selection command without parameters, false if it is not one
+    pub fn movement(&mut self, method: &str) -> bool {
+        let (method, modify) = match method.strip_suffix("_and_modify_selection") {
+            Some(method) => (method, true),
+            None => (method, false),
+        };
+
+        match method {
+            // Without modifying, a selection collapses to the side moved towards
+            "move_left" => self.move_cursors(modify, |buffer, selection| if selection.is_caret() || modify {
+                buffer.text.prev_offset(selection.cursor).unwrap_or(0)
+            } else {
+                selection.start()
+            }),
+            "move_right" => self.move_cursors(modify, |buffer, selection| if selection.is_caret() || modify {
+                buffer.text.next_offset(selection.cursor).unwrap_or(selection.cursor)
+            } else {
+                selection.end()
+            }),
+            "move_up" => self.move_vertical(-1, modify),
+            "move_down" => self.move_vertical(1, modify),
+            "scroll_page_up" | "page_up" => self.move_vertical(-self.page_lines(), modify),
+            "scroll_page_down" | "page_down" => self.move_vertical(self.page_lines(), modify),
+            "move_word_left" => self.move_cursors(modify, |buffer, selection| buffer.word_left(selection.cursor)),
+            "move_word_right" => self.move_cursors(modify, |buffer, selection| buffer.word_right(selection.cursor)),
+            "move_to_left_end_of_line" => self.move_cursors(modify, |buffer, selection| buffer.line_start(selection.cursor)),
+            "move_to_right_end_of_line" => self.move_cursors(modify, |buffer, selection| buffer.line_end(selection.cursor)),
+            "move_to_beginning_of_document" => self.move_cursors(modify, |_, _| 0),
+            "move_to_end_of_document" => self.move_cursors(modify, |buffer, _| buffer.text.len()),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn goto_line(&mut self, line: usize) {
+        let offset = self.text.offset_of_line(cmp::min(line, self.text.line_count() - 1));
+        self.set_selections(vec![Selection::caret(offset)]);
+        self.last_edit = EditKind::Other;
+    }
+
+    pub fn select_all(&mut self) {
+        self.set_selections(vec![Selection::new(0, self.text.len())]);
+    }
+
+    pub fn collapse_selections(&mut self) {
+        let first = self.selections[0];
+        self.set_selections(vec![first]);
+    }
+
+    /// Drops all but the last selection, then the find, then the selection itself
+    pub fn cancel_operation(&mut self) {
+        let last = *self.selections.last().unwrap();
+        if self.selections.len() > 1 {
+            self.set_selections(vec![last]);
+        } else if self.find.is_some() {
+            self.find = None;
+        } else {
+            self.set_selections(vec![Selection::caret(last.cursor)]);
+        }
+    }
+
+    pub fn add_selection(&mut self, lines: isize) {
+        let selection = if lines < 0 { self.selections[0] } else { *self.selections.last().unwrap() };
+        let (cursor, column) = self.vertical(&selection, lines);
+        let mut selections = self.selections.clone();
+        selections.push(Selection { anchor: cursor, cursor, column: Some(column) });
+        self.set_selections(selections);
+    }
+
+    pub fn selection_into_lines(&mut self) {
+        let mut selections = vec![];
+        for selection in self.selections.iter() {
+            let first = self.text.line_of_offset(selection.start());
+            let last = self.text.line_of_offset(selection.end());
+            if first == last {
+                selections.push(*selection);
+                continue;
+            }
+            for line in first..=last {
+                let range = self.text.line_range(line);
+                let start = cmp::max(range.start, selection.start());
+                let end = cmp::min(range.end, selection.end());
+                if start < end || line == first {
+                    selections.push(Selection::new(start, end));
+                }
+            }
+        }
+        self.set_selections(selections);
+    }
+
+    pub fn gesture(&mut self, line: usize, col: usize, gesture: GestureType) {
+        let offset = self.offset_of_position(line, col);
+        let gesture = match gesture {
+            GestureType::PointSelect => GestureType::Select { granularity: SelectionGranularity::Point, multi: false },
+            GestureType::ToggleSel => GestureType::Select { granularity: SelectionGranularity::Point, multi: true },
+            GestureType::WordSelect => GestureType::Select { granularity: SelectionGranularity::Word, multi: false },
+            GestureType::MultiWordSelect => GestureType::Select { granularity: SelectionGranularity::Word, multi: true },
+            GestureType::LineSelect => GestureType::Select { granularity: SelectionGranularity::Line, multi: false },
+            GestureType::MultiLineSelect => GestureType::Select { granularity: SelectionGranularity::Line, multi: true },
+            GestureType::RangeSelect => GestureType::SelectExtend { granularity: SelectionGranularity::Point },
+            gesture => gesture,
+        };
+
+        match gesture {
+            GestureType::Select { granularity, multi } => {
+                let range = self.unit_range(offset, granularity);
+                let selection = Selection::new(range.start, range.end);
+                self.drag = Some((granularity, range.clone()));
+
+                if !multi {
+                    self.set_selections(vec![selection]);
+                } else if range.start == range.end && self.selections.len() > 1
+                    && self.selections.iter().any(|s| s.is_caret() && s.cursor == offset) {
+                    // Clicking a caret again takes it away
+                    let selections = self.selections.iter()
+                        .filter(|s| !(s.is_caret() && s.cursor == offset))
+                        .cloned()
+                        .collect();
+                    self.set_selections(selections);
+                } else {
+                    let mut selections = self.selections.clone();
+                    selections.push(selection);
+                    self.set_selections(selections);
+                }
+            },
+            GestureType::SelectExtend { granularity } => {
+                let last = *self.selections.last().unwrap();
+                let range = self.unit_range(offset, granularity);
+                let cursor = if offset < last.anchor { range.start } else { range.end };
+                self.extend_last(Selection::new(last.anchor, cursor));
+            },
+            GestureType::Drag => {
+                let (granularity, origin) = match &self.drag {
+                    Some((granularity, origin)) => (*granularity, origin.clone()),
+                    None => (SelectionGranularity::Point, offset..offset),
+                };
+                let range = self.unit_range(offset, granularity);
+                let selection = if range.start < origin.start {
+                    Selection::new(origin.end, range.start)
+                } else {
+                    Selection::new(origin.start, cmp::max(range.end, origin.end))
+                };
+                self.extend_last(selection);
+            },
+            _ => unreachable!(),
+        }
+        self.last_edit = EditKind::Other;
+    }
+
+    fn extend_last(&mut self, selection: Selection) {
+        let mut selections = self.selections.clone();
+        selections.pop();
+        selections.push(selection);
+        self.set_selections(selections);
+    }
+
+    pub fn find(&mut self, id: usize, chars: &str, case_sensitive: bool, is_regex: bool, whole_words: bool) {
+        if chars.is_empty() {
+            self.find = None;
+            return;
+        }
+
+        let pattern = if is_regex { chars.to_string() } else { regex::escape(chars) };
+        let pattern = if whole_words { format!(r"\b(?:{})\b", pattern) } else { pattern };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sensitive)
+            .multi_line(true)
+            .build()
+            .ok();
+
+        self.find = Some(Find {
+            id,
+            chars: chars.to_string(),
+            case_sensitive,
+            is_regex,
+            whole_words,
+            regex,
+        });
+    }
+
+    pub fn matches(&self) -> Vec<Range<usize>> {
+        match self.find.as_ref().and_then(|find| find.regex.as_ref()) {
+            Some(regex) => regex.find_iter(&self.text.to_string())
+                .filter(|m| m.start() < m.end())
+                .map(|m| m.start()..m.end())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn next_match(&self, matches: &[Range<usize>], wrap_around: bool, allow_same: bool, reverse: bool) -> Option<Range<usize>> {
+        let last = self.selections.last().unwrap();
+        let found = if reverse {
+            matches.iter().rev()
+                .find(|m| m.start < last.start() || (allow_same && m.start == last.start()))
+                .or(if wrap_around { matches.last() } else { None })
+        } else {
+            matches.iter()
+                .find(|m| m.start > last.start() || (allow_same && m.start == last.start()))
+                .or(if wrap_around { matches.first() } else { None })
+        };
+        found.cloned()
+    }
+
+    pub fn find_next(&mut self, wrap_around: bool, allow_same: bool, modify: ModifySelection, reverse: bool) {
+        let matches = self.matches();
+        let found = match self.next_match(&matches, wrap_around, allow_same, reverse) {
+            Some(found) => Selection::new(found.start, found.end),
+            None => return,
+        };
+
+        let mut selections = self.selections.clone();
+        match modify {
+            ModifySelection::None | ModifySelection::Set => selections = vec![found],
+            ModifySelection::Add => selections.push(found),
+            ModifySelection::AddRemovingCurrent => {
+                selections.pop();
+                selections.push(found);
+            },
+        }
+        self.set_selections(selections);
+        self.last_edit = EditKind::Other;
+    }
+
+    pub fn find_all(&mut self) {
+        let selections: Vec<Selection> = self.matches().into_iter()
+            .map(|m| Selection::new(m.start, m.end))
+            .collect();
+        if !selections.is_empty() {
+            self.set_selections(selections);
+        }
+    }
+
+    pub fn set_replacement(&mut self, chars: &str) {
+        self.replacement = Some(chars.to_string());
+    }
+
+    // The replacement for a match, with the regex's capture groups expanded
+    fn replacement_for(&self, range: &Range<usize>, text: &str) -> String {
+        let replacement = self.replacement.clone().unwrap_or_default();
+        let regex = match self.find.as_ref().and_then(|find| find.regex.as_ref()) {
+            Some(regex) if find_is_regex(&self.find) => regex,
+            _ => return replacement,
+        };
+
+        let mut expanded = String::new();
+        match regex.captures_at(text, range.start) {
+            Some(captures) if captures.get(0).map(|m| m.start()) == Some(range.start) => {
+                captures.expand(&replacement, &mut expanded);
+                expanded
+            },
+            _ => replacement,
+        }
+    }
+
+    pub fn replace_next(&mut self) {
+        let matches = self.matches();
+        let target = match self.next_match(&matches, true, true, false) {
+            Some(target) => target,
+            None => return,
+        };
+
+        let text = self.text.to_string();
+        let replacement = self.replacement_for(&target, &text);
+        let next = target.start + replacement.len();
+        self.edit(EditKind::Other, vec![(target, replacement)], false);
+        self.set_selections(vec![Selection::caret(next)]);
+        self.find_next(true, true, ModifySelection::Set, false);
+    }
+
+    pub fn replace_all(&mut self) {
+        let text = self.text.to_string();
+        let edits = self.matches().into_iter()
+            .map(|m| {
+                let replacement = self.replacement_for(&m, &text);
+                (m, replacement)
+            })
+            .collect();
+        self.edit(EditKind::Other, edits, false);
+    }
+
+    /// The selected text, or the word at the caret
+    pub fn selected_text(&self) -> String {
+        let last = self.selections.last().unwrap();
+        let range = if last.is_caret() { self.word_range(last.cursor) } else { last.range() };
+        self.text.slice(range)
+    }
+
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.text.line_of_offset(offset);
+        (line, offset - self.text.offset_of_line(line))
+    }
+
+    fn cursor_lines(&self) -> BTreeSet<usize> {
+        self.selections.iter().map(|selection| self.text.line_of_offset(selection.cursor)).collect()
+    }
+
+    // Update ops carry the text too, the line cache needs it to turn cursor offsets into columns
+    fn line_json(&self, line: usize) -> Value {
+        let start = self.text.offset_of_line(line);
+        let end = self.text.offset_of_line(line + 1);
+        let cursor: Vec<usize> = self.selections.iter()
+            .filter(|selection| self.text.line_of_offset(selection.cursor) == line)
+            .map(|selection| selection.cursor - start)
+            .collect();
+
+        json!({ "text": self.text.slice(start..end), "ln": line + 1, "cursor": cursor })
+    }
+
+    // Describes a run of lines which map to the frontend's lines `shift` after them, copying
+    // those it has the text of. Cursors are updated on lines in `updates`, and lines outside the
+    // viewport are invalidated.
+    fn push_lines(&self, ops: &mut UpdateOps, lines: Range<usize>, shift: Option<isize>,
+        visible: &Range<usize>, updates: &BTreeSet<usize>) {
+        let inside = cmp::max(lines.start, visible.start)..cmp::min(lines.end, visible.end);
+        if inside.start >= inside.end {
+            ops.invalidate(lines.end - lines.start);
+            return;
+        }
+
+        ops.invalidate(inside.start - lines.start);
+        for line in inside.clone() {
+            let old_line = shift.map(|shift| (line as isize + shift) as usize)
+                .filter(|old_line| self.sent_valid.contains(old_line));
+            match old_line {
+                Some(old_line) if updates.contains(&line) => ops.update(old_line, self.line_json(line)),
+                Some(old_line) => ops.copy(old_line, line),
+                None => ops.ins(self.line_json(line)),
+            }
+        }
+        ops.invalidate(lines.end - inside.end);
+    }
+
+    /// The changes since the last update in xi's format, for the frontend's line cache. Only the
+    /// lines in the viewport are sent, the rest are invalidated.
+    pub fn update(&mut self) -> Value {
+        let old_count = self.sent_lines;
+        let new_count = self.text.line_count();
+        let (before, after) = self.unchanged.take().unwrap_or((old_count, old_count));
+        let before = cmp::min(before, cmp::min(old_count, new_count));
+        let after = cmp::min(after, cmp::min(old_count, new_count) - before);
+
+        let cursor_lines = self.cursor_lines();
+        let mut updates: BTreeSet<usize> = cursor_lines.clone();
+        for &line in self.sent_cursor_lines.iter() {
+            if line < before {
+                updates.insert(line);
+            } else if line >= old_count - after {
+                updates.insert(line + new_count - old_count);
+            }
+        }
+
+        let visible = cmp::min(self.viewport.start, new_count)..cmp::min(self.viewport.end, new_count);
+        let mut ops = UpdateOps::default();
+        self.push_lines(&mut ops, 0..before, Some(0), &visible, &updates);
+        self.push_lines(&mut ops, before..new_count - after, None, &visible, &updates);
+        self.push_lines(&mut ops, new_count - after..new_count, Some(old_count as isize - new_count as isize),
+            &visible, &updates);
+
+        self.sent_lines = new_count;
+        self.sent_valid = visible;
+        self.sent_cursor_lines = cursor_lines;
+
+        json!({
+            "ops": ops.ops,
+            "pristine": self.is_pristine(),
+            "annotations": self.annotations(),
+        })
+    }
+
+    fn annotations(&self) -> Vec<Value> {
+        let to_range = |range: Range<usize>| {
+            let (start_line, start_col) = self.position(range.start);
+            let (end_line, end_col) = self.position(range.end);
+            json!([start_line, start_col, end_line, end_col])
+        };
+
+        let selections: Vec<Value> = self.selections.iter()
+            .filter(|selection| !selection.is_caret())
+            .map(|selection| to_range(selection.range()))
+            .collect();
+        let mut annotations = vec![json!({
+            "type": "selection",
+            "n": selections.len(),
+            "ranges": selections,
+            "payloads": null,
+        })];
+
+        if let Some(find) = &self.find {
+            let matches: Vec<Value> = self.matches().into_iter().map(to_range).collect();
+            let payloads: Vec<Value> = matches.iter().map(|_| json!({ "id": find.id })).collect();
+            annotations.push(json!({
+                "type": "find",
+                "n": matches.len(),
+                "ranges": matches,
+                "payloads": payloads,
+            }));
+        }
+
+        annotations
+    }
+}
+
+/// Update ops as they are built, merging runs of the same op
+#[derive(Default)]
+struct UpdateOps {
+    ops: Vec<Value>,
+    // The next of the frontend's old lines, which copies and updates take from
+    old_line: usize,
+}
+
+impl UpdateOps {
+    // Old lines before the one wanted are dropped
+    fn skip_to(&mut self, old_line: usize) {
+        if old_line > self.old_line {
+            self.ops.push(json!({ "op": "skip", "n": old_line - self.old_line }));
+            self.old_line = old_line;
+        }
+    }
+
+    fn copy(&mut self, old_line: usize, line: usize) {
+        self.skip_to(old_line);
+        self.old_line += 1;
+        if let Some(last) = self.ops.last_mut().filter(|op| op["op"] == "copy") {
+            last["n"] = json!(last["n"].as_u64().unwrap_or(0) + 1);
+        } else {
+            self.ops.push(json!({ "op": "copy", "n": 1, "ln": line + 1 }));
+        }
+    }
+
+    fn update(&mut self, old_line: usize, line: Value) {
+        self.skip_to(old_line);
+        self.old_line += 1;
+        self.push_lines("update", line);
+    }
+
+    fn ins(&mut self, line: Value) {
+        self.push_lines("ins", line);
+    }
+
+    fn push_lines(&mut self, op: &str, line: Value) {
+        if let Some(last) = self.ops.last_mut().filter(|last| last["op"] == op) {
+            last["n"] = json!(last["n"].as_u64().unwrap_or(0) + 1);
+            last["lines"].as_array_mut().unwrap().push(line);
+        } else {
+            self.ops.push(json!({ "op": op, "n": 1, "lines": [line] }));
+        }
+    }
+
+    fn invalidate(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        if let Some(last) = self.ops.last_mut().filter(|op| op["op"] == "invalidate") {
+            last["n"] = json!(last["n"].as_u64().unwrap_or(0) + n as u64);
+        } else {
+            self.ops.push(json!({ "op": "invalidate", "n": n }));
+        }
+    }
+}
+
+fn find_is_regex(find: &Option<Find>) -> bool {
+    find.as_ref().map_or(false, |find| find.is_regex)
+}
+
+// Moves an offset along with the text around it through edits sorted by their range
+fn map_offset(offset: usize, edits: &[(Range<usize>, String)]) -> usize {
+    let mut delta: isize = 0;
+    for (range, text) in edits {
+        let new_start = (range.start as isize + delta) as usize;
+        if offset < range.start {
+            break;
+        }
+        if offset < range.end {
+            return new_start + cmp::min(offset - range.start, text.len());
+        }
+        delta += text.len() as isize - (range.end - range.start) as isize;
+    }
+    (offset as isize + delta) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(buffer: &Buffer) -> usize {
+        buffer.selections()[0].cursor
+    }
+
+    // Each op of an update with its count
+    fn ops(update: &Value) -> Vec<(String, u64)> {
+        update["ops"].as_array().unwrap().iter()
+            .map(|op| (op["op"].as_str().unwrap().to_string(), op["n"].as_u64().unwrap()))
+            .collect()
+    }
+
+    fn op(name: &str, n: u64) -> (String, u64) {
+        (name.to_string(), n)
+    }
+
+    #[test]
+    fn positions_are_clamped() {
+        let buffer = Buffer::new("ab\nçd\n");
+
+        assert_eq!(buffer.offset_of_position(0, 1), 1);
+        // Past the end of a line, before its newline
+        assert_eq!(buffer.offset_of_position(0, 10), 2);
+        // Within a char, back to its start
+        assert_eq!(buffer.offset_of_position(1, 1), 3);
+        assert_eq!(buffer.offset_of_position(1, 2), 5);
+        // Past the last line, on the last line
+        assert_eq!(buffer.offset_of_position(9, 4), 7);
+    }
+
+    #[test]
+    fn vertical_moves_keep_their_column() {
+        let mut buffer = Buffer::new("long line\nab\nüüüüüüüüü\n");
+        buffer.set_selections(vec![Selection::caret(7)]);
+
+        // Through a shorter line, then on at the column in chars
+        buffer.movement("move_down");
+        assert_eq!(cursor(&buffer), 12);
+        buffer.movement("move_down");
+        assert_eq!(cursor(&buffer), 27);
+        buffer.movement("move_up");
+        buffer.movement("move_up");
+        assert_eq!(cursor(&buffer), 7);
+
+        // A horizontal move sets the column again
+        buffer.movement("move_down");
+        buffer.movement("move_left");
+        buffer.movement("move_down");
+        assert_eq!(cursor(&buffer), 15);
+    }
+
+    #[test]
+    fn moves_past_the_ends_keep_their_column() {
+        let mut buffer = Buffer::new("abc\nabcdef");
+        buffer.set_selections(vec![Selection::caret(2)]);
+
+        buffer.movement("move_up");
+        assert_eq!(cursor(&buffer), 0);
+        buffer.movement("move_down");
+        assert_eq!(cursor(&buffer), 6);
+        buffer.movement("move_down");
+        assert_eq!(cursor(&buffer), buffer.text().len());
+    }
+
+    #[test]
+    fn only_lines_in_the_viewport_are_sent() {
+        let text: String = (0..100).map(|n| format!("{}\n", n)).collect();
+        let mut buffer = Buffer::new(&text);
+        buffer.set_viewport(10..20);
+        let update = buffer.update();
+        assert_eq!(ops(&update), vec![op("invalidate", 10), op("ins", 10), op("invalidate", 81)]);
+        assert_eq!(update["ops"][1]["lines"][0]["text"], json!("10\n"));
+
+        // Lines still in view are copied, those scrolled in are sent
+        buffer.set_viewport(15..25);
+        assert_eq!(ops(&buffer.update()),
+            vec![op("invalidate", 15), op("skip", 15), op("copy", 5), op("ins", 5), op("invalidate", 76)]);
+
+        // An edit out of view only invalidates
+        buffer.insert("x");
+        assert_eq!(ops(&buffer.update()),
+            vec![op("invalidate", 15), op("skip", 15), op("copy", 10), op("invalidate", 76)]);
+    }
+
+    #[test]
+    fn cursors_in_view_are_updated() {
+        let mut buffer = Buffer::new("ab\ncd\nef");
+        buffer.set_viewport(0..10);
+        buffer.update();
+
+        buffer.movement("move_down");
+        let update = buffer.update();
+        assert_eq!(ops(&update), vec![op("update", 2), op("copy", 1)]);
+        assert_eq!(update["ops"][0]["lines"][1]["cursor"], json!([0]));
+    }
+}
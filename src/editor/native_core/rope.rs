@@ -0,0 +1,388 @@
+//! An immutable rope of utf-8 text
+//!
+//! Edits return a new rope which shares every untouched chunk with the old one, so keeping old
+//! versions around for undo costs little. Offsets are in bytes and must be on char boundaries.
+
+use std::cmp;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+const MAX_LEAF: usize = 1024;
+// Trees deeper than this are rebuilt, edits at one end of the text would otherwise make a list
+const MAX_HEIGHT: usize = 48;
+
+#[derive(Clone)]
+pub struct Rope {
+    root: Arc<Node>,
+}
+
+enum Node {
+    Leaf(String),
+    Branch {
+        left: Arc<Node>,
+        right: Arc<Node>,
+        len: usize,
+        newlines: usize,
+        height: usize,
+    },
+}
+
+impl Node {
+    fn leaf(text: String) -> Arc<Node> {
+        Arc::new(Node::Leaf(text))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(text) => text.len(),
+            Node::Branch { len, .. } => *len,
+        }
+    }
+
+    fn newlines(&self) -> usize {
+        match self {
+            Node::Leaf(text) => text.bytes().filter(|b| *b == b'\n').count(),
+            Node::Branch { newlines, .. } => *newlines,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Branch { height, .. } => *height,
+        }
+    }
+
+    fn concat(left: Arc<Node>, right: Arc<Node>) -> Arc<Node> {
+        if left.len() == 0 {
+            return right;
+        }
+        if right.len() == 0 {
+            return left;
+        }
+        if let (Node::Leaf(a), Node::Leaf(b)) = (&*left, &*right) {
+            if a.len() + b.len() <= MAX_LEAF {
+                return Node::leaf(format!("{}{}", a, b));
+            }
+        }
+
+        Arc::new(Node::Branch {
+            len: left.len() + right.len(),
+            newlines: left.newlines() + right.newlines(),
+            height: cmp::max(left.height(), right.height()) + 1,
+            left,
+            right,
+        })
+    }
+
+    // Builds a balanced tree over the leaves
+    fn build(leaves: &[Arc<Node>]) -> Arc<Node> {
+        match leaves.len() {
+            0 => Node::leaf(String::new()),
+            1 => leaves[0].clone(),
+            n => Node::concat(Node::build(&leaves[..n / 2]), Node::build(&leaves[n / 2..])),
+        }
+    }
+
+    fn collect_leaves(node: &Arc<Node>, leaves: &mut Vec<Arc<Node>>) {
+        match &**node {
+            Node::Leaf(text) if text.is_empty() => (),
+            Node::Leaf(_) => leaves.push(node.clone()),
+            Node::Branch { left, right, .. } => {
+                Node::collect_leaves(left, leaves);
+                Node::collect_leaves(right, leaves);
+            },
+        }
+    }
+
+    fn slice(node: &Arc<Node>, range: Range<usize>) -> Arc<Node> {
+        if range.start == 0 && range.end >= node.len() {
+            return node.clone();
+        }
+        if range.start >= range.end {
+            return Node::leaf(String::new());
+        }
+
+        match &**node {
+            Node::Leaf(text) => Node::leaf(text[range].to_string()),
+            Node::Branch { left, right, .. } => {
+                let split = left.len();
+                let left = if range.start < split {
+                    Node::slice(left, range.start..cmp::min(range.end, split))
+                } else {
+                    Node::leaf(String::new())
+                };
+                let right = if range.end > split {
+                    Node::slice(right, range.start.saturating_sub(split)..range.end - split)
+                } else {
+                    Node::leaf(String::new())
+                };
+                Node::concat(left, right)
+            },
+        }
+    }
+
+    fn push_str(&self, range: Range<usize>, out: &mut String) {
+        match self {
+            Node::Leaf(text) => out.push_str(&text[range]),
+            Node::Branch { left, right, .. } => {
+                let split = left.len();
+                if range.start < split {
+                    left.push_str(range.start..cmp::min(range.end, split), out);
+                }
+                if range.end > split {
+                    right.push_str(range.start.saturating_sub(split)..range.end - split, out);
+                }
+            },
+        }
+    }
+
+    // The offset just after the nth newline, counting from 1
+    fn offset_after_newline(&self, n: usize) -> usize {
+        match self {
+            Node::Leaf(text) => text.match_indices('\n')
+                .nth(n - 1)
+                .map(|(ix, _)| ix + 1)
+                .unwrap_or(text.len()),
+            Node::Branch { left, right, .. } => {
+                let left_newlines = left.newlines();
+                if n <= left_newlines {
+                    left.offset_after_newline(n)
+                } else {
+                    left.len() + right.offset_after_newline(n - left_newlines)
+                }
+            },
+        }
+    }
+
+    fn newlines_before(&self, offset: usize) -> usize {
+        match self {
+            Node::Leaf(text) => text[..offset].bytes().filter(|b| *b == b'\n').count(),
+            Node::Branch { left, right, .. } => {
+                let split = left.len();
+                if offset <= split {
+                    left.newlines_before(offset)
+                } else {
+                    left.newlines() + right.newlines_before(offset - split)
+                }
+            },
+        }
+    }
+
+    // The leaf containing the byte at offset, with the offset the leaf starts at
+    fn leaf_at(&self, offset: usize) -> (&str, usize) {
+        match self {
+            Node::Leaf(text) => (text, 0),
+            Node::Branch { left, right, .. } => {
+                let split = left.len();
+                if offset < split {
+                    left.leaf_at(offset)
+                } else {
+                    let (text, start) = right.leaf_at(offset - split);
+                    (text, start + split)
+                }
+            },
+        }
+    }
+}
+
+impl Rope {
+    pub fn new() -> Self {
+        Self { root: Node::leaf(String::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lines are ended by a newline, so text ending with one has an empty last line
+    pub fn line_count(&self) -> usize {
+        self.root.newlines() + 1
+    }
+
+    pub fn offset_of_line(&self, line: usize) -> usize {
+        match line {
+            0 => 0,
+            _ if line > self.root.newlines() => self.len(),
+            _ => self.root.offset_after_newline(line),
+        }
+    }
+
+    pub fn line_of_offset(&self, offset: usize) -> usize {
+        self.root.newlines_before(cmp::min(offset, self.len()))
+    }
+
+    /// The range of a line, without its newline
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.offset_of_line(line);
+        let end = self.offset_of_line(line + 1);
+        let end = if end > start && self.char_before(end) == Some('\n') { end - 1 } else { end };
+        start..end
+    }
+
+    /// The text of a line including its newline
+    pub fn line(&self, line: usize) -> String {
+        self.slice(self.offset_of_line(line)..self.offset_of_line(line + 1))
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let mut out = String::with_capacity(range.len());
+        if range.start < range.end {
+            self.root.push_str(range.start..cmp::min(range.end, self.len()), &mut out);
+        }
+        out
+    }
+
+    pub fn char_at(&self, offset: usize) -> Option<char> {
+        if offset >= self.len() {
+            return None;
+        }
+        let (text, start) = self.root.leaf_at(offset);
+        text[offset - start..].chars().next()
+    }
+
+    pub fn char_before(&self, offset: usize) -> Option<char> {
+        if offset == 0 || offset > self.len() {
+            return None;
+        }
+        let (text, start) = self.root.leaf_at(offset - 1);
+        text[..offset - start].chars().next_back()
+    }
+
+    /// The offset of the char after the one at offset
+    pub fn next_offset(&self, offset: usize) -> Option<usize> {
+        self.char_at(offset).map(|ch| offset + ch.len_utf8())
+    }
+
+    pub fn prev_offset(&self, offset: usize) -> Option<usize> {
+        self.char_before(offset).map(|ch| offset - ch.len_utf8())
+    }
+
+    /// Replaces the range with text
+    pub fn edit(&self, range: Range<usize>, text: &str) -> Rope {
+        let before = Node::slice(&self.root, 0..range.start);
+        let after = Node::slice(&self.root, range.end..self.len());
+        let inserted = Rope::from(text).root;
+        let root = Node::concat(Node::concat(before, inserted), after);
+
+        if root.height() > MAX_HEIGHT {
+            let mut leaves = vec![];
+            Node::collect_leaves(&root, &mut leaves);
+            return Rope { root: Node::build(&leaves) };
+        }
+        Rope { root }
+    }
+}
+
+impl Default for Rope {
+    fn default() -> Self {
+        Rope::new()
+    }
+}
+
+impl<'a> From<&'a str> for Rope {
+    fn from(text: &str) -> Rope {
+        let mut leaves = vec![];
+        let mut rest = text;
+        while !rest.is_empty() {
+            let mut split = cmp::min(MAX_LEAF, rest.len());
+            while !rest.is_char_boundary(split) {
+                split -= 1;
+            }
+            leaves.push(Node::leaf(rest[..split].to_string()));
+            rest = &rest[split..];
+        }
+
+        Rope { root: Node::build(&leaves) }
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.slice(0..self.len()))
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rope({:?})", self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(rope: &Rope) -> Vec<Arc<Node>> {
+        let mut leaves = vec![];
+        Node::collect_leaves(&rope.root, &mut leaves);
+        leaves
+    }
+
+    #[test]
+    fn multi_byte_text() {
+        let rope = Rope::from("héllo\nwörld ✓\n");
+
+        assert_eq!(rope.line_count(), 3);
+        assert_eq!(rope.line_range(1), 7..17);
+        assert_eq!(rope.line(1), "wörld ✓\n");
+        assert_eq!(rope.char_at(1), Some('é'));
+        assert_eq!(rope.next_offset(1), Some(3));
+        assert_eq!(rope.prev_offset(3), Some(1));
+        assert_eq!(rope.char_before(17), Some('✓'));
+
+        let edited = rope.edit(1..3, "e");
+        assert_eq!(edited.to_string(), "hello\nwörld ✓\n");
+        assert_eq!(rope.to_string(), "héllo\nwörld ✓\n");
+    }
+
+    #[test]
+    fn leaves_split_on_char_boundaries() {
+        // MAX_LEAF is not a multiple of 3, so each leaf is cut short of it
+        let text = "€".repeat(MAX_LEAF);
+        let rope = Rope::from(text.as_str());
+
+        let leaves = leaves(&rope);
+        assert_eq!(leaves.len(), 4);
+        assert!(leaves.iter().all(|leaf| leaf.len() <= MAX_LEAF && leaf.len() % 3 == 0));
+        assert_eq!(rope.to_string(), text);
+        assert_eq!(rope.char_at(MAX_LEAF - 1), Some('€'));
+        assert_eq!(rope.char_before(MAX_LEAF - 1), Some('€'));
+        assert_eq!(rope.slice(MAX_LEAF - 4..MAX_LEAF + 2), "€€");
+    }
+
+    #[test]
+    fn lines_span_leaves() {
+        let text: String = (0..200).map(|ix| format!("line {}\n", ix)).collect();
+        let rope = Rope::from(text.as_str());
+        assert!(rope.root.height() > 0);
+
+        assert_eq!(rope.line_count(), 201);
+        for line in [0, 57, 132, 199].iter() {
+            assert_eq!(rope.line(*line), format!("line {}\n", line));
+            assert_eq!(rope.line_of_offset(rope.offset_of_line(*line)), *line);
+        }
+        assert_eq!(rope.line(200), "");
+        assert_eq!(rope.offset_of_line(500), rope.len());
+    }
+
+    #[test]
+    fn deep_trees_are_rebuilt() {
+        // Each append adds a level on the right, until the tree is rebuilt balanced
+        let chunk = "x".repeat(MAX_LEAF);
+        let mut rope = Rope::new();
+        for _ in 0..MAX_HEIGHT * 2 {
+            rope = rope.edit(rope.len()..rope.len(), &chunk);
+            assert!(rope.root.height() <= MAX_HEIGHT);
+        }
+
+        assert_eq!(rope.len(), MAX_HEIGHT * 2 * MAX_LEAF);
+        assert_eq!(leaves(&rope).len(), MAX_HEIGHT * 2);
+        assert_eq!(rope.slice(MAX_LEAF - 1..MAX_LEAF + 1), "xx");
+    }
+}
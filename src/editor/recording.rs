@@ -0,0 +1,314 @@
+use std::collections::{
+    HashSet,
+    VecDeque,
+};
+use std::fs::{
+    self,
+    File,
+};
+use std::io::{
+    BufWriter,
+    Write,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::sync::mpsc::{
+    channel,
+    Receiver,
+    Sender,
+};
+use std::thread;
+use std::time::Instant;
+
+use serde::{
+    Serialize,
+    Deserialize,
+};
+use serde_json::Value;
+
+use super::editor_rpc::{
+    Handler,
+    Transport,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    ToCore,
+    FromCore,
+}
+
+/// One line of a recording, the time is from when recording started
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub elapsed_ms: f64,
+    pub direction: Direction,
+    pub message: Value,
+}
+
+/// Logs every message between the frontend and the core to a JSONL file
+///
+/// A failed write is reported to the handler and ends the recording, rather than leaving one
+/// which is missing messages.
+#[derive(Clone)]
+pub struct Recorder {
+    log: Arc<Mutex<Log>>,
+    start: Instant,
+}
+
+struct Log {
+    // None once a write has failed
+    file: Option<BufWriter<File>>,
+    handler: Box<dyn Handler + Send>,
+}
+
+impl Recorder {
+    pub fn create<H: Handler + Send + 'static>(path: &str, handler: H) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|err| format!("unable to record to {}: {}", path, err))?;
+
+        Ok(Self {
+            log: Arc::new(Mutex::new(Log {
+                file: Some(BufWriter::new(file)),
+                handler: Box::new(handler),
+            })),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&self, direction: Direction, message: &Value) {
+        let entry = Entry {
+            elapsed_ms: self.start.elapsed().as_secs_f64() * 1000.0,
+            direction,
+            message: message.clone(),
+        };
+
+        // Flushed per message so the recording survives a crash, which is when it is wanted
+        let line = serde_json::to_string(&entry).unwrap();
+        let mut log = self.log.lock().unwrap();
+        let written = match &mut log.file {
+            Some(file) => writeln!(file, "{}", line).and_then(|_| file.flush()),
+            None => return,
+        };
+        if let Err(err) = written {
+            log.file = None;
+            log.handler.failed(format!("unable to record message, recording stopped: {}", err));
+        }
+    }
+
+    /// Records the messages sent through `transport` and those arriving on `rx`, which are
+    /// passed on to the returned receiver
+    pub fn wrap<T: Transport>(&self, transport: T, rx: Receiver<Value>) -> (RecordingTransport<T>, Receiver<Value>) {
+        let (tx, recorded_rx) = channel();
+        let recorder = self.clone();
+        thread::spawn(move || {
+            for message in rx.iter() {
+                recorder.record(Direction::FromCore, &message);
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (RecordingTransport { inner: transport, recorder: self.clone() }, recorded_rx)
+    }
+}
+
+pub struct RecordingTransport<T> {
+    inner: T,
+    recorder: Recorder,
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send(&self, message: &Value) {
+        self.recorder.record(Direction::ToCore, message);
+        self.inner.send(message);
+    }
+}
+
+/// Plays the core's side of a recording back to the frontend
+///
+/// Core messages are sent in their recorded order, holding each response until the frontend
+/// has made the request it answers. Frontend messages are compared with the recording, the
+/// first difference is reported to the handler as the replay having failed, and closes the
+/// connection. Otherwise it is closed once everything has been played back and compared.
+pub struct ReplayCore {
+    state: Mutex<ReplayState>,
+}
+
+struct ReplayState {
+    tx: Option<Sender<Value>>,
+    incoming: VecDeque<Value>,
+    expected: VecDeque<Value>,
+    requested: HashSet<u64>,
+    diverged: bool,
+    handler: Box<dyn Handler + Send>,
+}
+
+impl ReplayState {
+    fn pump(&mut self) {
+        while let Some(message) = self.incoming.front() {
+            let response_id = match message["method"] {
+                Value::Null => message["id"].as_u64(),
+                _ => None,
+            };
+            if let Some(id) = response_id {
+                if !self.requested.contains(&id) {
+                    return;
+                }
+            }
+
+            let message = self.incoming.pop_front().unwrap();
+            if let Some(tx) = &self.tx {
+                let _ = tx.send(message);
+            }
+        }
+        // Left open until the frontend has sent everything it was recorded sending, so messages
+        // after the core's last one are compared too
+        if self.expected.is_empty() || self.diverged {
+            self.tx = None;
+        }
+    }
+}
+
+impl ReplayCore {
+    pub fn load<H: Handler + Send + 'static>(path: &str, handler: H) -> Result<(Self, Receiver<Value>), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("unable to read recording {}: {}", path, err))?;
+
+        let mut incoming = VecDeque::new();
+        let mut expected = VecDeque::new();
+        for (ix, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let entry = serde_json::from_str::<Entry>(line)
+                .map_err(|err| format!("invalid recording {} at line {}: {}", path, ix + 1, err))?;
+            match entry.direction {
+                Direction::FromCore => incoming.push_back(entry.message),
+                Direction::ToCore => expected.push_back(entry.message),
+            }
+        }
+
+        let (tx, rx) = channel();
+        let mut state = ReplayState {
+            tx: Some(tx),
+            incoming,
+            expected,
+            requested: HashSet::new(),
+            diverged: false,
+            handler: Box::new(handler),
+        };
+        state.pump();
+
+        Ok((Self { state: Mutex::new(state) }, rx))
+    }
+}
+
+impl Transport for ReplayCore {
+    fn send(&self, message: &Value) {
+        let mut state = self.state.lock().unwrap();
+        let expected = state.expected.pop_front();
+        if !state.diverged && expected.as_ref() != Some(message) {
+            state.diverged = true;
+            state.handler.failed(format!("replay diverged, expected {} but the frontend sent {}",
+                expected.unwrap_or(Value::Null), message));
+        }
+
+        if let Some(id) = message["id"].as_u64() {
+            state.requested.insert(id);
+        }
+        state.pump();
+    }
+}
+
+// Dropped once the frontend has shut down, which it should only do after sending everything it
+// was recorded sending
+impl Drop for ReplayCore {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap();
+        if let (false, Some(expected)) = (state.diverged, state.expected.front()) {
+            state.handler.failed(format!("replay diverged, expected {} but the frontend stopped", expected));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    // Passes on what transports report as failures
+    struct Failures(Sender<String>);
+
+    impl Handler for Failures {
+        fn notification(&self, _method: &str, _params: &Value) {}
+
+        fn failed(&self, message: String) {
+            self.0.send(message).unwrap();
+        }
+    }
+
+    fn write_recording(name: &str, entries: &[(Direction, Value)]) -> String {
+        let path = env::temp_dir().join(format!("editor-test-{}-{}.jsonl", name, process::id()));
+        let lines: Vec<String> = entries.iter()
+            .map(|(direction, message)| serde_json::to_string(&Entry {
+                elapsed_ms: 0.0,
+                direction: *direction,
+                message: message.clone(),
+            }).unwrap())
+            .collect();
+        fs::write(&path, lines.join("\n")).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn responses_wait_for_their_requests() {
+        let path = write_recording("replay", &[
+            (Direction::ToCore, json!({ "id": 0, "method": "new_view", "params": {} })),
+            (Direction::FromCore, json!({ "id": 0, "result": "view-id-1" })),
+        ]);
+        let (tx, failures) = channel();
+        let (replay, rx) = ReplayCore::load(&path, Failures(tx)).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        replay.send(&json!({ "id": 0, "method": "new_view", "params": {} }));
+        assert_eq!(rx.try_recv().unwrap(), json!({ "id": 0, "result": "view-id-1" }));
+        assert!(failures.try_recv().is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn diverging_from_the_recording_fails_once() {
+        let path = write_recording("diverged", &[
+            (Direction::ToCore, json!({ "method": "client_started", "params": {} })),
+            (Direction::ToCore, json!({ "method": "set_theme", "params": {} })),
+        ]);
+        let (tx, failures) = channel();
+        let (replay, _rx) = ReplayCore::load(&path, Failures(tx)).unwrap();
+
+        replay.send(&json!({ "method": "set_theme", "params": {} }));
+        replay.send(&json!({ "method": "client_started", "params": {} }));
+        let failure = failures.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(failure.starts_with("replay diverged"), "{}", failure);
+        assert!(failures.try_recv().is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn failed_writes_stop_the_recording() {
+        let (tx, failures) = channel();
+        let recorder = Recorder::create("/dev/full", Failures(tx)).unwrap();
+
+        recorder.record(Direction::ToCore, &json!({ "method": "client_started" }));
+        recorder.record(Direction::ToCore, &json!({ "method": "set_theme" }));
+        let failure = failures.try_recv().unwrap();
+        assert!(failure.starts_with("unable to record message"), "{}", failure);
+        assert!(failures.try_recv().is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::{
+    Serialize,
+    Deserialize,
+};
+use serde_json::{
+    Value,
+    from_value,
+};
+use rpc::{
+    Config,
+    SplitDirection,
+};
+
+use super::layout::Layout;
+use super::state::ViewId;
+use crate::cli::OpenMode;
+
+/// Bumped whenever the session format changes. Sessions of any other version are rejected on
+/// load, until a migration from that version is added there.
+pub const SESSION_VERSION: u64 = 1;
+pub const DEFAULT_SESSION_FILE: &str = "Session.json";
+
+/// Snapshot of the open buffers, their layout and the state of each view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u64,
+    #[serde(default)]
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub active_tab: usize,
+    pub tabs: Vec<TabSession>,
+}
+
+/// The layout of a tab page, view ids in a session are only unique within the session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabSession {
+    pub layout: Layout,
+    #[serde(default)]
+    pub focused: Option<ViewId>,
+    pub views: HashMap<ViewId, ViewSession>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewSession {
+    #[serde(default)]
+    pub file_path: Option<String>,
+    #[serde(default)]
+    pub line: usize,
+    #[serde(default)]
+    pub col: usize,
+    #[serde(default)]
+    pub scroll_offset: f32,
+    /// Config set on the view itself, rather than the user or language defaults
+    #[serde(default)]
+    pub config: Option<Config>,
+}
+
+impl TabSession {
+    // Focuses the first view
+    fn new(layout: Layout, views: Vec<(ViewId, ViewSession)>) -> Self {
+        Self {
+            layout,
+            focused: views.first().map(|v| v.0.clone()),
+            views: views.into_iter().collect(),
+        }
+    }
+}
+
+impl Session {
+    pub fn new(theme: Option<String>, active_tab: usize, tabs: Vec<TabSession>) -> Self {
+        Self {
+            version: SESSION_VERSION,
+            theme,
+            active_tab,
+            tabs,
+        }
+    }
+
+    /// Lays out files from the command line, only the first file is opened without splits or tabs
+    pub fn from_files(files: &[String], open_mode: OpenMode) -> Self {
+        let views: Vec<(ViewId, ViewSession)> = files.iter()
+            .take(if open_mode == OpenMode::First { 1 } else { files.len() })
+            .enumerate()
+            .map(|(ix, file)| (format!("session-{}", ix), ViewSession {
+                file_path: Some(file.clone()),
+                ..Default::default()
+            }))
+            .collect();
+
+        let tabs = match open_mode {
+            OpenMode::Tabs => views.into_iter()
+                .map(|view| TabSession::new(Layout::View(view.0.clone()), vec![view]))
+                .collect(),
+            OpenMode::Split(direction) => vec![TabSession::new(
+                Layout::from_views(direction, views.iter().map(|v| v.0.clone()).collect()),
+                views)],
+            OpenMode::First => vec![TabSession::new(
+                Layout::from_views(SplitDirection::Horizontal, views.iter().map(|v| v.0.clone()).collect()),
+                views)],
+        };
+
+        Session::new(None, 0, tabs)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("unable to read session {}: {}", path, err))?;
+        let value: Value = serde_json::from_str(&contents)
+            .map_err(|err| format!("unable to parse session {}: {}", path, err))?;
+
+        let version = value["version"].as_u64()
+            .ok_or(format!("session {} has no version", path))?;
+        // Migrations from older versions go here, rewriting the value into the current format
+        let value = match version {
+            SESSION_VERSION => value,
+            _ => return Err(format!("session {} has unsupported version {}", path, version)),
+        };
+
+        let session: Session = from_value(value)
+            .map_err(|err| format!("unable to parse session {}: {}", path, err))?;
+        if session.tabs.is_empty() {
+            return Err(format!("session {} has no tabs", path));
+        }
+
+        Ok(session)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|err| format!("unable to serialize session: {}", err))?;
+
+        fs::write(path, contents)
+            .map_err(|err| format!("unable to write session {}: {}", path, err))
+    }
+}
@@ -1,25 +1,43 @@
-use std::sync::{
-    Arc,
-    Mutex,
-};
 use std::collections::HashMap;
+use std::path::Path;
 
 use winit::event::{
+    ElementState,
     ModifiersState,
+    MouseButton,
     VirtualKeyCode,
 };
-use winit::event_loop::EventLoopProxy;
 use xi_core_lib::plugins::Command;
 
 use super::ui::view::EditView;
+use super::ui::widget::Widget;
+use super::ui::tab_bar::{
+    TabBarWidget,
+    TabLabel,
+};
+use crate::render::display_list::DisplayList;
 use rpc::{ 
     PluginId,
     Style,
+    Theme,
     Mode,
     Action,
     ActionTarget,
+    Motion,
+    SelectionGranularity,
+    SplitDirection,
+    TabAction,
 };
 use super::view_commands::EditViewCommands;
+use super::session::{
+    Session,
+    TabSession,
+};
+use super::layout::{
+    Layout,
+    Placement,
+    neighbour,
+};
 use crate::events::{
     EditorEvent,
     EditorEventLoopProxy,
@@ -56,44 +74,227 @@ fn construct_bindingmap(bindings: Vec<KeyBinding>) -> BindingMap {
     map
 }
 
+// Commands held for a view which is not created yet, beyond this they are dropped
+const MAX_PENDING_VIEW_COMMANDS: usize = 64;
+// TODO: Derive from config
+const TAB_BAR_FONT_SIZE: f32 = 20.0;
+
+/// A tab page owns its own split layout and focused view
+#[derive(Default)]
+pub struct TabPage {
+    layout: Option<Layout>,
+    focused: Option<ViewId>,
+}
+
+impl TabPage {
+    pub fn view_ids(&self) -> Vec<ViewId> {
+        match &self.layout {
+            Some(layout) => layout.view_ids(),
+            None => vec![],
+        }
+    }
+
+    pub fn contains(&self, view_id: &ViewId) -> bool {
+        match &self.layout {
+            Some(layout) => layout.contains(view_id),
+            None => false,
+        }
+    }
+
+    /// Removes the view from the layout, focusing the next view in layout order
+    fn remove_view(&mut self, view_id: &ViewId) {
+        let view_ids = self.view_ids();
+        let next_focus = view_ids.iter()
+            .skip_while(|id| *id != view_id)
+            .nth(1)
+            .or_else(|| view_ids.iter().filter(|id| *id != view_id).last())
+            .cloned();
+
+        let removed_root = match &mut self.layout {
+            Some(layout) => !layout.remove(view_id) && layout.is_view(view_id),
+            None => false,
+        };
+        if removed_root {
+            self.layout = None;
+        }
+        if self.focused.as_ref() == Some(view_id) {
+            self.focused = next_focus;
+        }
+    }
+}
+
 pub struct EditorState {
-    pub focused: Option<ViewId>,
     pub views: HashMap<ViewId, EditView>, 
+    tabs: Vec<TabPage>,
+    active_tab: usize,
+    tab_bar: TabBarWidget,
+    display_list: DisplayList,
+    screen_size: [f32; 2],
+    pending_view_cmds: HashMap<ViewId, Vec<EditViewCommands>>,
+    // The `new_view` requests the core has yet to answer
+    opening_views: usize,
+    theme: Option<Theme>,
+    theme_name: Option<String>,
+    read_only: bool,
     themes: Vec<String>,
     languages: Vec<String>, 
     styles: HashMap<usize, Style>,
     plugins: HashMap<PluginId, PluginState>, 
     key_bindings: HashMap<VirtualKeyCode, Vec<KeyBinding>>,
     mouse_bindings: Vec<MouseBinding>,
+    // The view a selection is being dragged out in, from the click which started it
+    dragging: Option<ViewId>,
+    // Set while the pointer is dragged past the top or bottom of that view
+    auto_scroll: bool,
     event_proxy: EditorEventLoopProxy,
 }
 
 impl EditorState {
-    pub fn new(event_proxy: EventLoopProxy<EditorEvent>) -> Self {
+    pub fn new(event_proxy: EditorEventLoopProxy) -> Self {
         Self {
-            focused: Default::default(),
             views: HashMap::new(),
+            tabs: vec![TabPage::default()],
+            active_tab: 0,
+            tab_bar: TabBarWidget::new(0, TAB_BAR_FONT_SIZE),
+            display_list: DisplayList::new(TAB_BAR_FONT_SIZE),
+            screen_size: [0.0, 0.0],
+            pending_view_cmds: HashMap::new(),
+            opening_views: 0,
+            theme: None,
+            theme_name: None,
+            read_only: false,
             plugins: HashMap::new(),
             styles: HashMap::new(),
             themes: vec![],
             languages: vec![],
             mouse_bindings: default_mouse_bindings(),
             key_bindings: construct_bindingmap(default_key_bindings()),
+            dragging: None,
+            auto_scroll: false,
             event_proxy,
         }
     }
 
+    /// Marks what the core changed as dirty, a view or everything when None. It is drawn once
+    /// the event loop has handled every event waiting, however many changes there were.
+    pub fn mark_dirty(&mut self, view_id: Option<&str>) {
+        match view_id {
+            Some(view_id) => if let Some(view) = self.views.get_mut(view_id) {
+                view.set_dirty(true);
+            },
+            None => {
+                for view in self.views.values_mut() {
+                    view.set_dirty(true);
+                }
+                self.tab_bar.set_dirty(true);
+            },
+        }
+    }
 
     pub fn get_event_proxy(&self) -> EditorEventLoopProxy {
         self.event_proxy.clone()
     }
     
-    pub fn get_focused_view(&mut self) -> &mut EditView {
-        let view_id = self.focused.clone()
-            .expect("no focused EditView");
+    fn tab(&self) -> &TabPage {
+        &self.tabs[self.active_tab]
+    }
 
+    fn tab_mut(&mut self) -> &mut TabPage {
+        let active_tab = self.active_tab;
+        &mut self.tabs[active_tab]
+    }
+
+    /// The focused view of the active tab page. Views of a restored session are not focused until
+    /// the core has opened them.
+    pub fn focused(&self) -> Option<ViewId> {
+        self.tab().focused.clone()
+            .filter(|view_id| self.views.contains_key(view_id))
+    }
+
+    pub fn get_focused_view(&mut self) -> Option<&mut EditView> {
+        let view_id = self.focused()?;
         self.views.get_mut(&view_id)
-            .expect("Focused EditView not found in views")
+    }
+
+    /// Shows a message in the status bar of the focused view, and on stdout
+    pub fn show_message(&mut self, message: &str) {
+        println!("{}", message);
+        if let Some(view_id) = self.focused() {
+            self.send_view_cmd(&view_id, EditViewCommands::Message(message.to_string()));
+        }
+    }
+
+    /// Sends a command to the view with the given id. While a view is opening, commands for views
+    /// which do not exist yet are held until it is added, as the core can notify before `new_view`
+    /// returns. Otherwise they are for views which have closed, and are dropped.
+    pub fn send_view_cmd(&mut self, view_id: &str, command: EditViewCommands) {
+        if let Some(view) = self.views.get_mut(view_id) {
+            view.poke(command);
+            return;
+        }
+        if self.opening_views == 0 {
+            println!("dropping command for unknown view: {}", view_id);
+            return;
+        }
+
+        let pending = self.pending_view_cmds.entry(view_id.to_string()).or_insert_with(Vec::new);
+        if pending.len() < MAX_PENDING_VIEW_COMMANDS {
+            pending.push(command);
+        } else {
+            println!("dropping command for unknown view: {}", view_id);
+        }
+    }
+
+    /// Commands for unknown views are held from here until the core answers `new_view`
+    pub fn view_opening(&mut self) {
+        self.opening_views += 1;
+    }
+
+    /// Gives the view the core opened the commands held for it. Once no more views are opening,
+    /// the commands still held were for views which will never be added.
+    pub fn view_open_finished(&mut self, view_id: Option<&str>) {
+        if let Some(view_id) = view_id {
+            if let Some(pending) = self.pending_view_cmds.remove(view_id) {
+                if let Some(view) = self.views.get_mut(view_id) {
+                    for command in pending.into_iter() {
+                        view.poke(command);
+                    }
+                }
+            }
+        }
+
+        self.opening_views = self.opening_views.saturating_sub(1);
+        if self.opening_views == 0 {
+            self.pending_view_cmds.clear();
+        }
+    }
+
+    #[cfg(test)]
+    pub fn has_pending_view_cmds(&self) -> bool {
+        !self.pending_view_cmds.is_empty()
+    }
+
+    /// Theme changes apply to every view, and are kept for views created later
+    pub fn set_theme(&mut self, theme_name: String, theme: Theme) {
+        for (_, view) in self.views.iter_mut() {
+            view.poke(EditViewCommands::ThemeChanged(theme.clone()));
+        }
+        self.tab_bar.theme_changed(theme.clone());
+        self.theme = Some(theme);
+        self.theme_name = Some(theme_name);
+    }
+
+    /// Views opened read-only refuse to save
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn get_theme(&self) -> Option<Theme> {
+        self.theme.clone()
     }
 
     pub fn set_available_themes(&mut self, themes: Vec<String>) {
@@ -137,23 +338,385 @@ impl EditorState {
         }
     }
 
-    pub fn align_views_horizontally(&mut self, screen_size: [f32; 2]) {
-        let view_count = self.views.len();
-        let view_height = if view_count > 0 {
-            screen_size[1] / view_count as f32
+    /// Adds a new view to the layout, and focuses it. Returns the view it replaced, if any, which
+    /// the core still has open.
+    pub fn add_view(&mut self, view_id: ViewId, edit_view: EditView, placement: Placement) -> Option<ViewId> {
+        if let Placement::Tab = placement {
+            self.active_tab = if self.tab().layout.is_some() {
+                self.tabs.insert(self.active_tab + 1, TabPage::default());
+                self.active_tab + 1
+            } else {
+                self.active_tab
+            };
+            self.mark_tab_dirty();
+        }
+
+        // Replaced views may be in any tab page, such as placeholders for a restored session
+        let tab_ix = match &placement {
+            Placement::Replace(old_view_id) => self.tabs.iter()
+                .position(|tab| tab.contains(old_view_id))
+                .unwrap_or(self.active_tab),
+            _ => self.active_tab,
+        };
+        let tab = &mut self.tabs[tab_ix];
+        let focus = match &placement {
+            Placement::Replace(old_view_id) => tab.focused.is_none()
+                || tab.focused.as_ref() == Some(old_view_id),
+            _ => true,
+        };
+        let mut replaced = None;
+        let placed = match (&mut tab.layout, placement) {
+            (Some(layout), Placement::Replace(old_view_id)) => {
+                if old_view_id != view_id && self.views.remove(&old_view_id).is_some() {
+                    replaced = Some(old_view_id.clone());
+                }
+                layout.replace(&old_view_id, view_id.clone())
+            },
+            (Some(layout), Placement::Split(direction)) => {
+                if let Some(focused) = &tab.focused {
+                    layout.split(focused, view_id.clone(), direction)
+                } else {
+                    false
+                }
+            },
+            _ => false,
+        };
+        if !placed {
+            tab.layout = Some(Layout::View(view_id.clone()));
+        }
+
+        self.views.insert(view_id.clone(), edit_view);
+        if focus {
+            tab.focused = Some(view_id);
+        }
+
+        replaced
+    }
+
+    /// Replaces every tab page and its views with the layouts of a session, views are opened in
+    /// place of the session view ids afterwards.
+    pub fn restore_tabs(&mut self, session: &Session) {
+        self.views.clear();
+        self.pending_view_cmds.clear();
+        self.opening_views = 0;
+        self.tabs = session.tabs.iter()
+            .map(|tab| TabPage {
+                layout: Some(tab.layout.clone()),
+                focused: tab.focused.clone(),
+            })
+            .collect();
+        self.active_tab = session.active_tab.min(self.tabs.len() - 1);
+        self.mark_tab_dirty();
+    }
+
+    /// Captures the tab pages and the state of their views, renaming view ids to ids which are
+    /// only meaningful within the session
+    pub fn session(&self) -> Session {
+        let mut next_id = 0;
+        let tabs = self.tabs.iter()
+            .filter_map(|tab| {
+                let mut layout = tab.layout.clone()?;
+                let mut focused = None;
+                let mut views = HashMap::new();
+
+                for view_id in layout.view_ids() {
+                    let session_id = format!("session-{}", next_id);
+                    next_id += 1;
+
+                    layout.replace(&view_id, session_id.clone());
+                    if tab.focused.as_ref() == Some(&view_id) {
+                        focused = Some(session_id.clone());
+                    }
+                    let view_session = self.views.get(&view_id)
+                        .map(|view| view.session())
+                        .unwrap_or_default();
+                    views.insert(session_id, view_session);
+                }
+
+                Some(TabSession {
+                    layout,
+                    focused,
+                    views,
+                })
+            })
+            .collect();
+
+        Session::new(self.theme_name.clone(), self.active_tab, tabs)
+    }
+
+    /// Removes the view from the tab page holding it, closing the tab page once it is empty.
+    /// Returns true when no views remain.
+    pub fn remove_view(&mut self, view_id: &ViewId) -> bool {
+        self.views.remove(view_id);
+        self.pending_view_cmds.remove(view_id);
+
+        if let Some(ix) = self.tabs.iter().position(|tab| tab.contains(view_id)) {
+            self.tabs[ix].remove_view(view_id);
+            if self.tabs[ix].layout.is_none() && self.tabs.len() > 1 {
+                self.remove_tab(ix);
+            }
+        }
+
+        self.views.len() == 0
+    }
+
+    fn remove_tab(&mut self, index: usize) -> Vec<ViewId> {
+        let tab = self.tabs.remove(index);
+        if self.active_tab >= self.tabs.len() || self.active_tab > index {
+            self.active_tab -= 1;
+        }
+        self.mark_tab_dirty();
+
+        tab.view_ids()
+    }
+
+    /// Returns the ids of the views in the active tab page, in layout order
+    pub fn view_ids(&self) -> Vec<ViewId> {
+        self.tab().view_ids()
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.tabs.len()
+    }
+
+    /// Switches tab pages, or closes the active tab page returning the ids of the views it held
+    pub fn handle_tab_action(&mut self, action: TabAction) -> Vec<ViewId> {
+        let count = self.tabs.len();
+        match action {
+            TabAction::Next => self.set_active_tab((self.active_tab + 1) % count),
+            TabAction::Previous => self.set_active_tab((self.active_tab + count - 1) % count),
+            TabAction::Goto(number) => if number > 0 && number <= count {
+                self.set_active_tab(number - 1);
+            },
+            TabAction::Close => {
+                if count > 1 {
+                    let view_ids = self.remove_tab(self.active_tab);
+                    for view_id in view_ids.iter() {
+                        self.views.remove(view_id);
+                        self.pending_view_cmds.remove(view_id);
+                    }
+                    return view_ids;
+                } else {
+                    self.show_message("Cannot close last tab page");
+                }
+            },
+            // New tab pages are created once their view has been opened
+            TabAction::New(_) => (),
+        }
+
+        vec![]
+    }
+
+    fn set_active_tab(&mut self, index: usize) {
+        if index != self.active_tab {
+            self.active_tab = index;
+            self.mark_tab_dirty();
+        }
+    }
+
+    // Every view in the active tab page needs drawing after switching tabs
+    fn mark_tab_dirty(&mut self) {
+        for view_id in self.view_ids().iter() {
+            if let Some(view) = self.views.get_mut(view_id) {
+                view.set_dirty(true);
+            }
+        }
+        self.tab_bar.set_dirty(true);
+    }
+
+    fn update_tab_bar(&mut self) {
+        let labels = self.tabs.iter().map(|tab| {
+            let view = tab.focused.as_ref().and_then(|view_id| self.views.get(view_id));
+            let name = view
+                .and_then(|view| view.name())
+                .map(|path| Path::new(&path).file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or(path.clone()))
+                .unwrap_or(String::from("[No Name]"));
+
+            TabLabel {
+                name,
+                modified: view.map(|view| !view.is_pristine()).unwrap_or(false),
+            }
+        }).collect();
+
+        self.tab_bar.set_tabs(labels, self.active_tab);
+    }
+
+    pub fn resize_focused(&mut self, direction: SplitDirection, steps: isize) -> bool {
+        let tab = self.tab_mut();
+        if let (Some(layout), Some(focused)) = (&mut tab.layout, &tab.focused) {
+            layout.resize(focused, direction, steps)
         } else {
-            screen_size[1]
+            false
+        }
+    }
+
+    /// Moves focus to another view, marking both the old and new views as dirty
+    pub fn set_focus(&mut self, view_id: ViewId) {
+        if self.focused().as_ref() == Some(&view_id) || !self.tab().contains(&view_id) {
+            return;
+        }
+        if let Some(old_view_id) = self.tab_mut().focused.take() {
+            if let Some(old_view) = self.views.get_mut(&old_view_id) {
+                old_view.set_focused(false);
+                old_view.set_dirty(true);
+            }
+        }
+        if let Some(new_view) = self.views.get_mut(&view_id) {
+            new_view.set_focused(true);
+            new_view.set_dirty(true);
+        }
+        self.tab_mut().focused = Some(view_id);
+    }
+
+    pub fn focus_direction(&mut self, motion: &Motion) {
+        let focused = match self.focused() {
+            Some(focused) => focused,
+            None => return,
         };
-        
-        let mut view_top = 0.0;
-        for (_, view) in self.views.iter_mut() {
-            view.poke(EditViewCommands::Position([0.0, view_top]));
-            view.poke(EditViewCommands::Resize([screen_size[0], view_height]));
+        let rects = match &self.tab().layout {
+            Some(layout) => layout.arrange([0.0, 0.0], self.screen_size),
+            None => return,
+        };
+        if let Some(view_id) = neighbour(&rects, &focused, motion) {
+            self.set_focus(view_id);
+        }
+    }
+
+    pub fn focus_cycle(&mut self, forward: bool) {
+        let mut view_ids = self.view_ids();
+        if !forward {
+            view_ids.reverse();
+        }
+        let next = match &self.tab().focused {
+            Some(focused) => view_ids.iter()
+                .skip_while(|id| *id != focused)
+                .nth(1)
+                .or(view_ids.first())
+                .cloned(),
+            None => view_ids.first().cloned(),
+        };
+        if let Some(view_id) = next {
+            self.set_focus(view_id);
+        }
+    }
+
+    pub fn exchange_focused(&mut self) {
+        let tab = self.tab_mut();
+        let exchanged = match (&mut tab.layout, &tab.focused) {
+            (Some(layout), Some(focused)) => layout.exchange(focused),
+            _ => None,
+        };
+        // Focus stays in the same place on screen, which now holds the other view
+        if let Some(view_id) = exchanged {
+            self.set_focus(view_id);
+        }
+    }
+
+    pub fn rotate_focused(&mut self, motion: &Motion) {
+        let tab = self.tab_mut();
+        if let (Some(layout), Some(focused)) = (&mut tab.layout, &tab.focused) {
+            layout.rotate(focused, motion);
+        }
+    }
+
+    pub fn equalize_views(&mut self) {
+        if let Some(layout) = &mut self.tab_mut().layout {
+            layout.equalize();
+        }
+    }
+
+    /// Removes every view other than the focused view from the layout, returning the removed ids
+    pub fn only_focused(&mut self) -> Vec<ViewId> {
+        let focused = match self.focused() {
+            Some(focused) => focused,
+            None => return vec![],
+        };
+        let removed: Vec<ViewId> = self.view_ids().into_iter()
+            .filter(|id| *id != focused)
+            .collect();
+
+        for view_id in removed.iter() {
+            self.views.remove(view_id);
+            self.pending_view_cmds.remove(view_id);
+        }
+        self.tab_mut().layout = Some(Layout::View(focused));
+
+        removed
+    }
+
+    /// Positions the tab bar, shown when there is more than one tab page, and the views of
+    /// the active tab page beneath it
+    pub fn layout_views(&mut self, screen_size: [f32; 2]) {
+        self.screen_size = screen_size;
 
-            view_top += view_height;
+        let tab_bar_height = if self.tabs.len() > 1 {
+            self.tab_bar.size()[1]
+        } else {
+            0.0
+        };
+        self.tab_bar.set_position(0.0, 0.0);
+        self.tab_bar.set_width(screen_size[0]);
+
+        let rects = match &self.tab().layout {
+            Some(layout) => layout.arrange(
+                [0.0, tab_bar_height],
+                [screen_size[0], screen_size[1] - tab_bar_height]),
+            None => return,
+        };
+
+        for rect in rects.into_iter() {
+            if let Some(view) = self.views.get_mut(&rect.view_id) {
+                view.poke(EditViewCommands::Position(rect.position));
+                view.poke(EditViewCommands::Resize(rect.size));
+            }
         }
     }
 
+    /// Rebuilds the display list from the tab bar and the views of the active tab page when any
+    /// of them have changed, returns true if a redraw is needed
+    pub fn queue_draw(&mut self) -> bool {
+        let focused = match self.focused() {
+            Some(focused) => focused,
+            None => return false,
+        };
+        let view_ids = self.view_ids();
+        for view_id in view_ids.iter() {
+            if let Some(view) = self.views.get_mut(view_id) {
+                view.set_focused(*view_id == focused);
+            }
+        }
+        self.update_tab_bar();
+
+        let views_dirty = view_ids.iter()
+            .filter_map(|view_id| self.views.get(view_id))
+            .any(|view| view.dirty());
+        if !views_dirty && !self.tab_bar.dirty() {
+            return false;
+        }
+
+        // Widgets share the display list, so everything on screen is drawn together
+        self.display_list.clear();
+        if self.tabs.len() > 1 {
+            self.tab_bar.queue_draw(&mut self.display_list);
+        } else {
+            self.tab_bar.set_dirty(false);
+        }
+        for view_id in view_ids.iter() {
+            if let Some(view) = self.views.get_mut(view_id) {
+                view.queue_draw(&mut self.display_list);
+                view.set_dirty(false);
+            }
+        }
+
+        true
+    }
+
+    pub fn display_list(&self) -> &DisplayList {
+        &self.display_list
+    }
+
     pub fn process_keyboard_input(&self, 
         mode: Mode, modifiers: ModifiersState, key: Key
     ) -> Option<(Vec<Action>, ActionTarget)> {
@@ -164,7 +727,7 @@ impl EditorState {
         if kc.is_none() {
             return None;
         }
-        if self.focused.is_none() {
+        if self.focused().is_none() {
             return None;
         }
 
@@ -182,70 +745,411 @@ impl EditorState {
         None
     }
 
-    pub fn update_from_input(&mut self, input: Arc<Mutex<InputState>>) -> bool {
-        if let Ok(ref input) = input.clone().try_lock() {
-            let should_keydown = input.key.is_some() 
-                || input.modifiers.ctrl() || input.modifiers.shift() || input.modifiers.alt();
-            let should_mouse = input.mouse.button.is_some()
-                || input.mouse.line_scroll.1 != 0.0;
+    /// The view of the active tab page under a point in the window
+    fn view_at(&self, point: [f32; 2]) -> Option<ViewId> {
+        self.view_ids().into_iter()
+            .find(|view_id| self.views.get(view_id).map(|view| view.contains(point)).unwrap_or(false))
+    }
 
-            let mut handled = false;
-            if self.focused.is_none() { 
-                return false;
+    // Focuses the view under the pointer, then runs the binding for the button there with the
+    // line and column it was pressed at
+    fn click(&mut self, button: MouseButton, input: &InputState) -> bool {
+        let point = [input.mouse.cursor.0, input.mouse.cursor.1];
+        let view_id = match self.view_at(point) {
+            Some(view_id) => view_id,
+            None => return false,
+        };
+        self.set_focus(view_id.clone());
+
+        let view = match self.views.get_mut(&view_id) {
+            Some(view) => view,
+            None => return false,
+        };
+        let mode = view.mode();
+        let binding = self.mouse_bindings.iter()
+            .find(|binding| binding.is_triggered_by(mode, input.modifiers, &button));
+        if let (Some(binding), Some((line, col))) = (binding, view.hit_test(point, &mut self.display_list)) {
+            let granularity = match input.mouse.clicks {
+                2 => SelectionGranularity::Word,
+                3 => SelectionGranularity::Line,
+                _ => SelectionGranularity::Point,
+            };
+            view.poke(EditViewCommands::Pointer(line, col, granularity));
+            for action in binding.get_actions().into_iter() {
+                view.poke_target(EditViewCommands::Action(action), binding.get_target());
             }
+            if button == MouseButton::Left {
+                self.dragging = Some(view_id);
+            }
+        }
 
-            let mut actions: Vec<Action> = vec!();
-            let mut target: Option<ActionTarget> = None;
-            if let edit_view = self.get_focused_view() {
-                let mode = edit_view.mode();
+        true
+    }
 
-                if should_keydown && input.key.is_some() {
-                    if let Some((bound_actions, action_target)) 
-                        = &self.process_keyboard_input(mode, input.modifiers, input.key.unwrap()) {
-                            actions = bound_actions.clone();
-                            target = Some(action_target.clone());
-                    }
+    /// Extends the selection being dragged out to the pointer, scrolling the view when the
+    /// pointer is past its top or bottom. Returns true if a drag is under way.
+    pub fn drag(&mut self, input: &InputState) -> bool {
+        if input.mouse.state != Some(ElementState::Pressed) {
+            self.dragging = None;
+        }
+        let view = match &self.dragging {
+            Some(view_id) => self.views.get_mut(view_id),
+            None => None,
+        };
+        let view = match view {
+            Some(view) => view,
+            None => {
+                self.auto_scroll = false;
+                return false;
+            },
+        };
+
+        let point = [input.mouse.cursor.0, input.mouse.cursor.1];
+        self.auto_scroll = view.drag(point, &mut self.display_list);
+        true
+    }
+
+    /// Whether a drag is past the edge of its view, so should go on scrolling while the pointer
+    /// is still
+    pub fn auto_scrolling(&self) -> bool {
+        self.auto_scroll
+    }
+
+    pub fn update_from_input(&mut self, input: &InputState) -> bool {
+        let should_keydown = input.key.is_some() 
+            || input.modifiers.ctrl() || input.modifiers.shift() || input.modifiers.alt();
+        let should_mouse = input.mouse.button.is_some()
+            || input.mouse.line_scroll.1 != 0.0;
+
+        let mut handled = false;
+        if self.focused().is_none() { 
+            return false;
+        }
+        if let Some(button) = input.mouse.pressed {
+            handled = self.click(button, input);
+        } else if input.mouse.dragged {
+            handled = self.drag(input);
+        } else if input.mouse.state != Some(ElementState::Pressed) {
+            self.dragging = None;
+            self.auto_scroll = false;
+        }
+
+        let mut actions: Vec<Action> = vec!();
+        let mut target: Option<ActionTarget> = None;
+        if let Some(edit_view) = self.get_focused_view() {
+            let mode = edit_view.mode();
+
+            if should_keydown && input.key.is_some() {
+                if let Some((bound_actions, action_target)) 
+                    = &self.process_keyboard_input(mode, input.modifiers, input.key.unwrap()) {
+                        actions = bound_actions.clone();
+                        target = Some(action_target.clone());
                 }
             }
-            if actions.len() > 0 && target.is_some() {
-                match target.unwrap() {
-                    ActionTarget::EventLoop => {
-                        for action in actions.iter() {
-                            match self.event_proxy.send_event(EditorEvent::Action(action.clone())) {
-                                Ok(_) => (),
-                                Err(err) => println!("unable to send event to event_loop: {}", err),
-                            }
+        }
+        if actions.len() > 0 && target.is_some() {
+            match target.unwrap() {
+                ActionTarget::EventLoop => {
+                    for action in actions.iter() {
+                        match self.event_proxy.send_event(EditorEvent::Action(action.clone())) {
+                            Ok(_) => (),
+                            Err(err) => println!("unable to send event to event_loop: {}", err),
                         }
-                    },
-                    ActionTarget::FocusedView | ActionTarget::StatusBar => {
-                        if let edit_view = self.get_focused_view() {
-                            for action in actions.iter() {
-                                edit_view.poke_target(EditViewCommands::Action(action.clone()), target.unwrap());
-                            }
+                    }
+                },
+                ActionTarget::FocusedView | ActionTarget::StatusBar => {
+                    if let Some(edit_view) = self.get_focused_view() {
+                        for action in actions.iter() {
+                            edit_view.poke_target(EditViewCommands::Action(action.clone()), target.unwrap());
                         }
                     }
                 }
-                handled = true;
             }
+            handled = true;
+        }
 
-            if let edit_view = self.get_focused_view() {
-                if should_mouse {
-                    if input.mouse.line_scroll.1 != 0.0 {
-                        edit_view.mouse_scroll(input.mouse.line_scroll.1);
-                        handled = true;
-                    }
-                }
-                // If focus changed, force dirty
-                if input.window_focus_changed {
-                    edit_view.set_dirty(true);
+        if let Some(edit_view) = self.get_focused_view() {
+            if should_mouse {
+                if input.mouse.line_scroll.1 != 0.0 {
+                    edit_view.mouse_scroll(input.mouse.line_scroll.1);
                     handled = true;
                 }
             }
+            // If focus changed, force dirty
+            if input.window_focus_changed {
+                edit_view.set_dirty(true);
+                handled = true;
+            }
+        }
 
-            handled
-        } else {
-            println!("unable to lock input in update_from_input");
-            false
+        handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::Receiver;
+    use std::time::Instant;
+
+    use serde_json::{
+        json,
+        Value,
+    };
+    use winit::event::{
+        ElementState,
+        ModifiersState,
+        MouseButton,
+        VirtualKeyCode,
+    };
+    use rpc::SplitDirection;
+
+    use crate::editor::App;
+    use crate::editor::fake_core::FakeCore;
+    use crate::editor::layout::Placement;
+    use crate::editor::session::Session;
+    use crate::editor::timers::{
+        Timer,
+        AUTO_SCROLL_INTERVAL,
+    };
+    use crate::editor::ui::view::EditView;
+    use crate::editor::view_commands::EditViewCommands;
+    use crate::cli::OpenMode;
+    use crate::events::EditorEvent;
+    use crate::events::binding::Key;
+    use crate::events::state::InputState;
+    use crate::editor::tests::{
+        SCREEN_SIZE,
+        FONT_SIZE,
+        start,
+        attach_view,
+        split_view,
+        settle,
+        take_sent,
+        update,
+        click,
+    };
+
+    #[test]
+    fn click_focuses_split_and_places_cursor() {
+        let (fake, mut app, events) = start();
+        attach_view(&mut app, "view-id-1");
+        attach_view(&mut app, "view-id-2");
+        app.state.set_focus("view-id-1".to_string());
+        app.state.layout_views(SCREEN_SIZE);
+        fake.notify("update", update("view-id-2", &["hello world"]));
+        settle(&mut app, &events);
+        take_sent(&fake, &app);
+
+        // Past the end of the only line, the cursor goes to the end of it
+        let view = &app.state.views["view-id-2"];
+        let (position, size) = (view.position(), view.size());
+        let mut input = InputState::new();
+        input.mouse.pressed = Some(MouseButton::Left);
+        input.mouse.cursor = (position[0] + size[0] - 1.0, position[1] + size[1] - 1.0);
+        assert!(app.state.update_from_input(&input));
+
+        assert_eq!(app.state.focused(), Some("view-id-2".to_string()));
+        let sent = take_sent(&fake, &app);
+        let gesture = &sent.iter()
+            .find(|message| message["params"]["method"] == json!("gesture"))
+            .expect("no gesture sent")["params"];
+        assert_eq!(gesture["view_id"], json!("view-id-2"));
+        assert_eq!(gesture["params"], json!({ "line": 0, "col": 11, "ty": "point_select" }));
+    }
+
+    // The type of each gesture sent since the last call
+    fn take_gestures(fake: &FakeCore, app: &App) -> Vec<Value> {
+        take_sent(fake, app).into_iter()
+            .filter(|message| message["params"]["method"] == json!("gesture"))
+            .map(|message| message["params"]["params"]["ty"].clone())
+            .collect()
+    }
+
+    #[test]
+    fn clicks_and_drags_send_gestures() {
+        let (fake, mut app, events) = start();
+        attach_view(&mut app, "view-id-1");
+        app.state.layout_views(SCREEN_SIZE);
+        let lines: Vec<String> = (0..100).map(|ix| format!("line {}", ix)).collect();
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+        fake.notify("update", update("view-id-1", &lines));
+        settle(&mut app, &events);
+        take_sent(&fake, &app);
+
+        let view = &app.state.views["view-id-1"];
+        let (position, size) = (view.position(), view.size());
+        let point = [position[0] + size[0] / 2.0, position[1] + size[1] / 2.0];
+
+        click(&mut app, point, ModifiersState::empty(), 2);
+        click(&mut app, point, ModifiersState::empty(), 3);
+        click(&mut app, point, ModifiersState::SHIFT, 1);
+        click(&mut app, point, ModifiersState::ALT, 1);
+        click(&mut app, point, ModifiersState::CTRL, 2);
+        assert_eq!(take_gestures(&fake, &app), vec![
+            json!({ "select": { "granularity": "word", "multi": false } }),
+            json!({ "select": { "granularity": "line", "multi": false } }),
+            json!({ "select_extend": { "granularity": "point" } }),
+            json!({ "select": { "granularity": "point", "multi": true } }),
+            json!({ "select": { "granularity": "word", "multi": true } }),
+        ]);
+
+        // Dragging below the view scrolls it, and goes on scrolling while the pointer is still
+        let mut input = InputState::new();
+        input.mouse.state = Some(ElementState::Pressed);
+        input.mouse.dragged = true;
+        input.mouse.cursor = (point[0], position[1] + size[1] + 100.0);
+        app.input = input;
+        assert!(app.state.update_from_input(&app.input));
+        assert!(app.state.auto_scrolling());
+        let now = Instant::now();
+        app.update_timers(now, false);
+        assert!(app.fire_timers(now + AUTO_SCROLL_INTERVAL));
+        assert!(app.timers.is_scheduled(&Timer::AutoScroll));
+        assert_eq!(take_gestures(&fake, &app), vec![json!("drag"), json!("drag")]);
+
+        // Releasing the button ends the drag
+        app.input.mouse.state = Some(ElementState::Released);
+        app.input.mouse.dragged = false;
+        app.state.update_from_input(&app.input);
+        app.update_timers(now, false);
+        assert!(!app.state.auto_scrolling());
+        assert!(!app.timers.is_scheduled(&Timer::AutoScroll));
+    }
+
+    // Lays out five views, with e beneath the rest, a on the left, and b above c and d
+    //
+    //     +---+---+
+    //     |   | b |
+    //     | a +-+-+
+    //     |   |c|d|
+    //     +---+-+-+
+    //     |   e   |
+    //     +-------+
+    fn start_with_splits() -> (FakeCore, App, Receiver<EditorEvent>) {
+        let (fake, mut app, events) = start();
+        attach_view(&mut app, "a");
+        attach_view(&mut app, "e");
+        app.state.set_focus("a".to_string());
+        split_view(&mut app, "b", SplitDirection::Vertical);
+        attach_view(&mut app, "c");
+        split_view(&mut app, "d", SplitDirection::Vertical);
+        app.state.layout_views(SCREEN_SIZE);
+
+        (fake, app, events)
+    }
+
+    // Types Ctrl-W then the key, with shift when it is given as upper case, returning the focused view
+    fn window_key(app: &mut App, events: &Receiver<EditorEvent>, key: char) -> String {
+        let press = |app: &mut App, key: VirtualKeyCode, modifiers: ModifiersState| {
+            let mut input = InputState::new();
+            input.key = Some(Key::KeyCode(key));
+            input.modifiers = modifiers;
+            app.state.update_from_input(&input);
+        };
+        press(app, VirtualKeyCode::W, ModifiersState::CTRL);
+        let code = match key.to_ascii_lowercase() {
+            'h' => VirtualKeyCode::H,
+            'j' => VirtualKeyCode::J,
+            'k' => VirtualKeyCode::K,
+            'l' => VirtualKeyCode::L,
+            'o' => VirtualKeyCode::O,
+            'r' => VirtualKeyCode::R,
+            'w' => VirtualKeyCode::W,
+            'x' => VirtualKeyCode::X,
+            _ => panic!("no window key {}", key),
+        };
+        let modifiers = if key.is_ascii_uppercase() { ModifiersState::SHIFT } else { ModifiersState::empty() };
+        press(app, code, modifiers);
+        for event in events.try_iter() {
+            app.handle_event(event, SCREEN_SIZE, FONT_SIZE);
+        }
+
+        app.state.focused().expect("no view is focused")
+    }
+
+    #[test]
+    fn window_keys_move_focus_to_the_neighbouring_split() {
+        let (_fake, mut app, events) = start_with_splits();
+        app.state.set_focus("a".to_string());
+
+        let moves = [
+            ('h', "a"), ('k', "a"), ('l', "b"), ('k', "b"), ('l', "b"), ('j', "c"), ('l', "d"), ('j', "e"),
+            ('j', "e"), ('k', "a"), ('j', "e"), ('l', "e"), ('h', "e"),
+        ];
+        for (key, focused) in moves.iter() {
+            assert_eq!(window_key(&mut app, &events, *key), *focused, "after Ctrl-W {}", key);
         }
+
+        app.state.set_focus("d".to_string());
+        assert_eq!(window_key(&mut app, &events, 'k'), "b");
+        app.state.set_focus("d".to_string());
+        assert_eq!(window_key(&mut app, &events, 'h'), "c");
+        assert_eq!(window_key(&mut app, &events, 'h'), "a");
+    }
+
+    #[test]
+    fn window_keys_cycle_focus_in_layout_order() {
+        let (_fake, mut app, events) = start_with_splits();
+        app.state.set_focus("a".to_string());
+
+        let forward: Vec<String> = (0..5).map(|_| window_key(&mut app, &events, 'w')).collect();
+        assert_eq!(forward, vec!["b", "c", "d", "e", "a"]);
+        let backward: Vec<String> = (0..2).map(|_| window_key(&mut app, &events, 'W')).collect();
+        assert_eq!(backward, vec!["e", "d"]);
+    }
+
+    #[test]
+    fn window_keys_exchange_and_rotate_views() {
+        let (_fake, mut app, events) = start_with_splits();
+        app.state.set_focus("c".to_string());
+
+        // Focus stays where it was on screen, on the view moved there
+        assert_eq!(window_key(&mut app, &events, 'x'), "d");
+        assert_eq!(app.state.view_ids(), vec!["a", "b", "d", "c", "e"]);
+
+        assert_eq!(window_key(&mut app, &events, 'r'), "d");
+        assert_eq!(app.state.view_ids(), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(window_key(&mut app, &events, 'R'), "d");
+        assert_eq!(app.state.view_ids(), vec!["a", "b", "d", "c", "e"]);
+
+        // The split holding a and the rest of the views is not made up of views only
+        app.state.set_focus("e".to_string());
+        assert_eq!(window_key(&mut app, &events, 'r'), "e");
+        assert_eq!(app.state.view_ids(), vec!["a", "b", "d", "c", "e"]);
+    }
+
+    #[test]
+    fn window_only_closes_every_other_view() {
+        let (fake, mut app, events) = start_with_splits();
+        app.state.set_focus("c".to_string());
+        take_sent(&fake, &app);
+
+        assert_eq!(window_key(&mut app, &events, 'o'), "c");
+        assert_eq!(app.state.view_ids(), vec!["c"]);
+        let closed: Vec<Value> = take_sent(&fake, &app).into_iter()
+            .filter(|message| message["method"] == json!("close_view"))
+            .map(|message| message["params"]["view_id"].clone())
+            .collect();
+        assert_eq!(closed, vec![json!("a"), json!("b"), json!("d"), json!("e")]);
+    }
+
+    #[test]
+    fn window_only_drops_commands_held_for_the_views_it_closes() {
+        let (fake, mut app, _events) = start();
+        fake.ignore("new_view");
+        let files = vec!["a.txt".to_string(), "b.txt".to_string()];
+        app.restore_session(Session::from_files(&files, OpenMode::Split(SplitDirection::Vertical)),
+            SCREEN_SIZE, FONT_SIZE, vec![], None);
+        app.state.add_view("view-id-1".to_string(), EditView::new(0, FONT_SIZE, None),
+            Placement::Replace("session-0".to_string()));
+
+        app.state.send_view_cmd("session-1", EditViewCommands::Message("held".to_string()));
+        assert!(app.state.has_pending_view_cmds());
+
+        app.state.only_focused();
+        assert_eq!(app.state.view_ids(), vec!["view-id-1"]);
+        assert!(!app.state.has_pending_view_cmds());
     }
 }
@@ -0,0 +1,652 @@
+use std::env;
+use std::io::{
+    BufRead,
+    BufReader,
+    ErrorKind,
+    Write,
+};
+use std::os::unix::net::UnixStream;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::sync::mpsc::{
+    self,
+    Receiver,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+use winit::event::{
+    ElementState,
+    ModifiersState,
+    MouseButton,
+    VirtualKeyCode,
+};
+use rpc::{
+    Action,
+    CoreNotification,
+    Mode,
+    SplitDirection,
+    TabAction,
+};
+
+use super::{
+    startup_commands,
+    ActionFlow,
+    App,
+    AppDispatcher,
+    CoreEvent,
+};
+use super::editor_rpc::{
+    Core,
+    Transport,
+};
+use super::fake_core::FakeCore;
+use super::layout::Placement;
+use super::native_core::NativeCore;
+use super::session::Session;
+use super::timers::{
+    Timer,
+    Timers,
+    MESSAGE_TIMEOUT,
+    KEY_SEQUENCE_TIMEOUT,
+};
+use super::ui::view::EditView;
+use super::ui::widget::Widget;
+use super::view_commands::EditViewCommands;
+use crate::cli::{
+    OpenMode,
+    Options,
+};
+use crate::remote::{
+    self,
+    parse_file_line,
+    RemoteServer,
+    Request,
+};
+use crate::events::{
+    EditorEvent,
+    EditorEventLoopProxy,
+    binding::Key,
+    state::InputState,
+};
+
+pub(crate) const SCREEN_SIZE: [f32; 2] = [800.0, 600.0];
+pub(crate) const FONT_SIZE: f32 = 20.0;
+pub(crate) const TIMEOUT: Duration = Duration::from_secs(5);
+
+// The app with the core's events going to the receiver, `settle` plays the part of the event loop
+pub(crate) fn start_with<T: Transport + 'static>(transport: T, rx: Receiver<Value>) -> (App, Receiver<EditorEvent>) {
+    let (sender, events) = mpsc::channel();
+    let proxy = EditorEventLoopProxy::Headless(sender);
+    let core = Core::new(transport, rx, AppDispatcher::new(proxy.clone()));
+
+    (App::new(core, proxy), events)
+}
+
+pub(crate) fn start() -> (FakeCore, App, Receiver<EditorEvent>) {
+    let (fake, rx) = FakeCore::new();
+    let (app, events) = start_with(fake.clone(), rx);
+
+    (fake, app, events)
+}
+
+// Starts the app on the native core with an empty view, `view-id-1`
+pub(crate) fn start_native() -> (App, Receiver<EditorEvent>) {
+    let (native, rx) = NativeCore::new();
+    let (mut app, events) = start_with(native, rx);
+
+    attach_view(&mut app, "view-id-1");
+    let response = app.core.lock().unwrap().send_request_future("new_view", &json!({}), Some(TIMEOUT));
+    assert_eq!(response.wait(), Ok(json!("view-id-1")));
+    // Lines outside the viewport are only invalidated
+    app.send_notification("edit", &edit("view-id-1", "scroll", json!([0, 10]))["params"]);
+    settle(&mut app, &events);
+
+    (app, events)
+}
+
+// Sends an edit to the native core and applies the update it sends back
+pub(crate) fn native_edit(app: &mut App, events: &Receiver<EditorEvent>, method: &str, params: Value) {
+    app.send_notification("edit", &edit("view-id-1", method, params)["params"]);
+    settle(app, events);
+}
+
+// Adds a view as the `new_view` response would, but without a round trip through the core
+pub(crate) fn attach_view(app: &mut App, view_id: &str) {
+    split_view(app, view_id, SplitDirection::Horizontal);
+}
+
+// As attach_view, splitting the focused view in the given direction
+pub(crate) fn split_view(app: &mut App, view_id: &str, direction: SplitDirection) {
+    app.state.add_view(view_id.to_string(), EditView::new(0, FONT_SIZE, None), Placement::Split(direction));
+
+    let proxy = app.state.get_event_proxy();
+    let view = app.state.views.get_mut(view_id).unwrap();
+    view.poke(EditViewCommands::Core(Arc::downgrade(&app.core)));
+    view.poke(EditViewCommands::ViewId(view_id.to_string()));
+    view.poke(EditViewCommands::Proxy(proxy));
+}
+
+pub(crate) fn poke(app: &mut App, view_id: &str, command: EditViewCommands) {
+    app.state.send_view_cmd(view_id, command);
+}
+
+// Applies a notification as if it had come from the core
+pub(crate) fn notify(app: &mut App, method: &str, params: Value) {
+    let notification = CoreNotification::decode(method, &params).unwrap();
+    app.handle_core_event(CoreEvent::Notification(notification));
+}
+
+// The fake and native cores answer any request after handling everything sent before it, so the
+// answer to this one shows they have caught up
+pub(crate) const BARRIER_METHOD: &str = "barrier";
+
+// Waits for the core to handle everything sent so far, then handles the events it caused, until
+// no more arrive
+pub(crate) fn settle(app: &mut App, events: &Receiver<EditorEvent>) {
+    loop {
+        let response = app.core.lock().unwrap().send_request_future(BARRIER_METHOD, &json!({}), Some(TIMEOUT));
+        response.wait().expect("timed out waiting for the core");
+
+        let waiting: Vec<EditorEvent> = events.try_iter().collect();
+        if waiting.is_empty() {
+            return;
+        }
+        for event in waiting {
+            app.handle_event(event, SCREEN_SIZE, FONT_SIZE);
+        }
+    }
+}
+
+// Everything the frontend has sent since the last call, once it has all reached the core
+pub(crate) fn take_sent(fake: &FakeCore, app: &App) -> Vec<Value> {
+    let response = app.core.lock().unwrap().send_request_future(BARRIER_METHOD, &json!({}), Some(TIMEOUT));
+    response.wait().expect("timed out waiting for the core");
+
+    fake.take_sent().into_iter()
+        .filter(|message| message["method"] != json!(BARRIER_METHOD))
+        .collect()
+}
+
+pub(crate) fn line_texts(app: &App, view_id: &str) -> Vec<String> {
+    let line_cache = app.state.views[view_id].line_cache();
+
+    (0..line_cache.height())
+        .map(|ix| line_cache.get_line(ix).map(|line| line.text().to_string()).unwrap_or_default())
+        .collect()
+}
+
+pub(crate) fn update(view_id: &str, lines: &[&str]) -> Value {
+    let lines: Vec<Value> = lines.iter()
+        .enumerate()
+        .map(|(ix, text)| json!({ "text": format!("{}\n", text), "ln": ix + 1 }))
+        .collect();
+
+    json!({
+        "view_id": view_id,
+        "update": {
+            "ops": [{ "op": "ins", "n": lines.len(), "lines": lines }],
+            "pristine": true,
+            "annotations": [],
+        },
+    })
+}
+
+pub(crate) fn edit(view_id: &str, method: &str, params: Value) -> Value {
+    json!({
+        "method": "edit",
+        "params": {
+            "method": method,
+            "params": params,
+            "view_id": view_id,
+        },
+    })
+}
+
+#[test]
+fn new_view_request() {
+    let (fake, mut app, events) = start();
+    app.open_new_view(Some("notes.txt".to_string()),
+        Placement::Split(SplitDirection::Horizontal), SCREEN_SIZE, FONT_SIZE);
+
+    settle(&mut app, &events);
+    assert_eq!(app.focused_view_id(), Some("view-id-1".to_string()));
+    assert_eq!(fake.sent()[0], json!({
+        "method": "new_view",
+        "params": { "file_path": "notes.txt" },
+        "id": 0,
+    }));
+}
+
+#[test]
+fn opening_in_place_of_a_view_closes_it() {
+    let (fake, mut app, events) = start();
+    app.open_new_view(None, Placement::Split(SplitDirection::Horizontal), SCREEN_SIZE, FONT_SIZE);
+    settle(&mut app, &events);
+    take_sent(&fake, &app);
+
+    app.handle_action(Action::Open(Some("notes.txt".to_string())), SCREEN_SIZE, FONT_SIZE);
+    settle(&mut app, &events);
+    assert_eq!(app.state.view_ids(), vec!["view-id-2".to_string()]);
+    assert_eq!(app.focused_view_id(), Some("view-id-2".to_string()));
+    assert!(take_sent(&fake, &app).contains(&json!({
+        "method": "close_view",
+        "params": { "view_id": "view-id-1" },
+    })));
+}
+
+#[test]
+fn core_notifications_mark_views_dirty() {
+    let (fake, mut app, events) = start();
+    attach_view(&mut app, "view-id-1");
+    app.state.views.get_mut("view-id-1").unwrap().set_dirty(false);
+
+    fake.notify("scroll_to", json!({ "view_id": "view-id-1", "line": 0, "col": 0 }));
+    settle(&mut app, &events);
+
+    assert!(app.state.views["view-id-1"].dirty());
+}
+
+#[test]
+fn every_core_message_is_applied_in_order() {
+    let (fake, mut app, events) = start();
+    attach_view(&mut app, "view-id-1");
+
+    for ix in 0..200 {
+        fake.notify("update", update("view-id-1", &[&ix.to_string()]));
+        poke(&mut app, "view-id-1", EditViewCommands::Action(Action::InsertChar('a')));
+    }
+    settle(&mut app, &events);
+
+    assert_eq!(line_texts(&app, "view-id-1"), vec!["199\n"]);
+}
+
+#[test]
+fn notifications_without_a_view_are_kept() {
+    let (fake, mut app, events) = start();
+    fake.notify("update", update("view-id-1", &["early"]));
+    fake.notify("scroll_to", json!({ "view_id": "view-id-1", "line": 0 }));
+
+    app.open_new_view(None, Placement::Split(SplitDirection::Horizontal), SCREEN_SIZE, FONT_SIZE);
+
+    settle(&mut app, &events);
+    assert_eq!(app.focused_view_id(), Some("view-id-1".to_string()));
+    assert_eq!(line_texts(&app, "view-id-1"), vec!["early\n"]);
+}
+
+#[test]
+fn notifications_after_a_view_closes_are_dropped() {
+    let (fake, mut app, events) = start();
+    attach_view(&mut app, "view-id-1");
+    app.close_view("view-id-1".to_string());
+
+    fake.notify("update", update("view-id-1", &["late"]));
+    fake.notify("scroll_to", json!({ "view_id": "view-id-1", "line": 0 }));
+    settle(&mut app, &events);
+    assert!(!app.state.has_pending_view_cmds());
+
+    // Held while a view opens, what is left once it has are for views which will never be added
+    fake.notify("update", update("view-id-9", &["never"]));
+    app.open_new_view(None, Placement::Split(SplitDirection::Horizontal), SCREEN_SIZE, FONT_SIZE);
+    settle(&mut app, &events);
+    assert_eq!(app.state.view_ids().len(), 1);
+    assert!(!app.state.has_pending_view_cmds());
+}
+
+#[test]
+fn malformed_notifications_are_dropped() {
+    let (fake, mut app, events) = start();
+    attach_view(&mut app, "view-id-1");
+
+    fake.notify("scroll_to", json!({ "view_id": "view-id-1", "line": "ten" }));
+    fake.notify("not_a_method", json!({}));
+    fake.notify("update", update("view-id-1", &["still here"]));
+    settle(&mut app, &events);
+
+    assert_eq!(line_texts(&app, "view-id-1"), vec!["still here\n"]);
+}
+
+#[test]
+fn status_notifications_reach_the_view() {
+    let (_fake, mut app, _events) = start();
+    attach_view(&mut app, "view-id-1");
+    app.state.views.get_mut("view-id-1").unwrap().take_new_message();
+
+    notify(&mut app, "replace_status",
+        json!({ "view_id": "view-id-1", "status": { "chars": "bar", "preserve_case": false } }));
+    assert!(app.state.views.get_mut("view-id-1").unwrap().take_new_message());
+
+    notify(&mut app, "show_hover", json!({ "request_id": 1, "result": "fn main()" }));
+    assert!(app.state.views.get_mut("view-id-1").unwrap().take_new_message());
+
+    let item = json!({ "view_id": "view-id-1", "source": "git", "key": "branch", "value": "main", "alignment": "left" });
+    notify(&mut app, "add_status_item", item);
+    notify(&mut app, "update_status_item", json!({ "view_id": "view-id-1", "key": "branch", "value": "dev" }));
+    notify(&mut app, "remove_status_item", json!({ "view_id": "view-id-1", "key": "branch" }));
+    assert!(!app.state.views.get_mut("view-id-1").unwrap().take_new_message());
+}
+
+#[test]
+fn measure_width_is_answered() {
+    let (fake, mut app, events) = start();
+    fake.request(7, "measure_width", json!([{ "id": 0, "strings": ["", "abc"] }]));
+
+    settle(&mut app, &events);
+    let response = take_sent(&fake, &app).into_iter()
+        .find(|message| message["method"].is_null())
+        .unwrap();
+    assert_eq!(response["id"], json!(7));
+    assert_eq!(response["result"][0][0], json!(0.0));
+    assert!(response["result"][0][1].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn failed_new_view_adds_no_view() {
+    let (fake, mut app, events) = start();
+    fake.error_for("new_view", json!({ "code": 1, "message": "Permission denied" }));
+
+    app.open_new_view(Some("secret.txt".to_string()),
+        Placement::Split(SplitDirection::Horizontal), SCREEN_SIZE, FONT_SIZE);
+    let event = events.recv_timeout(TIMEOUT).expect("timed out waiting for the core");
+
+    // Headless, the run stops rather than waiting for a view
+    assert!(matches!(app.handle_event(event, SCREEN_SIZE, FONT_SIZE), ActionFlow::Failed));
+    assert!(app.state.views.is_empty());
+}
+
+#[test]
+fn failed_session_views_leave_the_layout() {
+    let (fake, mut app, events) = start();
+    fake.error_for("new_view", json!({ "code": 1, "message": "Permission denied" }));
+
+    let files = vec!["a.txt".to_string(), "b.txt".to_string()];
+    app.restore_session(Session::from_files(&files, OpenMode::Tabs), SCREEN_SIZE, FONT_SIZE, vec![], None);
+    settle(&mut app, &events);
+
+    assert_eq!(app.state.tab_count(), 1);
+    assert!(app.state.view_ids().is_empty());
+    assert_eq!(app.state.focused(), None);
+}
+
+#[test]
+fn stdin_is_only_opened_from_the_command_line() {
+    let (fake, mut app, events) = start();
+    let files = vec!["-".to_string()];
+    app.restore_session(Session::from_files(&files, OpenMode::First), SCREEN_SIZE, FONT_SIZE, vec![],
+        Some("piped\n"));
+    settle(&mut app, &events);
+    notify(&mut app, "update", update("view-id-1", &[]));
+
+    let sent = take_sent(&fake, &app);
+    assert_eq!(sent[0]["method"], json!("new_view"));
+    assert!(sent[0]["params"]["file_path"].is_null());
+    assert!(sent.iter().any(|message| message["params"]["params"]["chars"] == json!("piped\n")));
+
+    // Reading it again would block the event loop until standard input is closed
+    app.state.views.get_mut("view-id-1").unwrap().take_new_message();
+    app.handle_action(Action::Open(Some("-".to_string())), SCREEN_SIZE, FONT_SIZE);
+    assert!(take_sent(&fake, &app).iter().all(|message| message["method"] != json!("new_view")));
+    assert!(app.state.views.get_mut("view-id-1").unwrap().take_new_message());
+}
+
+#[test]
+fn last_tab_page_stays_open() {
+    let (_fake, mut app, _events) = start();
+    attach_view(&mut app, "view-id-1");
+    app.state.views.get_mut("view-id-1").unwrap().take_new_message();
+
+    app.handle_action(Action::Tab(TabAction::Close), SCREEN_SIZE, FONT_SIZE);
+    assert_eq!(app.state.tab_count(), 1);
+    assert!(app.state.views.contains_key("view-id-1"));
+    assert!(app.state.views.get_mut("view-id-1").unwrap().take_new_message());
+}
+
+#[test]
+fn files_which_are_not_opened_are_reported() {
+    let mut options = Options::default();
+    options.files = vec!["a.txt".to_string(), "b.txt".to_string(), "c.txt".to_string()];
+    match startup_commands(&options).first() {
+        Some(EditViewCommands::Message(message)) => assert!(message.starts_with("2 more files were not opened")),
+        _ => panic!("expected a message about the files not opened"),
+    }
+
+    options.open_mode = OpenMode::Tabs;
+    assert!(startup_commands(&options).is_empty());
+}
+
+#[test]
+fn input_before_views_open_is_ignored() {
+    let (fake, mut app, _events) = start();
+    fake.ignore("new_view");
+    let interval = Duration::from_millis(500);
+    app.timers = Timers::new(Some(interval), None);
+    app.input.window_focused = true;
+
+    let files = vec!["a.txt".to_string(), "b.txt".to_string()];
+    app.restore_session(Session::from_files(&files, OpenMode::Split(SplitDirection::Vertical)),
+        SCREEN_SIZE, FONT_SIZE, vec![], None);
+    assert_eq!(app.state.focused(), None);
+
+    let mut input = InputState::new();
+    input.key = Some(Key::KeyCode(VirtualKeyCode::J));
+    assert!(!app.state.update_from_input(&input));
+    click(&mut app, [10.0, 10.0], ModifiersState::empty(), 1);
+    app.run_command("w".to_string());
+
+    let now = Instant::now();
+    app.restart_blink(now);
+    assert!(app.fire_timers(now + interval));
+    assert!(app.timers.is_scheduled(&Timer::CursorBlink));
+}
+
+// An update from the core saying the buffer has changes which are not saved
+pub(crate) fn modified(view_id: &str, lines: &[&str]) -> Value {
+    let mut params = update(view_id, lines);
+    params["update"]["pristine"] = json!(false);
+    params
+}
+
+#[test]
+fn a_crashed_core_fails_a_headless_run() {
+    let (_fake, mut app, _events) = start();
+    assert!(matches!(app.handle_core_event(CoreEvent::Disconnected), ActionFlow::Exit));
+
+    let crashed = CoreEvent::Crashed(String::from("exit status: 101"));
+    assert!(matches!(app.handle_core_event(crashed), ActionFlow::Continue));
+    assert!(matches!(app.handle_core_event(CoreEvent::Disconnected), ActionFlow::Failed));
+}
+
+#[test]
+fn quitting_is_refused_while_a_buffer_has_changes() {
+    let (_fake, mut app, _events) = start();
+    attach_view(&mut app, "view-id-1");
+    notify(&mut app, "update", modified("view-id-1", &["changed"]));
+
+    // Headless, a refused quit fails the run rather than waiting for a command which never comes
+    assert!(matches!(app.handle_action(Action::QuitAll, SCREEN_SIZE, FONT_SIZE), ActionFlow::Failed));
+    assert!(matches!(app.handle_action(Action::ForceQuitAll, SCREEN_SIZE, FONT_SIZE), ActionFlow::Exit));
+
+    notify(&mut app, "update", update("view-id-1", &["changed"]));
+    assert!(matches!(app.handle_action(Action::QuitAll, SCREEN_SIZE, FONT_SIZE), ActionFlow::Exit));
+}
+
+#[test]
+fn undecodable_notifications_are_reported_as_errors() {
+    let (fake, app, events) = start();
+    fake.notify("scroll_to", json!({ "line": 1 }));
+    let response = app.core.lock().unwrap().send_request_future(BARRIER_METHOD, &json!({}), Some(TIMEOUT));
+    response.wait().expect("timed out waiting for the core");
+
+    let errors: Vec<String> = events.try_iter()
+        .filter_map(|event| match event {
+            EditorEvent::Core(CoreEvent::Error(message)) => Some(message),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("unable to decode core->fe scroll_to"), "{}", errors[0]);
+}
+
+#[test]
+fn cursor_blinks_and_typing_holds_it() {
+    let (_fake, mut app, _events) = start();
+    attach_view(&mut app, "view-id-1");
+    let interval = Duration::from_millis(500);
+    app.timers = Timers::new(Some(interval), None);
+    app.input.window_focused = true;
+
+    let now = Instant::now();
+    app.restart_blink(now);
+    app.state.views.get_mut("view-id-1").unwrap().set_dirty(false);
+
+    assert!(!app.fire_timers(now + interval / 2));
+    assert!(app.fire_timers(now + interval));
+    assert!(!app.state.views["view-id-1"].cursor_visible());
+    assert!(app.state.views["view-id-1"].dirty());
+
+    // A key shows it again, and puts off the next blink
+    app.restart_blink(now + interval + interval / 2);
+    assert!(app.state.views["view-id-1"].cursor_visible());
+    assert!(!app.fire_timers(now + interval * 2));
+    assert!(app.fire_timers(now + interval * 3));
+    assert!(!app.state.views["view-id-1"].cursor_visible());
+}
+
+#[test]
+fn messages_and_unfinished_motions_time_out() {
+    let (_fake, mut app, _events) = start();
+    attach_view(&mut app, "view-id-1");
+    let now = Instant::now();
+
+    app.state.show_message("written");
+    poke(&mut app, "view-id-1", EditViewCommands::Action(Action::SetMode(Mode::Motion)));
+    app.update_timers(now, false);
+    assert!(app.timers.is_scheduled(&Timer::Message("view-id-1".to_string())));
+    assert!(app.timers.is_scheduled(&Timer::KeySequence("view-id-1".to_string())));
+
+    assert!(app.fire_timers(now + KEY_SEQUENCE_TIMEOUT));
+    assert_eq!(app.state.views["view-id-1"].mode(), Mode::Normal);
+    assert!(app.timers.is_scheduled(&Timer::Message("view-id-1".to_string())));
+
+    app.state.remove_view(&"view-id-1".to_string());
+    assert!(app.fire_timers(now + MESSAGE_TIMEOUT));
+    assert_eq!(app.timers.next_deadline(), None);
+}
+
+#[test]
+fn autosave_writes_modified_files() {
+    let (fake, mut app, events) = start();
+    for (view_id, filename) in [("view-id-1", Some("a.txt")), ("view-id-2", None)].iter() {
+        app.state.add_view(view_id.to_string(), EditView::new(0, FONT_SIZE, filename.map(String::from)),
+            Placement::Split(SplitDirection::Horizontal));
+        let core = Arc::downgrade(&app.core);
+        poke(&mut app, view_id, EditViewCommands::Core(core));
+        poke(&mut app, view_id, EditViewCommands::ViewId(view_id.to_string()));
+
+        let mut changed = update(view_id, &["changed"]);
+        changed["update"]["pristine"] = json!(false);
+        fake.notify("update", changed);
+    }
+    settle(&mut app, &events);
+    take_sent(&fake, &app);
+
+    let delay = Duration::from_secs(30);
+    app.timers = Timers::new(None, Some(delay));
+    let now = Instant::now();
+    app.update_timers(now, true);
+    assert!(app.fire_timers(now + delay));
+
+    let saves: Vec<Value> = take_sent(&fake, &app).into_iter()
+        .filter(|message| message["method"] == json!("save"))
+        .collect();
+    assert_eq!(saves.len(), 1);
+    assert_eq!(saves[0]["params"]["view_id"], json!("view-id-1"));
+}
+
+// Presses the left button at a point in the window, with the modifiers held, as the nth click
+pub(crate) fn click(app: &mut App, point: [f32; 2], modifiers: ModifiersState, clicks: usize) {
+    let mut input = InputState::new();
+    input.modifiers = modifiers;
+    input.mouse.state = Some(ElementState::Pressed);
+    input.mouse.pressed = Some(MouseButton::Left);
+    input.mouse.clicks = clicks;
+    input.mouse.cursor = (point[0], point[1]);
+    app.state.update_from_input(&input);
+}
+
+#[test]
+fn remote_wait_ends_when_its_view_closes() {
+    let (_fake, mut app, events) = start();
+    let socket = env::temp_dir().join(format!("editor-test-{}.sock", process::id()));
+    let socket = socket.to_string_lossy().to_string();
+    app.remote = Some(RemoteServer::listen(&socket, app.state.get_event_proxy()).unwrap());
+
+    let mut client = UnixStream::connect(&socket).unwrap();
+    let open = Request::Open { file: "/tmp/notes.txt".to_string(), line: None, wait: true };
+    writeln!(client, "{}", serde_json::to_string(&open).unwrap()).unwrap();
+    let event = events.recv_timeout(TIMEOUT).expect("timed out waiting for the remote request");
+    app.handle_event(event, SCREEN_SIZE, FONT_SIZE);
+    settle(&mut app, &events);
+    assert!(app.state.views.contains_key("view-id-1"));
+
+    // Nothing is sent while the view is open
+    client.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+    match lines.next() {
+        Some(Err(err)) => assert!(err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut),
+        other => panic!("expected no response, got {:?}", other),
+    }
+
+    client.set_read_timeout(Some(TIMEOUT)).unwrap();
+    app.handle_event(EditorEvent::Action(Action::Close), SCREEN_SIZE, FONT_SIZE);
+    let closed = lines.next().unwrap().unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&closed).unwrap(), json!({ "closed": "/tmp/notes.txt" }));
+
+    app.remote.unwrap().shutdown();
+}
+
+#[test]
+fn remote_client_waits_until_its_file_is_closed() {
+    let (_fake, mut app, events) = start();
+    let socket = env::temp_dir().join(format!("editor-test-client-{}.sock", process::id()));
+    let socket = socket.to_string_lossy().to_string();
+    app.remote = Some(RemoteServer::listen(&socket, app.state.get_event_proxy()).unwrap());
+
+    // As `editor --remote a.txt:3`, which returns once the request is sent
+    let (file, line) = parse_file_line("a.txt:3");
+    remote::send(&socket, vec![Request::Open { file, line, wait: false }]).unwrap();
+    while !app.state.views.contains_key("view-id-1") {
+        let event = events.recv_timeout(TIMEOUT).expect("timed out waiting for the remote open");
+        app.handle_event(event, SCREEN_SIZE, FONT_SIZE);
+        settle(&mut app, &events);
+    }
+
+    // Then `editor --remote-wait /tmp/b.txt`, which opens in place of the focused view
+    let (done, finished) = mpsc::channel();
+    let client_socket = socket.clone();
+    thread::spawn(move || {
+        let wait = Request::Open { file: "/tmp/b.txt".to_string(), line: None, wait: true };
+        done.send(remote::send(&client_socket, vec![wait])).unwrap();
+    });
+    while !app.state.views.contains_key("view-id-2") {
+        let event = events.recv_timeout(TIMEOUT).expect("timed out waiting for the remote wait");
+        app.handle_event(event, SCREEN_SIZE, FONT_SIZE);
+        settle(&mut app, &events);
+    }
+    assert_eq!(app.state.view_ids(), vec!["view-id-2"]);
+    assert_eq!(app.state.focused(), Some("view-id-2".to_string()));
+
+    // The client still waits while the file is open, and returns once it is closed
+    assert!(finished.recv_timeout(Duration::from_millis(50)).is_err());
+    app.handle_event(EditorEvent::Action(Action::Close), SCREEN_SIZE, FONT_SIZE);
+    assert_eq!(finished.recv_timeout(TIMEOUT).expect("the client is still waiting"), Ok(()));
+
+    app.remote.unwrap().shutdown();
+}
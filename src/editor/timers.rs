@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use super::state::ViewId;
+
+/// How long a message stays in the status bar
+pub const MESSAGE_TIMEOUT: Duration = Duration::from_secs(4);
+/// How long a partly typed motion, ie. `g` or `12`, waits for its next key
+pub const KEY_SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often a view scrolls a line while a selection is dragged past its edge
+pub const AUTO_SCROLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Something the event loop does at a set time
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Timer {
+    /// Shows or hides the cursor of the focused view
+    CursorBlink,
+    /// Clears the message from a view's status bar
+    Message(ViewId),
+    /// Gives up on a motion which is still waiting for keys
+    KeySequence(ViewId),
+    /// Saves the modified files once typing has paused
+    Autosave,
+    /// Scrolls on while a selection is dragged past the edge of a view
+    AutoScroll,
+}
+
+/// The deadlines the event loop waits until, at most one for each timer
+#[derive(Default)]
+pub struct Timers {
+    deadlines: HashMap<Timer, Instant>,
+    cursor_blink: Option<Duration>,
+    autosave: Option<Duration>,
+}
+
+impl Timers {
+    /// The cursor blinks and files are saved at these intervals, neither when None
+    pub fn new(cursor_blink: Option<Duration>, autosave: Option<Duration>) -> Self {
+        Self {
+            deadlines: HashMap::new(),
+            cursor_blink,
+            autosave,
+        }
+    }
+
+    pub fn cursor_blink(&self) -> Option<Duration> {
+        self.cursor_blink
+    }
+
+    pub fn autosave(&self) -> Option<Duration> {
+        self.autosave
+    }
+
+    /// Fires the timer after the delay from now, replacing any deadline it had
+    pub fn schedule(&mut self, timer: Timer, now: Instant, delay: Duration) {
+        self.deadlines.insert(timer, now + delay);
+    }
+
+    pub fn cancel(&mut self, timer: &Timer) {
+        self.deadlines.remove(timer);
+    }
+
+    pub fn is_scheduled(&self, timer: &Timer) -> bool {
+        self.deadlines.contains_key(timer)
+    }
+
+    /// The earliest deadline, when the event loop should next wake
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.values().min().cloned()
+    }
+
+    /// Removes the timers whose deadline has passed, earliest first
+    pub fn expired(&mut self, now: Instant) -> Vec<Timer> {
+        let mut expired: Vec<(Timer, Instant)> = self.deadlines.iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(timer, deadline)| (timer.clone(), *deadline))
+            .collect();
+        expired.sort_by_key(|(_, deadline)| *deadline);
+
+        for (timer, _) in expired.iter() {
+            self.deadlines.remove(timer);
+        }
+        expired.into_iter().map(|(timer, _)| timer).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
+    #[test]
+    fn timers_expire_earliest_first() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        timers.schedule(Timer::Autosave, now, Duration::from_secs(3));
+        timers.schedule(Timer::CursorBlink, now, Duration::from_secs(1));
+        timers.schedule(Timer::Message("view-id-1".to_string()), now, Duration::from_secs(2));
+        // Scheduling again moves the deadline rather than adding another
+        timers.schedule(Timer::Autosave, now, Duration::from_secs(10));
+
+        assert_eq!(timers.next_deadline(), Some(now + Duration::from_secs(1)));
+        assert_eq!(timers.expired(now + Duration::from_secs(5)),
+            vec![Timer::CursorBlink, Timer::Message("view-id-1".to_string())]);
+        assert_eq!(timers.next_deadline(), Some(now + Duration::from_secs(10)));
+    }
+}
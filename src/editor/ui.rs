@@ -8,3 +8,7 @@ pub mod primitive;
 pub mod view;
 pub mod status;
 pub mod find_replace;
+pub mod tab_bar;
+
+#[cfg(test)]
+mod snapshot;
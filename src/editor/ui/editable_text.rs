@@ -22,7 +22,7 @@ use crate::rpc::{
     Motion,
     Quantity,
 };
-use crate::render::Renderer;
+use crate::render::display_list::DisplayList;
 use crate::editor::view_resources::Resources;
 
 pub const CURSOR_TEXT: &str = "\u{2588}";
@@ -50,9 +50,8 @@ impl Widget for EditableTextWidget {
         self.dirty
     }
 
-    fn queue_draw(&mut self, renderer: &mut Renderer) {
-        renderer.get_text_context().borrow_mut()
-            .queue_text(&self.section.to_borrowed());
+    fn queue_draw(&mut self, display_list: &mut DisplayList) {
+        display_list.push_section(&self.section.to_borrowed());
 
         if self.focused {
             let mut text = self.text();
@@ -60,15 +59,14 @@ impl Widget for EditableTextWidget {
                 text = text[..self.cursor_pos].to_string();
             }
             let text_width = if text.len() > 0 {
-                renderer.get_text_context().borrow().get_text_width(&text)
+                display_list.text_width(&text)
             } else {
                 0.0
             };
             let text_pos = self.position();
             self.cursor.screen_position = (text_pos[0] + text_width, text_pos[1]);
 
-            renderer.get_text_context().borrow_mut()
-                .queue_text(&self.cursor.to_borrowed());
+            display_list.push_section(&self.cursor.to_borrowed());
         }
     }
 }
@@ -12,7 +12,7 @@ use super::{
 };
 use crate::editor::view_resources::Resources;
 use crate::rpc::Query;
-use crate::render::Renderer;
+use crate::render::display_list::DisplayList;
 
 pub struct FindWidget {
     index: usize,
@@ -36,8 +36,8 @@ impl Widget for FindWidget {
         self.text.dirty()
     }
 
-    fn queue_draw(&mut self, renderer: &mut Renderer) {
-        self.text.queue_draw(renderer);
+    fn queue_draw(&mut self, display_list: &mut DisplayList) {
+        self.text.queue_draw(display_list);
     }
 }
 
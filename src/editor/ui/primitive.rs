@@ -7,8 +7,11 @@ use super::widget::{
     Widget,
 };
 use super::colour::ColourRGBA;
-use crate::render::Renderer;
-use crate::render::primitive::Primitive;
+use crate::render::display_list::{
+    DisplayList,
+    Quad,
+    Rect,
+};
 
 pub struct PrimitiveWidget {
     index: usize,
@@ -88,18 +91,12 @@ impl Widget for PrimitiveWidget {
         self.size
     }
 
-    fn queue_draw(&mut self, renderer: &mut Renderer) {
-        renderer
-            .get_primitive_context().borrow_mut()
-            .queue_primitive(self.index, Primitive {
-                top_left: self.position,
-                bottom_right: [
-                    self.position[0] + self.size[0],
-                    self.position[1] + self.size[1],
-                ],
-                depth: self.depth,
-                colour: self.colour,
-            });
+    fn queue_draw(&mut self, display_list: &mut DisplayList) {
+        display_list.push_quad(Quad {
+            rect: Rect::new(self.position, self.size),
+            depth: self.depth,
+            colour: self.colour,
+        });
     }
 
     fn dirty(&self) -> bool {
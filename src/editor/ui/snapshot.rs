@@ -0,0 +1,286 @@
+//! Snapshot tests for rendered views
+//!
+//! A view is fed recorded xi `update` notifications from `tests/fixtures`, drawn into a display
+//! list at a fixed size and reduced to a character grid with the colours of each cell. The
+//! result is compared with the golden file in `tests/snapshots`, which is only written when
+//! `UPDATE_SNAPSHOTS` is set.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use rpc::{
+    Action,
+    Theme,
+};
+use crate::render::display_list::{
+    DisplayList,
+    GlyphRun,
+    Align,
+};
+use crate::editor::view_commands::EditViewCommands;
+use super::editable_text::CURSOR_TEXT;
+use super::view::EditView;
+use super::widget::Widget;
+
+const FONT_SIZE: f32 = 20.0;
+const VIEW_SIZE: [f32; 2] = [640.0, 240.0];
+
+type Colour = [f32; 4];
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    ch: char,
+    fg: Option<Colour>,
+    bg: Option<Colour>,
+}
+
+/// Display list items snapped to a grid of monospace character cells
+struct CharGrid {
+    cell_size: [f32; 2],
+    columns: usize,
+    cells: Vec<Vec<Cell>>,
+}
+
+impl CharGrid {
+    fn new(display_list: &DisplayList, cell_height: f32, size: [f32; 2]) -> Self {
+        let cell_size = [display_list.text_width("M"), cell_height];
+        let columns = (size[0] / cell_size[0]).ceil() as usize;
+        let rows = (size[1] / cell_size[1]).ceil() as usize;
+        let blank = Cell { ch: ' ', fg: None, bg: None };
+
+        let mut grid = Self {
+            cell_size,
+            columns,
+            cells: vec![vec![blank; columns]; rows],
+        };
+        for layer in display_list.flatten() {
+            for quad in layer.quads.iter() {
+                grid.fill(quad.rect.position, quad.rect.max(), quad.colour);
+            }
+            for run in layer.glyphs.iter() {
+                grid.write(display_list, run);
+            }
+        }
+
+        grid
+    }
+
+    // Cells take the colour of quads covering their centre, which hide any text drawn before
+    fn fill(&mut self, min: [f32; 2], max: [f32; 2], colour: Colour) {
+        let [w, h] = self.cell_size;
+        for (row, cells) in self.cells.iter_mut().enumerate() {
+            let y = (row as f32 + 0.5) * h;
+            if y < min[1] || y >= max[1] {
+                continue;
+            }
+            for (column, cell) in cells.iter_mut().enumerate() {
+                let x = (column as f32 + 0.5) * w;
+                if x >= min[0] && x < max[0] {
+                    *cell = Cell { ch: ' ', fg: None, bg: Some(colour) };
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, display_list: &DisplayList, run: &GlyphRun) {
+        let width: f32 = run.spans.iter()
+            .flat_map(|span| span.text.chars())
+            .map(|ch| self.advance(display_list, ch))
+            .sum();
+        let x0 = match run.h_align {
+            Align::Start => run.position[0],
+            Align::Center => run.position[0] - width / 2.0,
+            Align::End => run.position[0] - width,
+        };
+        let row = (run.position[1] / self.cell_size[1]).round() as usize;
+        if row >= self.cells.len() {
+            return;
+        }
+
+        let mut x = x0;
+        for span in run.spans.iter() {
+            for ch in span.text.chars() {
+                let advance = self.advance(display_list, ch);
+                let column = (x / self.cell_size[0]).round() as usize;
+                if x - x0 + advance > run.bounds[0] + 0.5 || column >= self.columns {
+                    return;
+                }
+                let centre = [(column as f32 + 0.5) * self.cell_size[0], (row as f32 + 0.5) * self.cell_size[1]];
+                let clipped = run.clip.map_or(false, |clip| {
+                    let max = clip.max();
+                    centre[0] < clip.position[0] || centre[0] >= max[0]
+                    || centre[1] < clip.position[1] || centre[1] >= max[1]
+                });
+                if !ch.is_whitespace() && !clipped {
+                    let cell = &mut self.cells[row][column];
+                    cell.ch = ch;
+                    cell.fg = Some(span.colour);
+                }
+                x += advance;
+            }
+        }
+    }
+
+    // The cursor's block glyph is not among the font bounds used for layout, it covers one cell
+    fn advance(&self, display_list: &DisplayList, ch: char) -> f32 {
+        let text = ch.to_string();
+        if text == CURSOR_TEXT {
+            self.cell_size[0]
+        } else {
+            display_list.text_width(&text)
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for cells in self.cells.iter() {
+            let line: String = cells.iter().map(|cell| cell.ch).collect();
+            writeln!(out, "|{}|", line).unwrap();
+        }
+
+        writeln!(out).unwrap();
+        for (row, cells) in self.cells.iter().enumerate() {
+            let mut start = 0;
+            for column in 1..=cells.len() {
+                let same = column < cells.len()
+                    && cells[column].fg == cells[start].fg
+                    && cells[column].bg == cells[start].bg;
+                if same {
+                    continue;
+                }
+                writeln!(out, "{:>2} {:>3}..{:<3} fg {} bg {}",
+                    row, start, column, hex(cells[start].fg), hex(cells[start].bg)).unwrap();
+                start = column;
+            }
+        }
+
+        out
+    }
+}
+
+fn hex(colour: Option<Colour>) -> String {
+    match colour {
+        Some(colour) => colour.iter()
+            .map(|c| format!("{:02x}", (c.max(0.0).min(1.0) * 255.0).round() as u8))
+            .collect::<Vec<_>>()
+            .concat(),
+        None => "--------".to_string(),
+    }
+}
+
+fn fixture(name: &str) -> Value {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    let content = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("unable to read {:?}: {}", path, err));
+
+    serde_json::from_str(&content)
+        .unwrap_or_else(|err| panic!("invalid fixture {:?}: {}", path, err))
+}
+
+fn theme(name: &str) -> Theme {
+    serde_json::from_value(fixture(name)).expect("invalid theme fixture")
+}
+
+/// Builds a focused view at `VIEW_SIZE` from the recorded updates in `updates`
+fn view(updates: &str, theme_name: &str) -> EditView {
+    let mut view = EditView::new(0, FONT_SIZE, Some("main.rs".to_string()));
+    view.poke(EditViewCommands::ThemeChanged(theme(theme_name)));
+    view.poke(EditViewCommands::Resize(VIEW_SIZE));
+    view.poke(EditViewCommands::Position([0.0, 0.0]));
+    view.set_focused(true);
+
+    for update in fixture(updates).as_array().expect("updates must be an array") {
+        let update = serde_json::from_value(update.clone()).expect("invalid update fixture");
+        view.poke(EditViewCommands::ApplyUpdate(update));
+    }
+    // As xi does once a buffer has loaded, the cursor starts on the first line
+    view.poke(EditViewCommands::ScrollTo(0));
+
+    view
+}
+
+fn render(view: &mut EditView) -> String {
+    let mut display_list = DisplayList::new(FONT_SIZE);
+    view.queue_draw(&mut display_list);
+
+    CharGrid::new(&display_list, FONT_SIZE * 1.06, VIEW_SIZE).render()
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{}.txt", name));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).expect("unable to write snapshot");
+        return;
+    }
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(err) => panic!("unable to read snapshot {:?}: {}, rerun with UPDATE_SNAPSHOTS=1 to create it\n\nactual:\n{}",
+            path, err, actual),
+    };
+    if expected != actual {
+        panic!("snapshot {} differs, rerun with UPDATE_SNAPSHOTS=1 to accept\n\nexpected:\n{}\nactual:\n{}",
+            name, expected, actual);
+    }
+}
+
+#[test]
+fn status_bar() {
+    let mut view = view("buffer.json", "theme_dark.json");
+    assert_snapshot("status_bar", &render(&mut view));
+}
+
+#[test]
+fn status_items() {
+    let mut view = view("buffer.json", "theme_dark.json");
+    view.poke(EditViewCommands::StatusItem("branch".to_string(), "master".to_string()));
+    view.poke(EditViewCommands::StatusItem("lint".to_string(), "2 warnings".to_string()));
+    view.poke(EditViewCommands::StatusItem("branch".to_string(), "main".to_string()));
+    view.poke(EditViewCommands::StatusItem("spell".to_string(), "en".to_string()));
+    view.poke(EditViewCommands::RemoveStatusItem("lint".to_string()));
+    assert_snapshot("status_items", &render(&mut view));
+}
+
+#[test]
+fn gutter() {
+    let mut view = view("buffer.json", "theme_dark.json");
+    view.poke(EditViewCommands::Action(Action::ToggleLineNumbers));
+    assert_snapshot("gutter", &render(&mut view));
+}
+
+#[test]
+fn selection() {
+    let mut view = view("selection.json", "theme_dark.json");
+    view.poke(EditViewCommands::ScrollTo(6));
+    assert_snapshot("selection", &render(&mut view));
+}
+
+#[test]
+fn light_theme() {
+    let mut view = view("selection.json", "theme_light.json");
+    view.poke(EditViewCommands::ScrollTo(6));
+    assert_snapshot("light_theme", &render(&mut view));
+}
+
+#[test]
+fn scrolled() {
+    let mut view = view("buffer.json", "theme_dark.json");
+    view.poke(EditViewCommands::Action(Action::ToggleLineNumbers));
+    view.poke(EditViewCommands::ScrollTo(24));
+    assert_snapshot("scrolled", &render(&mut view));
+}
+
+#[test]
+fn unfocused() {
+    let mut view = view("selection.json", "theme_dark.json");
+    view.set_focused(false);
+    assert_snapshot("unfocused", &render(&mut view));
+}
@@ -15,6 +15,7 @@ use glyph_brush::{
 use super::widget::{
     Widget,
     hash_widget,
+    BAR_LAYER,
 };
 use super::primitive::PrimitiveWidget;
 use super::editable_text::EditableTextWidget;
@@ -26,7 +27,10 @@ use rpc::{
     Motion,
     Quantity,
 };
-use crate::render::Renderer;
+use crate::render::display_list::{
+    DisplayList,
+    Rect,
+};
 use crate::editor::view_resources::Resources;
 
 // TODO: Derive from config
@@ -60,6 +64,8 @@ pub struct Status {
     pub line_current: usize,
     pub line_count: usize,
     pub language: Option<String>,
+    /// Plugin status items by key, in the order they were added
+    pub items: Vec<(String, String)>,
 }
 
 impl Hash for StatusWidget {
@@ -90,12 +96,15 @@ impl Widget for StatusWidget {
         self.dirty || self.status_text.dirty()
     }
 
-    fn queue_draw(&mut self, renderer: &mut Renderer) {
+    fn queue_draw(&mut self, display_list: &mut DisplayList) {
+        // Over any line of the view which reaches into the bar
+        display_list.push_layer(BAR_LAYER);
+        display_list.push_clip(Rect::new(self.position, self.size));
 
         // Primitives (Background quads)
-        self.background.queue_draw(renderer);
+        self.background.queue_draw(display_list);
         if self.focused {
-            self.mode_primitive.queue_draw(renderer);
+            self.mode_primitive.queue_draw(display_list);
         }
 
         // Command Widget
@@ -105,30 +114,30 @@ impl Widget for StatusWidget {
         };
         if self.focused {
             if draw_status_text {
-                self.status_text.queue_draw(renderer);
+                self.status_text.queue_draw(display_list);
                 self.set_dirty(false);
             }
         }
 
-        if let ctx = &mut renderer.get_text_context().clone().borrow_mut() {
-            if !self.focused {
-                ctx.queue_text(&self.filename_section.to_borrowed());
-            } else {
-                ctx.queue_text(&self.mode_section.to_borrowed());
-                    
-                if !draw_status_text {
-                   ctx.queue_text(&self.filename_section.to_borrowed());
-                    
-                    let status_width = ctx.get_text_width(
-                        &self.status_section.text[0].text.to_string());
-                    self.status_section.bounds = (status_width + self.scale, self.size[1]);
-                    self.status_section.screen_position = 
-                        (self.size[0] - status_width - self.scale, self.position[1]);
-                    ctx.queue_text(&self.status_section.to_borrowed());    
-                }
+        if !self.focused {
+            display_list.push_section(&self.filename_section.to_borrowed());
+        } else {
+            display_list.push_section(&self.mode_section.to_borrowed());
+
+            if !draw_status_text {
+                display_list.push_section(&self.filename_section.to_borrowed());
 
+                let status_width = display_list.text_width(
+                    &self.status_section.text[0].text.to_string());
+                self.status_section.bounds = (status_width + self.scale, self.size[1]);
+                self.status_section.screen_position =
+                    (self.position[0] + self.size[0] - status_width - self.scale, self.position[1]);
+                display_list.push_section(&self.status_section.to_borrowed());
             }
         }
+
+        display_list.pop_clip();
+        display_list.pop_layer();
     }
 }
 
@@ -265,20 +274,45 @@ impl StatusWidget {
     }
 
     pub fn update_line_status(&mut self, line_num: usize, line_count: usize, language: Option<String>) {
-        let line_percent: usize = if line_count > 0 {
-            ((line_num as f32 / line_count as f32) * 100.0) as usize
+        self.status.line_count = line_count;
+        self.status.line_current = line_num;
+        self.status.language = language;
+
+        self.update_status_section();
+    }
+
+    /// Sets the value of a status item, or removes it given None
+    pub fn set_item(&mut self, key: String, value: Option<String>) {
+        let existing = self.status.items.iter().position(|(item_key, _)| *item_key == key);
+        match (existing, value) {
+            (Some(ix), Some(value)) => self.status.items[ix].1 = value,
+            (None, Some(value)) => self.status.items.push((key, value)),
+            (Some(ix), None) => {
+                self.status.items.remove(ix);
+            },
+            (None, None) => (),
+        }
+
+        self.update_status_section();
+    }
+
+    // Status items are all drawn before the line status, whatever alignment the plugin asked for
+    fn update_status_section(&mut self) {
+        let line_percent: usize = if self.status.line_count > 0 {
+            ((self.status.line_current as f32 / self.status.line_count as f32) * 100.0) as usize
         } else {
             0
         };
 
-        let status_content = format!("{} {}% {}/{}", 
-            language.clone().unwrap_or(String::new()), 
+        let mut status_content = String::new();
+        for (_, value) in self.status.items.iter() {
+            status_content.push_str(value);
+            status_content.push(' ');
+        }
+        status_content.push_str(&format!("{} {}% {}/{}",
+            self.status.language.clone().unwrap_or(String::new()),
             line_percent,
-            line_num, line_count);
-
-        self.status.line_count = line_count;
-        self.status.line_current = line_num;
-        self.status.language = language;
+            self.status.line_current, self.status.line_count));
 
         self.status_section.text[0].text = status_content;
         self.status_section.text[0].scale = PxScale::from(self.scale);
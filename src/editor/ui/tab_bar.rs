@@ -0,0 +1,166 @@
+use std::hash::{
+    Hash,
+    Hasher,
+};
+use glyph_brush::{
+    Section,
+    Layout,
+    Text,
+};
+
+use super::widget::{
+    Widget,
+    hash_widget,
+    BAR_LAYER,
+};
+use super::primitive::PrimitiveWidget;
+use rpc::Theme;
+use crate::render::display_list::{
+    DisplayList,
+    Rect,
+};
+use crate::editor::view_resources::Resources;
+
+#[derive(Hash, Clone, PartialEq)]
+pub struct TabLabel {
+    pub name: String,
+    pub modified: bool,
+}
+
+impl TabLabel {
+    fn text(&self, number: usize) -> String {
+        if self.modified {
+            format!("{} {} +", number, self.name)
+        } else {
+            format!("{} {}", number, self.name)
+        }
+    }
+}
+
+pub struct TabBarWidget {
+    index: usize,
+    position: [f32; 2],
+    size: [f32; 2],
+    dirty: bool,
+    labels: Vec<TabLabel>,
+    active: usize,
+    resources: Resources,
+    background: PrimitiveWidget,
+    active_background: PrimitiveWidget,
+}
+
+impl Hash for TabBarWidget {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_widget(self, state);
+        self.labels.hash(state);
+        self.active.hash(state);
+        self.background.hash(state);
+        self.active_background.hash(state);
+    }
+}
+
+impl Widget for TabBarWidget {
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn position(&self) -> [f32; 2] {
+        self.position
+    }
+
+    fn size(&self) -> [f32; 2] {
+        self.size
+    }
+
+    fn dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn queue_draw(&mut self, display_list: &mut DisplayList) {
+        let pad = self.resources.pad();
+        let scale = self.resources.scale;
+        let mut x = self.position[0];
+
+        // Over views scrolled part way into a line, and labels past the end are cut off
+        display_list.push_layer(BAR_LAYER);
+        display_list.push_clip(Rect::new(self.position, self.size));
+        self.background.queue_draw(display_list);
+
+        for (ix, label) in self.labels.iter().enumerate() {
+            let content = label.text(ix + 1);
+            let width = pad + pad + display_list.text_width(content.as_str());
+
+            if ix == self.active {
+                self.active_background.set_position(x, self.position[1]);
+                self.active_background.set_size([width, self.size[1]]);
+                self.active_background.queue_draw(display_list);
+            }
+
+            let section = Section::default()
+                .add_text(Text::new(content.as_str())
+                    .with_scale(scale)
+                    .with_color(self.resources.fg))
+                .with_layout(Layout::default_single_line())
+                .with_screen_position((x + pad, self.position[1]))
+                .with_bounds((width, self.size[1]));
+            display_list.push_section(&section);
+
+            x += width;
+        }
+        display_list.pop_clip();
+        display_list.pop_layer();
+        self.dirty = false;
+    }
+}
+
+impl TabBarWidget {
+    pub fn new(index: usize, scale: f32) -> Self {
+        let resources = Resources::new(scale);
+        let size = [0.0, resources.line_gap()];
+        let background = PrimitiveWidget::new(0, [0.0, 0.0, 0.2], size, resources.gutter_bg);
+        let active_background = PrimitiveWidget::new(1, [0.0, 0.0, 0.3], size, resources.bg);
+
+        Self {
+            index,
+            position: [0.0, 0.0],
+            size,
+            dirty: true,
+            labels: vec![],
+            active: 0,
+            resources,
+            background,
+            active_background,
+        }
+    }
+
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.position = [x, y];
+        self.background.set_position(x, y);
+        self.dirty = true;
+    }
+
+    pub fn set_width(&mut self, width: f32) {
+        self.size[0] = width;
+        self.background.set_width(width);
+        self.dirty = true;
+    }
+
+    pub fn set_tabs(&mut self, labels: Vec<TabLabel>, active: usize) {
+        if self.labels != labels || self.active != active {
+            self.labels = labels;
+            self.active = active;
+            self.dirty = true;
+        }
+    }
+
+    pub fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    pub fn theme_changed(&mut self, theme: Theme) {
+        self.resources.update_from_theme(theme);
+        self.background.set_colour(self.resources.gutter_bg);
+        self.active_background.set_colour(self.resources.bg);
+        self.dirty = true;
+    }
+}
@@ -9,7 +9,7 @@ use super::widget::{
     hash_widget,
 };
 use super::colour::ColourRGBA;
-use crate::render::Renderer;
+use crate::render::display_list::DisplayList;
 
 use glyph_brush::{
     Section,
@@ -89,6 +89,11 @@ impl TextWidget {
        self.dirty = true;
     }
 
+    pub fn set_bounds(&mut self, width: f32, height: f32) {
+       self.section.bounds = (width, height);
+       self.dirty = true;
+    }
+
     pub fn get_section(&self) -> &OwnedSection {
         &self.section
     }
@@ -121,8 +126,7 @@ impl Widget for TextWidget {
         self.dirty
     }
 
-    fn queue_draw(&mut self, renderer: &mut Renderer) {
-        renderer.get_text_context().borrow_mut()
-            .queue_text(&self.section.to_borrowed());
+    fn queue_draw(&mut self, display_list: &mut DisplayList) {
+        display_list.push_section(&self.section.to_borrowed());
     }
 }
@@ -24,6 +24,9 @@ use glyph_brush::{
 use rpc::{ 
     Action,
     ActionTarget,
+    EditCommand,
+    ModifySelection,
+    MouseAction,
     GestureType,
     SelectionGranularity,
     PluginAction,
@@ -35,15 +38,24 @@ use rpc::{
     Config,
     Theme,
     Style,
+    TabAction,
+    ViewUpdate,
+};
+use crate::render::display_list::{
+    DisplayList,
+    Rect,
 };
-use crate::render::Renderer;
 use crate::editor::{
     plugins::PluginState,
-    linecache::LineCache,
+    linecache::{
+        LineCache,
+        utf8_offset,
+    },
     editor_rpc::Core,
     commands::command_to_actions,
     view_commands::EditViewCommands,
     view_resources::Resources,
+    session::ViewSession,
 };
 use crate::events::{
     EditorEventLoopProxy,
@@ -68,6 +80,12 @@ use super::{
 type Method = String;
 type Params = Value;
 
+// A message for the core, held until the view knows its core and id
+enum Pending {
+    Notification(Method, Params),
+    Edit(EditCommand),
+}
+
 pub struct EditView {
     index: usize,
     size: [f32; 2],
@@ -76,13 +94,19 @@ pub struct EditView {
     focused: bool,
     view_id: Option<String>,
     filepath: Option<String>,
+    name: Option<String>,
+    pristine: bool,
+    read_only: bool,
+    loaded: bool,
+    when_loaded: Vec<EditViewCommands>,
     line_cache: LineCache,
     scroll_offset: f32,
     viewport: Range<usize>,
     core: Weak<Mutex<Core>>,
     event_proxy: Option<EditorEventLoopProxy>,
-    pending: Vec<(Method, Params)>,
+    pending: Vec<Pending>,
     config: Option<Config>,
+    config_overrides: Option<Config>,
     theme: Option<Theme>,
     language: Option<String>,
     resources: Resources,
@@ -93,6 +117,12 @@ pub struct EditView {
     plugins: HashMap<PluginId, PluginState>,
     current_line: usize,
     show_line_numbers: bool,
+    // Hidden for half of each blink
+    cursor_visible: bool,
+    // Set when a message is shown, until the editor has started its timeout
+    new_message: bool,
+    // Where the mouse was last pressed, as a line and column, and what the click selects
+    pointer: Option<(usize, usize, SelectionGranularity)>,
 }
 
 impl Hash for EditView {
@@ -108,6 +138,7 @@ impl Hash for EditView {
         self.find_replace.hash(state);
         self.current_line.hash(state);
         self.show_line_numbers.hash(state);
+        self.cursor_visible.hash(state);
     }
 }
 
@@ -124,45 +155,38 @@ impl Widget for EditView {
         self.size
     }
 
-    fn queue_draw(&mut self, renderer: &mut Renderer) {
-        let text_ctx = renderer.get_text_context().clone();
-
+    fn queue_draw(&mut self, display_list: &mut DisplayList) {
         let line_gap = self.resources.line_gap();
         let pad = self.resources.pad();
-        let drawable_height = self.drawable_text_height();
         let first_line = self.y_to_line(self.position[1]);
-        let last_line = std::cmp::min(self.y_to_line(drawable_height) + 1, self.line_cache.height());
+        let last_line = self.last_visible_line();
         
-        // Ensure our text context is up to date
+        // Ensure text is measured at our font size
         let scale = self.resources.scale;
-        if let text_ctx = &mut text_ctx.borrow_mut() {
-            if scale != text_ctx.get_font_size() {
-                text_ctx.set_font_size(scale);
-            }
+        if scale != display_list.font_size() {
+            display_list.set_font_size(scale);
         }
 
-        // Figure out the maximum width of the line number
-        let gutter_width = if self.show_line_numbers {
-            pad + pad 
-                + text_ctx.borrow().get_text_width(last_line.to_string().clone().as_str())
-        } else {
-            0.0
-        };
+        let gutter_width = self.gutter_width(last_line, display_list);
         let x0 = self.position[0] + pad + gutter_width;
+        let text_width = self.size[0] - (x0 - self.position[0]);
         let mut y = self.position[1] + self.line_to_content_y(first_line) - self.scroll_offset;
 
         // Background & Gutter
-        self.background.queue_draw(renderer);
+        self.background.queue_draw(display_list);
         self.gutter.set_width(gutter_width);
-        self.gutter.queue_draw(renderer);
+        self.gutter.queue_draw(display_list);
 
         // Status Bar
-        let mode_width = pad + pad + text_ctx.borrow()
-            .get_text_width(self.mode().to_string().clone().as_str());
+        let mode_width = pad + pad + display_list
+            .text_width(self.mode().to_string().clone().as_str());
 
         self.status_bar.set_mode_width(mode_width);
         self.status_bar.set_scale(line_gap);
-        self.status_bar.queue_draw(renderer);
+        self.status_bar.queue_draw(display_list);
+
+        // Long lines and selections must not spill into neighbouring views
+        display_list.push_clip(Rect::new(self.position, self.size));
     
         // Selection start index, background = 0, gutter = 1, status_bar = 2, 3, 4
         let mut s_ix = 6;
@@ -180,52 +204,52 @@ impl Widget for EditView {
                             continue;
                         }
                         let sel_content = &line_content[selection.start_col..selection.end_col];
-                        let sel_x0 = text_ctx.borrow().get_text_width(&line_content[..selection.start_col]);
-                        let width = text_ctx.borrow().get_text_width(sel_content);
+                        let sel_x0 = display_list.text_width(&line_content[..selection.start_col]);
+                        let width = display_list.text_width(sel_content);
 
                         let mut selection = PrimitiveWidget::new(
                             s_ix, [x0 + sel_x0, y, 0.2], [width, scale], self.resources.sel);
 
-                        selection.queue_draw(renderer);
+                        selection.queue_draw(display_list);
                         s_ix += 1;
                     }
                 }
 
                 // Line body
                 text_widget.set_position(x0, y);
-                text_widget.queue_draw(renderer);
+                text_widget.set_bounds(text_width, line_gap);
+                text_widget.queue_draw(display_list);
 
                 // Cursors
-                if self.focused && line_num == self.current_line - 1 {
+                if self.focused && self.cursor_visible && line_num + 1 == self.current_line {
                     let cursors = text_widget.get_cursor();
                     for offset in cursors {
                         let section = &text_widget.get_section().to_borrowed();
-                        let pos = text_ctx.borrow_mut()
-                            .get_cursor_position(section, offset); 
+                        let pos = display_list.cursor_position(section, offset);
 
                         let mut offside = create_offside_section(CURSOR_TEXT, self.resources.cursor, scale);
                         offside.screen_position = pos;
-                        text_ctx.borrow_mut()
-                            .queue_text(&offside.to_borrowed());
+                        display_list.push_section(&offside.to_borrowed());
                     }
                 }
 
                 // Line numbers
                 if self.show_line_numbers {
                     let content = (line_num + 1).to_string();
-                    let left_offset = text_ctx.borrow().get_text_width(content.as_str());
+                    let left_offset = display_list.text_width(content.as_str());
 
                     let mut offside = create_offside_section(
                         content.clone().as_str(), self.resources.gutter_fg, scale);
-                    offside.screen_position = (gutter_width - left_offset - pad, y);
-                    text_ctx.borrow_mut()
-                        .queue_text(&offside.to_borrowed());
+                    offside.screen_position = (self.position[0] + gutter_width - left_offset - pad, y);
+                    display_list.push_section(&offside.to_borrowed());
                 }
                 
                 text_widget.set_dirty(true);
             }
             y += line_gap;
         }
+
+        display_list.pop_clip();
     }
 
     fn dirty(&self) -> bool {
@@ -259,6 +283,7 @@ impl EditView {
             line_current: 0,
             line_count: 0,
             language: None,
+            items: vec![],
         };
         let status_bar = StatusWidget::new(2, status, &resources);
         let find_replace = FindWidget::new(3, &resources);
@@ -274,6 +299,7 @@ impl EditView {
             dirty: true,
             view_id: None,
             config: None,
+            config_overrides: None,
             theme: None,
             language: None,
             line_cache: LineCache::new(),
@@ -281,11 +307,19 @@ impl EditView {
             viewport: 0..0,
             current_line: 0,
             show_line_numbers: false,
+            cursor_visible: true,
+            new_message: false,
+            pointer: None,
             core: Default::default(),
             pending: Default::default(),
             event_proxy: None,
             plugins: HashMap::new(),
             filepath: filename,
+            name: None,
+            pristine: true,
+            read_only: false,
+            loaded: false,
+            when_loaded: vec![],
             status_bar,
             find_replace,
             resources,
@@ -303,10 +337,28 @@ impl EditView {
             })
     }
 
-    fn apply_update(&mut self, update: &Value) {
+    fn apply_update(&mut self, update: &ViewUpdate) {
         self.line_cache.apply_update(update);
+        if let Some(pristine) = update.pristine {
+            self.pristine = pristine;
+        }
+        self.status_bar.update_line_status(self.current_line, self.line_cache.height(), self.language.clone());
         self.constrain_scroll();
         self.dirty = true;
+
+        if !self.loaded {
+            self.loaded = true;
+            self.when_loaded(vec![]);
+        }
+    }
+
+    fn when_loaded(&mut self, commands: Vec<EditViewCommands>) {
+        self.when_loaded.extend(commands);
+        if self.loaded {
+            for command in std::mem::replace(&mut self.when_loaded, vec![]) {
+                self.poke(command);
+            }
+        }
     }
 
     fn resize(&mut self, size: [f32; 2]) {
@@ -316,16 +368,16 @@ impl EditView {
         self.size = [size[0], height];
         self.gutter.set_height(height);
         self.background.set_size([size[0], height]);
-        self.status_bar.set_position(self.position[0], height); 
+        self.status_bar.set_position(self.position[0], self.position[1] + height);
         self.dirty = true;
 
         let (w, h) = (size[0], self.drawable_text_height());
-        self.send_edit_cmd("resize", &json!({ "width": w, "height": h }));
+        self.send_edit_cmd(EditCommand::Resize { width: w, height: h });
         self.update_viewport();
     }
     fn set_position(&mut self, x: f32, y: f32) {
         self.position = [x, y];
-        self.status_bar.set_position(x, y + self.size[1] - self.status_bar.size()[1]);
+        self.status_bar.set_position(x, y + self.size[1]);
         self.background.set_position(x, y);
         self.gutter.set_position(x, y);
         self.dirty = true;
@@ -340,76 +392,67 @@ impl EditView {
     }
 
     fn go_to_line(&mut self, line: usize) {
-        self.send_edit_cmd("goto_line", &json!({ "line": line }));
+        self.send_edit_cmd(EditCommand::GotoLine { line });
     }
 
     fn gesture(&mut self, line: usize, col: usize, gesture_type: GestureType) {
-        self.send_edit_cmd("gesture", &json!({
-            "line": line,
-            "col": col,
-            "ty": gesture_type,
-        }));
+        self.send_edit_cmd(EditCommand::Gesture { line, col, ty: gesture_type });
     }
 
     fn send_char(&mut self, ch: char) {
         if ch as u32 >= 0x20 {
-            let params = json!({"chars": ch.to_string()});
-            self.send_edit_cmd("insert", &params);
+            self.send_edit_cmd(EditCommand::Insert { chars: ch.to_string() });
         }
     }
 
     fn send_notification(&mut self, method: &str, params: &Value) {
-        let core = self.core.upgrade();
-        if core.is_some() && self.view_id.is_some() {
-            let core = core.unwrap();
-            if !core.lock().unwrap().send_notification(method, params) {
-                self.pending.push((method.to_owned(), params.clone()));  
-            } else {
-                /*println!("fe->core: {}", json!({
-                    method: params,
-                }));*/
-            }
-        } else {
-            //println!("queueing pending method: {}", method);
-            self.pending.push((method.to_owned(), params.clone()));
+        match (self.core.upgrade(), &self.view_id) {
+            (Some(core), Some(_)) => {
+                core.lock().unwrap().send_notification(method, params);
+            },
+            _ => self.pending.push(Pending::Notification(method.to_owned(), params.clone())),
         }
     }
 
-    fn send_edit_cmd(&mut self, method: &str, params: &Value) {
-        let core = self.core.upgrade();
-        if core.is_some() && self.view_id.is_some() {
-            let view_id = &self.view_id.clone().unwrap();
-            let edit_params = json!({
-                "method": method,
-                "params": params,
-                "view_id": view_id,
-            });
+    fn send_edit_cmd(&mut self, command: EditCommand) {
+        match (self.core.upgrade(), &self.view_id) {
+            (Some(core), Some(view_id)) => {
+                let (method, params) = command.to_method_params();
+                core.lock().unwrap().send_notification("edit", &json!({
+                    "method": method,
+                    "params": params,
+                    "view_id": view_id,
+                }));
+            },
+            _ => self.pending.push(Pending::Edit(command)),
+        }
+    }
 
-            let core = core.unwrap();
-            if !core.lock().unwrap().send_notification("edit", &edit_params) {
-                self.pending.push(("edit".to_string(), edit_params));
-            }
+    // Sends what was queued before the view had its core and id, in the order it was queued
+    fn flush_pending(&mut self) {
+        if self.core.upgrade().is_none() || self.view_id.is_none() {
+            return;
+        }
 
-        } else {
-            //println!("queueing pending method: {}", method);
-            self.pending.push((method.to_owned(), params.clone()));
+        for message in std::mem::replace(&mut self.pending, vec![]) {
+            match message {
+                Pending::Notification(method, params) => self.send_notification(&method, &params),
+                Pending::Edit(command) => self.send_edit_cmd(command),
+            }
         }
     }
-    
-    fn send_action(&mut self, method: &str) {
-        self.send_edit_cmd(method, &json!([]));
+
+    fn set_core(&mut self, core: Weak<Mutex<Core>>) {
+        self.core = core;
+        self.flush_pending();
     }
 
     fn set_view(&mut self, view_id: String) {
         self.view_id = Some(view_id.to_string());
+        self.flush_pending();
+
         self.viewport = 0..0;
         self.update_viewport();
-
-        let pending = std::mem::replace(&mut self.pending, Vec::new());
-        for notification in pending {
-            let (method, params) = notification;
-            self.send_edit_cmd(&method, &params);
-        }
     }
 
     fn save_to_file(&mut self, filename: Option<String>) {
@@ -418,11 +461,21 @@ impl EditView {
         } else {
             self.filepath.clone()
         };
-        if self.view_id.is_some() && filename.is_some() {
+        if self.read_only {
+            self.show_message(String::from("Unable to save, the file was opened read-only"));
+        } else if self.view_id.is_some() && filename.is_some() {
             self.send_notification("save", &json!({
                 "view_id": self.view_id,
                 "file_path": filename,
             }));
+            // The core marks the buffer pristine once it has been written, taken as done here so
+            // that a `:wq` can close the view straight after
+            self.pristine = true;
+            // Unnamed buffers take the name of the file they are first saved to
+            if self.filepath.is_none() {
+                self.filepath = filename.clone();
+                self.status_bar.update_filename(filename);
+            }
         } else {
             error!("Unable to save to file: {:?}", filename); 
         }
@@ -440,6 +493,8 @@ impl EditView {
     }
 
     fn modify_config(&mut self, config: Config) {
+        self.config_overrides.get_or_insert_with(Config::default).merge(config.clone());
+
         let changes = if let Some(self_config) = self.config.clone() {
             self_config.get_json_changes(config)
         } else {
@@ -552,9 +607,10 @@ impl EditView {
             }
         }
     }
-    fn split_view(&self, filename: Option<String>) {
+    // Forwards actions which affect the whole window to the event loop
+    fn send_window_action(&self, action: Action) {
         if let Some(proxy) = &self.event_proxy {
-            match proxy.send_event(EditorEvent::Action(Action::Split(filename))) {
+            match proxy.send_event(EditorEvent::Action(action)) {
                 Ok(_) => (),
                 Err(err) => panic!(err),
             }
@@ -571,10 +627,113 @@ impl EditView {
     pub fn mode(&self) -> Mode {
         self.status_bar.mode()
     }
+    pub fn line_cache(&self) -> &LineCache {
+        &self.line_cache
+    }
+
     pub fn get_filepath(&self) -> Option<String> {
         self.filepath.clone()
     }
 
+    /// The file path, or the name given to a buffer without a file such as `[stdin]`
+    pub fn name(&self) -> Option<String> {
+        self.filepath.clone().or(self.name.clone())
+    }
+
+    fn set_name(&mut self, name: String) {
+        if self.filepath.is_none() {
+            self.status_bar.update_filename(Some(name.clone()));
+            self.dirty = true;
+        }
+        self.name = Some(name);
+    }
+
+    fn show_message(&mut self, message: String) {
+        self.status_bar.set_text(&message);
+        self.status_bar.set_dirty(true);
+        self.dirty = true;
+        self.new_message = true;
+    }
+
+    fn set_status_item(&mut self, key: String, value: Option<String>) {
+        self.status_bar.set_item(key, value);
+        self.status_bar.set_dirty(true);
+        self.dirty = true;
+    }
+
+    /// Whether a message has been shown since this was last asked
+    pub fn take_new_message(&mut self) -> bool {
+        std::mem::replace(&mut self.new_message, false)
+    }
+
+    // Only the status bar is redrawn, and not while it holds a command or motion being typed
+    fn clear_message(&mut self) {
+        match self.mode() {
+            Mode::Command | Mode::Motion => (),
+            _ => {
+                self.status_bar.set_text("");
+                self.status_bar.set_dirty(true);
+            },
+        }
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        if self.cursor_visible != visible {
+            self.cursor_visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Whether the buffer has changes which can be saved to its file
+    pub fn unsaved(&self) -> bool {
+        !self.pristine && !self.read_only && self.filepath.is_some() && self.view_id.is_some()
+    }
+
+    fn insert_text(&mut self, text: String) {
+        if text.len() > 0 {
+            self.send_edit_cmd(EditCommand::Insert { chars: text });
+        }
+    }
+
+    pub fn session(&self) -> ViewSession {
+        let line = self.current_line.saturating_sub(1);
+        // The line cache keeps cursors in utf-16 units, gestures take utf-8 offsets
+        let col = self.line_cache.get_line(line)
+            .and_then(|l| l.cursor().first().map(|&col| utf8_offset(l.text(), col)))
+            .unwrap_or(0);
+
+        ViewSession {
+            file_path: self.filepath.clone(),
+            line,
+            col,
+            scroll_offset: self.scroll_offset,
+            config: self.config_overrides.clone(),
+        }
+    }
+
+    fn restore(&mut self, session: ViewSession) {
+        if let Some(config) = session.config {
+            self.modify_config(config);
+        }
+        self.gesture(session.line, session.col, GestureType::Select {
+            granularity: SelectionGranularity::Point,
+            multi: false,
+        });
+        self.scroll_offset = session.scroll_offset;
+        self.constrain_scroll();
+        self.update_viewport();
+        self.dirty = true;
+    }
+
+    /// False when the buffer has unsaved changes
+    pub fn is_pristine(&self) -> bool {
+        self.pristine
+    }
+
     fn mode_selection_granularity(&self) -> SelectionGranularity {
         match self.mode() {
             Mode::SelectBlock => SelectionGranularity::Point,
@@ -595,19 +754,81 @@ impl EditView {
         }
     }
 
+    // Motions are an optional count followed by the motion keys, ie. `12gg` or `3gt`
     fn execute_motion(&mut self) -> Vec<Action> {
         let motion_text = self.status_bar.get_text();
-        self.status_bar.set_text("");
-        self.set_mode(Mode::Normal);
+        let count_len = motion_text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(motion_text.len());
+        let (count, keys) = motion_text.split_at(count_len);
+        let count = count.parse::<usize>().ok();
 
         let mut actions: Vec<Action> = vec!();
-        if let Ok(number) = motion_text.parse::<usize>() {
-            actions.push(Action::Motion((Motion::Middle, Some(Quantity::Line(number)))));
+        match keys {
+            // Wait for the rest of the motion
+            "g" => return actions,
+            "gg" | "G" => {
+                actions.push(match (keys, count) {
+                    ("G", None) => Action::Motion((Motion::Last, Some(Quantity::Line(0)))),
+                    (_, line) => Action::Motion((Motion::First, Some(Quantity::Line(line.unwrap_or(1))))),
+                });
+                // Both land at the start of the line
+                actions.push(Action::Motion((Motion::First, None)));
+            },
+            "gt" => actions.push(Action::Tab(match count {
+                Some(number) => TabAction::Goto(number),
+                None => TabAction::Next,
+            })),
+            "gT" => for _ in 0..count.unwrap_or(1) {
+                actions.push(Action::Tab(TabAction::Previous));
+            },
+            "" => if let Some(number) = count {
+                actions.push(Action::Motion((Motion::Middle, Some(Quantity::Line(number)))));
+            },
+            _ => (),
         }
 
+        self.status_bar.set_text("");
+        self.set_mode(Mode::Normal);
+
         actions
     }
 
+    fn search(&mut self, pattern: String) {
+        self.send_edit_cmd(EditCommand::Find {
+            chars: pattern,
+            case_sensitive: false,
+            regex: true,
+            whole_words: false,
+        });
+        self.send_edit_cmd(EditCommand::FindNext {
+            wrap_around: true,
+            allow_same: true,
+            modify_selection: ModifySelection::Set,
+        });
+    }
+
+    fn substitute(&mut self, pattern: String, replacement: String, case_sensitive: bool) {
+        self.send_edit_cmd(EditCommand::Find {
+            chars: pattern,
+            case_sensitive,
+            regex: true,
+            whole_words: false,
+        });
+        self.send_edit_cmd(EditCommand::Replace {
+            chars: replacement,
+            preserve_case: false,
+        });
+        self.send_edit_cmd(EditCommand::ReplaceAll);
+    }
+
+    fn run_command(&mut self, command_text: String) {
+        command_to_actions(self, command_text).into_iter()
+            .filter(|a| match a { Action::Execute => false, _ => true })
+            .for_each(|a| {
+                self.poke(EditViewCommands::Action(a));
+            });
+    }
+
     pub fn execute_command(&mut self) -> Vec<Action> {
         let command_text = self.status_bar.get_text();
         self.status_bar.set_text("");
@@ -657,27 +878,44 @@ impl EditView {
 
         match action {
             Action::Open(filename) => self.open_file(filename),
-            Action::Split(filename) => self.split_view(filename),
+            Action::Split(filename) => self.send_window_action(
+                Action::Split(filename.or(self.filepath.clone()))),
+            Action::VerticalSplit(filename) => self.send_window_action(
+                Action::VerticalSplit(filename.or(self.filepath.clone()))),
+            Action::Window(window_action) => self.send_window_action(Action::Window(window_action)),
+            Action::Tab(tab_action) => self.send_window_action(Action::Tab(tab_action)),
             Action::Save(filename) => self.save_to_file(filename),
-            Action::InsertChar(ch) => self.send_char(ch),
+            Action::SaveSession(filename) => self.send_window_action(Action::SaveSession(filename)),
+            Action::InsertChar(ch) => match self.mode() {
+                Mode::Motion => return self.status_bar.poke(Box::new(Action::InsertChar(ch))),
+                _ => self.send_char(ch),
+            },
             Action::SetMode(mode) => self.set_mode(mode),
             Action::SetTheme(theme) => self.set_theme(theme.as_str()),
             Action::SetLanguage(language) => self.set_language(language.as_str()),
+            Action::Search(pattern) => self.search(pattern),
+            Action::Substitute((pattern, replacement, case_sensitive)) =>
+                self.substitute(pattern, replacement, case_sensitive),
+            Action::QuitAll => self.send_window_action(Action::QuitAll),
+            Action::ForceQuitAll => self.send_window_action(Action::ForceQuitAll),
+            Action::RestartCore => self.send_window_action(Action::RestartCore),
             Action::Plugin(plugin_action) => self.handle_plugin_action(plugin_action),
-            Action::Close => self.close_view(),
+            Action::Close if !self.pristine =>
+                self.show_message(String::from("No write since last change, add ! to override")),
+            Action::Close | Action::ForceClose => self.close_view(),
             Action::ToggleLineNumbers => self.show_line_numbers(!self.show_line_numbers),
-            Action::Undo => self.send_action("undo"),
-            Action::Redo => self.send_action("redo"),
-            Action::ClearSelection => self.send_action("collapse_selections"),
-            Action::SingleSelection => self.send_action("cancel_operation"),
-            Action::NewLine => self.send_action("insert_newline"),
-            Action::Cut => self.send_action("yank"),
-            Action::Copy => self.send_action("copy"),
-            Action::Paste => self.send_action("paste"),
-            Action::Indent => self.send_action("indent"),
-            Action::Outdent => self.send_action("outdent"),
-            Action::InsertTab => self.send_action("insert_tab"),
-            Action::DuplicateLine => self.send_action("duplicate_line"),
+            Action::Undo => self.send_edit_cmd(EditCommand::Undo),
+            Action::Redo => self.send_edit_cmd(EditCommand::Redo),
+            Action::ClearSelection => self.send_edit_cmd(EditCommand::CollapseSelections),
+            Action::SingleSelection => self.send_edit_cmd(EditCommand::CancelOperation),
+            Action::NewLine => self.send_edit_cmd(EditCommand::InsertNewline),
+            Action::Cut => self.send_edit_cmd(EditCommand::Yank),
+            Action::Copy => self.send_edit_cmd(EditCommand::Copy),
+            Action::Paste => self.send_edit_cmd(EditCommand::Paste),
+            Action::Indent => self.send_edit_cmd(EditCommand::Indent),
+            Action::Outdent => self.send_edit_cmd(EditCommand::Outdent),
+            Action::InsertTab => self.send_edit_cmd(EditCommand::InsertTab),
+            Action::DuplicateLine => self.send_edit_cmd(EditCommand::DuplicateLine),
             Action::IncreaseFontSize => self.increase_font_size(),
             Action::DecreaseFontSize => self.decrease_font_size(),
             Action::Execute => match self.mode() {
@@ -699,13 +937,13 @@ impl EditView {
             },
             Action::Motion((motion, quantity)) => match self.determine_action_quantity(quantity) {
                 Quantity::Number(n) => for _ in 0..n { match motion {
-                    Motion::Up => self.send_action("move_up"),
-                    Motion::Down => self.send_action("move_down"),
-                    Motion::Left => self.send_action("move_left"),
-                    Motion::Right => self.send_action("move_right"),
-                    Motion::First => self.send_action("move_to_left_end_of_line"),
-                    Motion::FirstOccupied => self.send_action("move_to_left_end_of_line"), // TODO: inaccurate
-                    Motion::Last => self.send_action("move_to_right_end_of_line"),
+                    Motion::Up => self.send_edit_cmd(EditCommand::MoveUp),
+                    Motion::Down => self.send_edit_cmd(EditCommand::MoveDown),
+                    Motion::Left => self.send_edit_cmd(EditCommand::MoveLeft),
+                    Motion::Right => self.send_edit_cmd(EditCommand::MoveRight),
+                    Motion::First => self.send_edit_cmd(EditCommand::MoveToLeftEndOfLine),
+                    Motion::FirstOccupied => self.send_edit_cmd(EditCommand::MoveToLeftEndOfLine), // TODO: inaccurate
+                    Motion::Last => self.send_edit_cmd(EditCommand::MoveToRightEndOfLine),
                     Motion::High => self.go_to_line(self.viewport.start),
                     Motion::Low => self.go_to_line(self.viewport.end),
                     Motion::Middle => {
@@ -719,17 +957,17 @@ impl EditView {
                     _ => return false,
                 } },
                 Quantity::Page(n) => for _ in 0..n { match motion {
-                    Motion::Up => self.send_action("scroll_page_up"),
-                    Motion::Down => self.send_action("scroll_page_down"),
+                    Motion::Up => self.send_edit_cmd(EditCommand::ScrollPageUp),
+                    Motion::Down => self.send_edit_cmd(EditCommand::ScrollPageDown),
                     _ => return false,
                 } },
                 Quantity::Word(n) => for _ in 0..n { match motion {
-                    Motion::Left => self.send_action("move_word_left"),
-                    Motion::Right => self.send_action("move_word_right"),
+                    Motion::Left => self.send_edit_cmd(EditCommand::MoveWordLeft),
+                    Motion::Right => self.send_edit_cmd(EditCommand::MoveWordRight),
                     _ => return false,
                 } },
                 Quantity::Line(n) => match motion {
-                    Motion::First => self.go_to_line(0),
+                    Motion::First => self.go_to_line(n.saturating_sub(1)),
                     Motion::Last => self.go_to_line(self.line_cache.height()),
                     Motion::High => self.go_to_line(self.viewport.start),
                     Motion::Low => self.go_to_line(self.viewport.end),
@@ -746,27 +984,27 @@ impl EditView {
                 _ => return false,
             },
             Action::Select((motion, quantity)) => match self.determine_action_quantity(quantity) {
-                Quantity::All => self.send_action("select_all"),
+                Quantity::All => self.send_edit_cmd(EditCommand::SelectAll),
                 Quantity::Line(n) => {
                     let last = if self.line_cache.height() > self.current_line + n {
                         self.current_line + n
                     } else {
                         self.line_cache.height()
                     };
-                    self.send_action("move_to_left_end_of_line");
-                    self.send_action("move_to_right_end_of_line_and_modify_selection");
+                    self.send_edit_cmd(EditCommand::MoveToLeftEndOfLine);
+                    self.send_edit_cmd(EditCommand::MoveToRightEndOfLineAndModifySelection);
                     for _ in self.current_line..last {
-                        self.send_action("move_down_and_modify_selection");
+                        self.send_edit_cmd(EditCommand::MoveDownAndModifySelection);
                     }
                 },
                 Quantity::Number(n) => for _ in 0..n { match motion {
-                    Motion::Up => self.send_action("move_up_and_modify_selection"),
-                    Motion::Down => self.send_action("move_down_and_modify_selection"),
-                    Motion::Left => self.send_action("move_left_and_modify_selection"),
-                    Motion::Right => self.send_action("move_right_and_modify_selection"),
-                    Motion::First => self.send_action("move_to_left_end_of_line_and_modify_selection"),
-                    Motion::FirstOccupied => self.send_action("move_to_left_end_of_line_and_modify_selection"),
-                    Motion::Last => self.send_action("move_to_right_end_of_line_and_modify_selection"),
+                    Motion::Up => self.send_edit_cmd(EditCommand::MoveUpAndModifySelection),
+                    Motion::Down => self.send_edit_cmd(EditCommand::MoveDownAndModifySelection),
+                    Motion::Left => self.send_edit_cmd(EditCommand::MoveLeftAndModifySelection),
+                    Motion::Right => self.send_edit_cmd(EditCommand::MoveRightAndModifySelection),
+                    Motion::First => self.send_edit_cmd(EditCommand::MoveToLeftEndOfLineAndModifySelection),
+                    Motion::FirstOccupied => self.send_edit_cmd(EditCommand::MoveToLeftEndOfLineAndModifySelection),
+                    Motion::Last => self.send_edit_cmd(EditCommand::MoveToRightEndOfLineAndModifySelection),
                     Motion::High => self.gesture(self.viewport.start, 0, GestureType::SelectExtend {
                         granularity: self.mode_selection_granularity(),
                     }),
@@ -781,8 +1019,8 @@ impl EditView {
                     _ => return false,
                 } },
                 Quantity::Word(n) => for _ in 0 ..n { match motion {
-                    Motion::Left => self.send_action("move_word_left_and_modify_selection"),
-                    Motion::Right => self.send_action("move_word_right_and_modify_selection"),
+                    Motion::Left => self.send_edit_cmd(EditCommand::MoveWordLeftAndModifySelection),
+                    Motion::Right => self.send_edit_cmd(EditCommand::MoveWordRightAndModifySelection),
                     _ => return false,
                 } },
                 _ => return false,
@@ -790,15 +1028,15 @@ impl EditView {
             Action::Delete((motion, quantity)) => match motion {
                 Motion::Left => match self.determine_action_quantity(quantity) {
                     Quantity::Word(n) => for _ in 0..n {
-                        self.send_action("delete_word_backward");
+                        self.send_edit_cmd(EditCommand::DeleteWordBackward);
                     },
-                    _ => self.send_action("delete_backward"),
+                    _ => self.send_edit_cmd(EditCommand::DeleteBackward),
                 },
                 Motion::Right => match self.determine_action_quantity(quantity) {
                     Quantity::Word(n) => for _ in 0..n {
-                        self.send_action("delete_word_forward");
+                        self.send_edit_cmd(EditCommand::DeleteWordForward);
                     },
-                    _ => self.send_action("delete_forward"),
+                    _ => self.send_edit_cmd(EditCommand::DeleteForward),
                 },
                 Motion::Up => {
                     self.handle_action(Action::Motion((Motion::Up, None)));
@@ -812,24 +1050,24 @@ impl EditView {
                     self.handle_action(Action::Select((Motion::Last, None)));
                     self.handle_action(Action::Delete((Motion::Left, Some(Quantity::Number(2)))));
                 },
-                Motion::First => self.send_action("delete_to_beginning_of_line"),
+                Motion::First => self.send_edit_cmd(EditCommand::DeleteToBeginningOfLine),
                 Motion::Last => {
-                    self.send_action("move_to_right_end_of_line_and_modify_selection");
-                    self.send_action("delete_backward");
+                    self.send_edit_cmd(EditCommand::MoveToRightEndOfLineAndModifySelection);
+                    self.send_edit_cmd(EditCommand::DeleteBackward);
                 },
                 Motion::High => {
                     self.gesture(self.viewport.start, 0, GestureType::Select {
                         granularity: self.mode_selection_granularity(),
                         multi: false,
                     });
-                    self.send_action("delete_backward");
+                    self.send_edit_cmd(EditCommand::DeleteBackward);
                 },
                 Motion::Low => {
                     self.gesture(self.viewport.end, 0, GestureType::Select {
                         granularity: self.mode_selection_granularity(),
                         multi: false,
                     });
-                    self.send_action("delete_backward");
+                    self.send_edit_cmd(EditCommand::DeleteBackward);
                 },
                 Motion::Middle => {
                     self.gesture(
@@ -838,15 +1076,28 @@ impl EditView {
                             granularity: self.mode_selection_granularity(),
                             multi: false,
                     });
-                    self.send_action("delete_backward");
+                    self.send_edit_cmd(EditCommand::DeleteBackward);
                 },
                 _ => (),
             },
             Action::AddCursor(motion) => match motion {
-                Motion::Up => self.send_action("add_selection_above"),
-                Motion::Down => self.send_action("add_selection_below"),
+                Motion::Up => self.send_edit_cmd(EditCommand::AddSelectionAbove),
+                Motion::Down => self.send_edit_cmd(EditCommand::AddSelectionBelow),
                 _ => (),
             },
+            Action::Mouse(mouse_action) => {
+                let (line, col, granularity) = match self.pointer.take() {
+                    Some(pointer) => pointer,
+                    None => return false,
+                };
+                let gesture = match (mouse_action, granularity) {
+                    (MouseAction::Select, SelectionGranularity::Point) => GestureType::PointSelect,
+                    (MouseAction::Select, granularity) => GestureType::Select { granularity, multi: false },
+                    (MouseAction::SelectExtend, granularity) => GestureType::SelectExtend { granularity },
+                    (MouseAction::AddCursor, granularity) => GestureType::Select { granularity, multi: true },
+                };
+                self.gesture(line, col, gesture);
+            },
             _ => return false,
         }
 
@@ -856,7 +1107,7 @@ impl EditView {
     pub fn poke(&mut self, command: EditViewCommands) -> bool {
         match command {
             EditViewCommands::ViewId(view_id) => self.set_view(view_id),
-            EditViewCommands::Core(core) => self.core = core.clone(),
+            EditViewCommands::Core(core) => self.set_core(core),
             EditViewCommands::Proxy(event_proxy) => self.event_proxy = Some(event_proxy),
             EditViewCommands::ApplyUpdate(update) => self.apply_update(&update),
             EditViewCommands::ScrollTo(line) => self.scroll_to(line),
@@ -870,6 +1121,18 @@ impl EditView {
             EditViewCommands::PluginChanged(plugin) => self.plugin_changed(plugin),
             EditViewCommands::PluginStopped(plugin_id) => self.plugin_stopped(plugin_id),
             EditViewCommands::Queries(queries) => self.queries_changed(queries),
+            EditViewCommands::Command(command_text) => self.run_command(command_text),
+            EditViewCommands::Name(name) => self.set_name(name),
+            EditViewCommands::InsertText(text) => self.insert_text(text),
+            EditViewCommands::Restore(session) => self.restore(session),
+            EditViewCommands::ReadOnly(read_only) => self.read_only = read_only,
+            EditViewCommands::Message(message) => self.show_message(message),
+            EditViewCommands::ClearMessage => self.clear_message(),
+            EditViewCommands::StatusItem(key, value) => self.set_status_item(key, Some(value)),
+            EditViewCommands::RemoveStatusItem(key) => self.set_status_item(key, None),
+            EditViewCommands::CursorVisible(visible) => self.set_cursor_visible(visible),
+            EditViewCommands::Pointer(line, col, granularity) => self.pointer = Some((line, col, granularity)),
+            EditViewCommands::WhenLoaded(commands) => self.when_loaded(commands),
             EditViewCommands::Action(action) => return self.handle_action(action),
         }
 
@@ -903,6 +1166,89 @@ impl EditView {
         }
     }
 
+    // The line after the last on screen
+    fn last_visible_line(&self) -> usize {
+        let drawable_height = self.drawable_text_height();
+        std::cmp::min(self.y_to_line(self.position[1] + drawable_height) + 1, self.line_cache.height())
+    }
+
+    // Wide enough for the largest line number on screen
+    fn gutter_width(&self, last_line: usize, display_list: &DisplayList) -> f32 {
+        if self.show_line_numbers {
+            let pad = self.resources.pad();
+            pad + pad + display_list.text_width(last_line.to_string().as_str())
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether a point in the window is over the view or its status bar
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        let bottom = self.position[1] + self.size[1] + self.status_bar.size()[1];
+        point[0] >= self.position[0] && point[0] < self.position[0] + self.size[0]
+            && point[1] >= self.position[1] && point[1] < bottom
+    }
+
+    /// The line and column of the text under a point in the window, measured with the glyphs
+    /// the view is drawn with. None over the status bar or before the buffer has loaded.
+    pub fn hit_test(&self, point: [f32; 2], display_list: &mut DisplayList) -> Option<(usize, usize)> {
+        let height = self.line_cache.height();
+        if height == 0 || !self.contains(point) || point[1] >= self.position[1] + self.size[1] {
+            return None;
+        }
+        let line = std::cmp::min(self.y_to_line(point[1]), height - 1);
+
+        Some((line, self.column_at(line, point[0], display_list)))
+    }
+
+    /// Extends the selection being made with the mouse to a point in the window. Past the top or
+    /// bottom of the text it scrolls a line further that way, returns true while it scrolls.
+    pub fn drag(&mut self, point: [f32; 2], display_list: &mut DisplayList) -> bool {
+        let height = self.line_cache.height();
+        if height == 0 {
+            return false;
+        }
+        let top = self.position[1];
+        let bottom = top + self.drawable_text_height();
+        let first = self.y_to_line(top);
+        let last = std::cmp::min(self.y_to_line(bottom - 1.0), height - 1);
+
+        let (line, scrolling) = if point[1] < top && first > 0 {
+            (first - 1, true)
+        } else if point[1] >= bottom && last + 1 < height {
+            (last + 1, true)
+        } else {
+            let y = point[1].max(top).min(bottom - 1.0);
+            (std::cmp::min(self.y_to_line(y), height - 1), false)
+        };
+        let col = self.column_at(line, point[0], display_list);
+        self.gesture(line, col, GestureType::Drag);
+
+        if scrolling {
+            self.scroll_to(line);
+            self.update_viewport();
+        }
+        scrolling
+    }
+
+    // The column of a line nearest to a window x, before the text start it is the first column
+    fn column_at(&self, line: usize, x: f32, display_list: &mut DisplayList) -> usize {
+        let scale = self.resources.scale;
+        if scale != display_list.font_size() {
+            display_list.set_font_size(scale);
+        }
+        let x0 = self.position[0] + self.resources.pad()
+            + self.gutter_width(self.last_visible_line(), display_list);
+
+        match self.line_cache.get_line(line) {
+            Some(text_line) => {
+                let text = text_line.text().trim_end_matches(|ch: char| ch == '\n' || ch == '\r');
+                display_list.offset_at(text, x - x0)
+            },
+            None => 0,
+        }
+    }
+
     fn y_to_line(&self, y: f32) -> usize {
         let pad = self.resources.pad();
         let mut line = (y + self.scroll_offset - pad - self.position[1]) / self.resources.line_gap();
@@ -920,13 +1266,13 @@ impl EditView {
         if viewport != self.viewport {
             self.viewport = viewport;
             self.status_bar.update_line_status(self.current_line, self.line_cache.height(), self.language.clone());
-            self.send_edit_cmd("scroll", &json!([first_line, last_line]));
+            self.send_edit_cmd(EditCommand::Scroll(first_line, last_line));
         }
     }
 
     #[inline]
     fn line_to_content_y(&self, line_num: usize) -> f32 {
-        self.position[0] + self.resources.pad() + (line_num as f32) * self.resources.line_gap()
+        self.resources.pad() + (line_num as f32) * self.resources.line_gap()
     }
 
     pub fn scroll_to(&mut self, line_num: usize) {
@@ -949,3 +1295,199 @@ impl EditView {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use serde_json::{
+        json,
+        Value,
+    };
+    use rpc::{
+        Action,
+        Mode,
+    };
+
+    use crate::editor::App;
+    use crate::editor::fake_core::FakeCore;
+    use crate::editor::view_commands::EditViewCommands;
+    use crate::editor::tests::{
+        SCREEN_SIZE,
+        FONT_SIZE,
+        start,
+        attach_view,
+        poke,
+        notify,
+        settle,
+        take_sent,
+        line_texts,
+        update,
+        edit,
+        modified,
+    };
+
+    #[test]
+    fn insert_sends_edit() {
+        let (fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        take_sent(&fake, &app);
+
+        poke(&mut app, "view-id-1", EditViewCommands::Action(Action::InsertChar('a')));
+        poke(&mut app, "view-id-1", EditViewCommands::Action(Action::Undo));
+
+        assert_eq!(take_sent(&fake, &app), vec![
+            edit("view-id-1", "insert", json!({ "chars": "a" })),
+            edit("view-id-1", "undo", json!([])),
+        ]);
+    }
+
+    #[test]
+    fn resize_reports_viewport() {
+        let (fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        take_sent(&fake, &app);
+
+        poke(&mut app, "view-id-1", EditViewCommands::Resize(SCREEN_SIZE));
+
+        let methods: Vec<Value> = take_sent(&fake, &app).iter()
+            .map(|message| message["params"]["method"].clone())
+            .collect();
+        assert_eq!(methods, vec![json!("resize"), json!("scroll")]);
+    }
+
+    #[test]
+    fn edit_round_trip() {
+        let (fake, mut app, events) = start();
+        attach_view(&mut app, "view-id-1");
+        notify(&mut app, "update", update("view-id-1", &[""]));
+        fake.reply_to("insert", vec![json!({
+            "method": "update",
+            "params": update("view-id-1", &["a"]),
+        })]);
+
+        poke(&mut app, "view-id-1", EditViewCommands::Action(Action::InsertChar('a')));
+
+        settle(&mut app, &events);
+        assert_eq!(line_texts(&app, "view-id-1"), vec!["a\n"]);
+    }
+
+    #[test]
+    fn fast_typing_keeps_every_key() {
+        let (fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        take_sent(&fake, &app);
+
+        // Updates arriving while typing used to make sends fail on the contended core lock
+        let text: String = (0..200).map(|ix| (b'a' + (ix % 26) as u8) as char).collect();
+        for (ix, ch) in text.char_indices() {
+            fake.notify("update", update("view-id-1", &[&text[..ix]]));
+            poke(&mut app, "view-id-1", EditViewCommands::Action(Action::InsertChar(ch)));
+        }
+
+        let typed: String = take_sent(&fake, &app).iter()
+            .filter(|message| message["params"]["method"] == json!("insert"))
+            .map(|message| message["params"]["params"]["chars"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(typed, text);
+    }
+
+    #[test]
+    fn pending_edits_are_sent_with_the_view_id() {
+        let (fake, app, _events) = start();
+        let mut view = EditView::new(0, FONT_SIZE, None);
+        view.poke(EditViewCommands::Core(Arc::downgrade(&app.core)));
+        view.poke(EditViewCommands::Action(Action::InsertChar('a')));
+        view.poke(EditViewCommands::Action(Action::Undo));
+        assert!(take_sent(&fake, &app).is_empty());
+
+        view.poke(EditViewCommands::ViewId("view-id-1".to_string()));
+
+        let sent = take_sent(&fake, &app);
+        assert_eq!(sent[..2], [
+            edit("view-id-1", "insert", json!({ "chars": "a" })),
+            edit("view-id-1", "undo", json!([])),
+        ]);
+    }
+
+    #[test]
+    fn sessions_save_the_cursor_as_a_utf8_offset() {
+        let (_fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        notify(&mut app, "update", json!({
+            "view_id": "view-id-1",
+            "update": {
+                "ops": [{ "op": "ins", "n": 1, "lines": [{ "text": "héllo wörld\n", "cursor": [7] }] }],
+            },
+        }));
+        notify(&mut app, "scroll_to", json!({ "view_id": "view-id-1", "line": 0, "col": 7 }));
+
+        let session = app.state.views["view-id-1"].session();
+        assert_eq!((session.line, session.col), (0, 7));
+    }
+
+    #[test]
+    fn closing_a_view_with_changes_needs_a_bang() {
+        let (fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        notify(&mut app, "update", modified("view-id-1", &["changed"]));
+        take_sent(&fake, &app);
+
+        app.run_command("q".to_string());
+        assert!(take_sent(&fake, &app).is_empty());
+        assert!(app.state.views.get_mut("view-id-1").unwrap().take_new_message());
+
+        app.run_command("q!".to_string());
+        let sent = take_sent(&fake, &app);
+        assert_eq!(sent[0], json!({ "method": "close_view", "params": { "view_id": "view-id-1" } }));
+    }
+
+    #[test]
+    fn write_and_quit_closes_a_view_with_changes() {
+        let (fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        notify(&mut app, "update", modified("view-id-1", &["changed"]));
+        take_sent(&fake, &app);
+
+        app.run_command("wq /tmp/saved.txt".to_string());
+        let methods: Vec<Value> = take_sent(&fake, &app).into_iter().map(|message| message["method"].clone()).collect();
+        assert_eq!(methods, vec![json!("save"), json!("close_view")]);
+    }
+
+    #[test]
+    fn read_only_views_refuse_to_save_on_the_status_bar() {
+        let (fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        poke(&mut app, "view-id-1", EditViewCommands::ReadOnly(true));
+        take_sent(&fake, &app);
+
+        app.run_command("w /tmp/read-only.txt".to_string());
+        assert!(take_sent(&fake, &app).is_empty());
+        assert!(app.state.views.get_mut("view-id-1").unwrap().take_new_message());
+    }
+
+    // Types a key sequence in motion mode, returning the edits it sent to the core
+    fn motion_edits(fake: &FakeCore, app: &mut App, keys: &str) -> Vec<Value> {
+        take_sent(fake, app);
+        poke(app, "view-id-1", EditViewCommands::Action(Action::SetMode(Mode::Motion)));
+        for key in keys.chars() {
+            poke(app, "view-id-1", EditViewCommands::Action(Action::InsertChar(key)));
+        }
+        poke(app, "view-id-1", EditViewCommands::Action(Action::Execute));
+
+        take_sent(fake, app).into_iter().map(|message| message["params"]["method"].clone()).collect()
+    }
+
+    #[test]
+    fn only_bound_motions_move_to_the_start_of_the_line() {
+        let (fake, mut app, _events) = start();
+        attach_view(&mut app, "view-id-1");
+        notify(&mut app, "update", update("view-id-1", &["one", "two", "three"]));
+
+        assert_eq!(motion_edits(&fake, &mut app, "gg").last(), Some(&json!("move_to_left_end_of_line")));
+        assert_eq!(motion_edits(&fake, &mut app, "G").last(), Some(&json!("move_to_left_end_of_line")));
+        assert_eq!(motion_edits(&fake, &mut app, "zg"), Vec::<Value>::new());
+        assert_eq!(motion_edits(&fake, &mut app, "xG"), Vec::<Value>::new());
+    }
+}
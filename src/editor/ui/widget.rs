@@ -4,13 +4,16 @@ use std::hash::{
 };
 use std::collections::hash_map::DefaultHasher;
 
-use crate::render::Renderer;
+use crate::render::display_list::DisplayList;
+
+/// Display list layer of the bars drawn over the text of views, as the status and tab bars are
+pub const BAR_LAYER: u32 = 1;
 
 pub trait Widget: Hash {
     fn index(&self) -> usize;
     fn position(&self) -> [f32; 2];
     fn size(&self) -> [f32; 2];
-    fn queue_draw(&mut self, renderer: &mut Renderer);
+    fn queue_draw(&mut self, display_list: &mut DisplayList);
     fn dirty(&self) -> bool;
 }
 
@@ -3,9 +3,9 @@ use std::sync::{
     Weak,
 };
 use std::collections::HashMap;
-use serde_json::Value;
 
 use super::plugins::PluginState;
+use super::session::ViewSession;
 use crate::events::EditorEventLoopProxy;
 use rpc::{
     PluginId,
@@ -14,12 +14,14 @@ use rpc::{
     Theme,
     Style,
     Query,
+    SelectionGranularity,
+    ViewUpdate,
 };
 use crate::editor::editor_rpc::Core;
 
 pub enum EditViewCommands {
     ViewId(String),
-    ApplyUpdate(Value),
+    ApplyUpdate(ViewUpdate),
     ScrollTo(usize),
     Core(Weak<Mutex<Core>>),
     Proxy(EditorEventLoopProxy),
@@ -34,5 +36,23 @@ pub enum EditViewCommands {
     PluginStopped(PluginId),
     Queries(Vec<Query>),
     Action(Action),
+    Command(String),
+    Name(String),
+    InsertText(String),
+    Restore(ViewSession),
+    ReadOnly(bool),
+    /// Shown in the status bar until the next command, or until it times out
+    Message(String),
+    ClearMessage,
+    /// A plugin's status item by key, shown in the status bar until it is removed
+    StatusItem(String, String),
+    RemoveStatusItem(String),
+    /// Shows or hides the cursor as it blinks
+    CursorVisible(bool),
+    /// The line and column under the mouse and what the click selects, for the mouse actions
+    /// which follow
+    Pointer(usize, usize, SelectionGranularity),
+    /// Commands applied once the first update for the buffer has arrived
+    WhenLoaded(Vec<EditViewCommands>),
 }
 
@@ -2,18 +2,40 @@ pub mod state;
 pub mod mapper_winit;
 pub mod binding;
 
+use std::sync::mpsc::Sender;
+
 use winit::event_loop::{
     EventLoop,
     EventLoopProxy,
+    EventLoopClosed,
 };
 use rpc::Action;
 
-#[derive(Clone)]
+use crate::editor::CoreEvent;
+
+/// Everything the event loop handles besides window events, it owns the app so input and
+/// whatever the core sends are applied in the order they arrive
 pub enum EditorEvent {
     Action(Action),
+    Core(CoreEvent),
 }
 
-pub type EditorEventLoopProxy = EventLoopProxy<EditorEvent>;
+/// Sends events to the event loop, which is a winit window or a channel when headless
+#[derive(Clone)]
+pub enum EditorEventLoopProxy {
+    Window(EventLoopProxy<EditorEvent>),
+    Headless(Sender<EditorEvent>),
+}
+
+impl EditorEventLoopProxy {
+    pub fn send_event(&self, event: EditorEvent) -> Result<(), EventLoopClosed<EditorEvent>> {
+        match self {
+            EditorEventLoopProxy::Window(proxy) => proxy.send_event(event),
+            EditorEventLoopProxy::Headless(sender) => sender.send(event)
+                .map_err(|err| EventLoopClosed(err.0)),
+        }
+    }
+}
 
 pub fn create_event_loop() -> EventLoop<EditorEvent> {
     EventLoop::<EditorEvent>::with_user_event()
@@ -3,7 +3,10 @@ use rpc::{
     ActionTarget,
     Motion,
     Mode,
+    MouseAction,
     Quantity,
+    SplitDirection,
+    WindowAction,
 };
 
 use winit::event::{
@@ -69,6 +72,7 @@ macro_rules! motion {
 }
 macro_rules! shift { () => {{ ModifiersState::SHIFT }}; }
 macro_rules! ctrl { () => {{ ModifiersState::CTRL }}; }
+macro_rules! alt { () => {{ ModifiersState::ALT }}; }
 macro_rules! mods_empty { () => {{ ModifiersState::empty() }}; }
 
 macro_rules! key_binding {
@@ -203,6 +207,10 @@ macro_rules! bind_motions {
 pub fn default_mouse_bindings() -> Vec<MouseBinding> {
     bindings!(
         MouseBinding;
+        MouseButton::Left; Action::Mouse(MouseAction::Select);
+        MouseButton::Left, shift!(); Action::Mouse(MouseAction::SelectExtend);
+        MouseButton::Left, ctrl!(); Action::Mouse(MouseAction::AddCursor);
+        MouseButton::Left, alt!(); Action::Mouse(MouseAction::AddCursor);
     )
 }
 pub fn bind_numeric(mode: Mode, target: ActionTarget) -> Vec<KeyBinding> {
@@ -339,13 +347,48 @@ fn motion_mode_bindings() -> Vec<KeyBinding> {
     bindings.extend(bind_numeric(Mode::Motion, ActionTarget::StatusBar));
 
     bindings.extend(bindings!(KeyBinding;
-        G, +Mode::Motion; Action::Motion((Motion::First, Some(Quantity::Line(0)))), motion!(Motion First), Action::SetMode(Mode::Normal);
+        G, +Mode::Motion, @ActionTarget::FocusedView; Action::InsertChar('g'), Action::Execute;
         G, shift!(), +Mode::Motion, @ActionTarget::FocusedView; Action::InsertChar('G'), Action::Execute;
+        T, +Mode::Motion, @ActionTarget::FocusedView; Action::InsertChar('t'), Action::Execute;
+        T, shift!(), +Mode::Motion, @ActionTarget::FocusedView; Action::InsertChar('T'), Action::Execute;
         Return, +Mode::Normal; Action::Execute;
     ));
 
     bindings
 }
+#[inline]
+fn window_mode_bindings() -> Vec<KeyBinding> {
+    bindings!(KeyBinding;
+        W, ctrl!(), +Mode::Normal; Action::SetMode(Mode::Window);
+
+        Equals, shift!(), +Mode::Window; Action::Window(WindowAction::Resize((SplitDirection::Horizontal, 1))), Action::SetMode(Mode::Normal);
+        Minus,  +Mode::Window; Action::Window(WindowAction::Resize((SplitDirection::Horizontal, -1))), Action::SetMode(Mode::Normal);
+        Period, shift!(), +Mode::Window; Action::Window(WindowAction::Resize((SplitDirection::Vertical, 1))), Action::SetMode(Mode::Normal);
+        Comma,  shift!(), +Mode::Window; Action::Window(WindowAction::Resize((SplitDirection::Vertical, -1))), Action::SetMode(Mode::Normal);
+        Equals, +Mode::Window; Action::Window(WindowAction::Equalize), Action::SetMode(Mode::Normal);
+        O,      +Mode::Window; Action::Window(WindowAction::Only), Action::SetMode(Mode::Normal);
+
+        H,      +Mode::Window; Action::Window(WindowAction::Focus(Motion::Left)), Action::SetMode(Mode::Normal);
+        J,      +Mode::Window; Action::Window(WindowAction::Focus(Motion::Down)), Action::SetMode(Mode::Normal);
+        K,      +Mode::Window; Action::Window(WindowAction::Focus(Motion::Up)), Action::SetMode(Mode::Normal);
+        L,      +Mode::Window; Action::Window(WindowAction::Focus(Motion::Right)), Action::SetMode(Mode::Normal);
+        Left,   +Mode::Window; Action::Window(WindowAction::Focus(Motion::Left)), Action::SetMode(Mode::Normal);
+        Down,   +Mode::Window; Action::Window(WindowAction::Focus(Motion::Down)), Action::SetMode(Mode::Normal);
+        Up,     +Mode::Window; Action::Window(WindowAction::Focus(Motion::Up)), Action::SetMode(Mode::Normal);
+        Right,  +Mode::Window; Action::Window(WindowAction::Focus(Motion::Right)), Action::SetMode(Mode::Normal);
+        W,      +Mode::Window; Action::Window(WindowAction::FocusNext), Action::SetMode(Mode::Normal);
+        W,      ctrl!(), +Mode::Window; Action::Window(WindowAction::FocusNext), Action::SetMode(Mode::Normal);
+        W,      shift!(), +Mode::Window; Action::Window(WindowAction::FocusPrevious), Action::SetMode(Mode::Normal);
+        X,      +Mode::Window; Action::Window(WindowAction::Exchange), Action::SetMode(Mode::Normal);
+        R,      +Mode::Window; Action::Window(WindowAction::Rotate(Motion::Down)), Action::SetMode(Mode::Normal);
+        R,      shift!(), +Mode::Window; Action::Window(WindowAction::Rotate(Motion::Up)), Action::SetMode(Mode::Normal);
+        C,      +Mode::Window; Action::SetMode(Mode::Normal), Action::Close;
+        Q,      +Mode::Window; Action::SetMode(Mode::Normal), Action::Close;
+        S,      +Mode::Window; Action::Split(None), Action::SetMode(Mode::Normal);
+        V,      +Mode::Window; Action::VerticalSplit(None), Action::SetMode(Mode::Normal);
+    )
+}
+
 #[inline]
 fn replace_mode_bindings() -> Vec<KeyBinding> {
     let mut replace_once_bindings = bind_alpha_numeric(Mode::ReplaceOnce, ActionTarget::FocusedView);
@@ -370,7 +413,7 @@ pub fn default_key_bindings() -> Vec<KeyBinding> {
 
         I, +Mode::Normal; Action::SetMode(Mode::Insert);
         V, +Mode::Normal; Action::SetMode(Mode::Select);
-        G, +Mode::Normal; Action::SetMode(Mode::Motion);
+        G, +Mode::Normal; Action::SetMode(Mode::Motion), Action::InsertChar('g');
         G, shift!(), +Mode::Normal; Action::Motion((Motion::Last, Some(Quantity::Line(0)))), motion!(Motion First), Action::SetMode(Mode::Normal);
         V, ctrl!(), +Mode::Normal; Action::SetMode(Mode::SelectBlock);
         V, shift!(), +Mode::Normal; Action::SetMode(Mode::SelectLine);
@@ -403,6 +446,7 @@ pub fn default_key_bindings() -> Vec<KeyBinding> {
     bindings.extend(command_mode_bindings());
     bindings.extend(motion_mode_bindings());
     bindings.extend(replace_mode_bindings());
+    bindings.extend(window_mode_bindings());
 
     bindings.extend(bindings!(KeyBinding;
         F1; Action::SetTheme(String::from("Solarized (dark)"));
@@ -1,4 +1,9 @@
 
+use std::time::{
+    Duration,
+    Instant,
+};
+
 use winit::event::{
     ModifiersState,
     WindowEvent,
@@ -9,10 +14,23 @@ use winit::event::{
 use super::mapper_winit::map_scancode;
 use super::binding::Key;
 
+// Presses closer together than this in time and distance count as a double or triple click
+const MULTI_CLICK_TIME: Duration = Duration::from_millis(500);
+const MULTI_CLICK_DISTANCE: f32 = 4.0;
+
 #[derive(Debug)]
 pub struct MouseState {
     pub state: Option<ElementState>,
     pub button: Option<MouseButton>,
+    /// The button pressed by the latest event, None once any other event arrives
+    pub pressed: Option<MouseButton>,
+    /// 1 for a single click, 2 for a double and 3 for a triple, a fourth starts again at 1
+    pub clicks: usize,
+    /// Whether the latest event moved the pointer while a button was held
+    pub dragged: bool,
+    /// The pointer in window pixels from the top left
+    pub cursor: (f32, f32),
+    last_press: Option<(MouseButton, Instant, (f32, f32))>,
     pub line_scroll: (f32, f32),
     pub position: (f32, f32),
     pub delta: (f32, f32),
@@ -23,6 +41,11 @@ impl Default for MouseState {
         Self {
             state: None,
             button: None,
+            pressed: None,
+            clicks: 0,
+            dragged: false,
+            cursor: (0.0, 0.0),
+            last_press: None,
             line_scroll: (0.0, 0.0),
             position: (0.0, 0.0),
             delta: (0.0, 0.0),
@@ -33,12 +56,18 @@ impl Default for MouseState {
 impl MouseState {
     // update_via_window_event returns true when the state has changed
     pub fn update_via_window_event(&mut self, input: WindowEvent, window_dimensions: [f32; 2]) -> bool {
+        self.pressed = None;
+        self.dragged = false;
         match input {
             WindowEvent::MouseInput { state, button, .. } => {
                 let change = self.state != Some(state) || self.button != Some(button);
 
                 self.state = Some(state);
                 self.button = Some(button);
+                if state == ElementState::Pressed {
+                    self.press(button, Instant::now());
+                    return true;
+                }
                 change
             },
             WindowEvent::MouseWheel { delta: MouseScrollDelta::LineDelta(x, y), .. } => {
@@ -49,21 +78,38 @@ impl MouseState {
             WindowEvent::CursorMoved { position, .. } => {
                 let (half_x, half_y) = (window_dimensions[0] / 2.0, window_dimensions[1] / 2.0); 
                 let (x, y) = (position.x as f32, position.y as f32);
+                self.cursor = (x, y);
                 let x = half_x - x;
                 let y = half_y - y;
 
                 self.delta.0 = self.position.0 - x;
                 self.delta.1 = self.position.1 - y;
 
-                let _change = self.position.0 != x || self.position.1 != y;
+                let change = self.position.0 != x || self.position.1 != y;
                 self.position.0 = x;
                 self.position.1 = y;
-                //change
-                false
+                // Only drags are of interest, plain moves change nothing
+                self.dragged = change && self.state == Some(ElementState::Pressed);
+                self.dragged
             },
             _ => false,
         }
     }
+
+    /// Records a press at the pointer, counting it as part of a double or triple click when it
+    /// follows the last press of the button closely enough
+    pub fn press(&mut self, button: MouseButton, now: Instant) {
+        let repeated = match self.last_press {
+            Some((last_button, time, (x, y))) => last_button == button
+                && now.duration_since(time) < MULTI_CLICK_TIME
+                && (self.cursor.0 - x).abs() <= MULTI_CLICK_DISTANCE
+                && (self.cursor.1 - y).abs() <= MULTI_CLICK_DISTANCE,
+            None => false,
+        };
+        self.clicks = if repeated { self.clicks % 3 + 1 } else { 1 };
+        self.last_press = Some((button, now, self.cursor));
+        self.pressed = Some(button);
+    }
 }
 
 #[derive(Debug)]
@@ -141,4 +187,33 @@ impl InputState {
     }
 }
 
-
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
+    use winit::event::MouseButton;
+
+    #[test]
+    fn presses_close_together_count_as_one_click() {
+        let now = Instant::now();
+        let mut mouse = MouseState::default();
+        let clicks: Vec<usize> = (0..4)
+            .map(|ix| {
+                mouse.press(MouseButton::Left, now + Duration::from_millis(100 * ix));
+                mouse.clicks
+            })
+            .collect();
+        assert_eq!(clicks, vec![1, 2, 3, 1]);
+
+        mouse.press(MouseButton::Left, now + Duration::from_secs(2));
+        assert_eq!(mouse.clicks, 1);
+        mouse.cursor = (100.0, 0.0);
+        mouse.press(MouseButton::Left, now + Duration::from_millis(2100));
+        assert_eq!(mouse.clicks, 1);
+    }
+}
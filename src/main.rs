@@ -2,6 +2,7 @@
 extern crate glyph_brush;
 extern crate serde;
 extern crate serde_json;
+extern crate regex;
 extern crate winit;
 extern crate xi_core_lib;
 extern crate xi_rpc;
@@ -11,12 +12,94 @@ extern crate rpc;
 
 mod events;
 mod editor;
+mod cli;
+mod remote;
 
 use std::env;
+use std::io::{
+    self,
+    Read,
+};
+use std::process;
 
-fn main() {
-    let filename = env::args().nth(1);
-    
-    editor::run("Editor", filename);    
+// Sends the files and commands to the editor listening on the socket
+fn run_remote(options: &cli::Options) -> Result<(), String> {
+    let socket = options.socket.clone().unwrap_or_else(remote::default_socket_path);
+    let wait = options.remote == Some(cli::Remote::Wait);
+
+    let mut requests: Vec<remote::Request> = options.files.iter()
+        .map(|arg| {
+            let (file, line) = remote::parse_file_line(arg);
+            remote::Request::Open { file, line, wait }
+        })
+        .collect();
+    requests.extend(options.remote_commands.iter().cloned().map(remote::Request::Command));
+
+    remote::send(&socket, requests)
+}
+
+// Strips the `file:line` suffixes given for a remote open, the first file's line is used as if
+// given with `+line`. Standard input, `-`, is left as it is.
+fn open_locally(options: &mut cli::Options) {
+    let files: Vec<(String, Option<usize>)> = options.files.iter()
+        .map(|arg| match arg.as_str() {
+            cli::STDIN_FILE => (arg.clone(), None),
+            _ => remote::parse_file_line(arg),
+        })
+        .collect();
+
+    if options.position.is_none() {
+        options.position = files.first()
+            .and_then(|(_, line)| *line)
+            .map(cli::StartPosition::Line);
+    }
+    options.files = files.into_iter().map(|(file, _)| file).collect();
 }
 
+// Standard input is read once here, the editor never blocks on it while running
+fn read_stdin(options: &mut cli::Options) {
+    if !options.files.iter().any(|file| file == cli::STDIN_FILE) {
+        return;
+    }
+
+    let mut text = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut text) {
+        eprintln!("unable to read standard input: {}", err);
+        process::exit(1);
+    }
+    options.stdin = Some(text);
+}
+
+fn main() {
+    match cli::parse(env::args().skip(1)) {
+        Ok(cli::Command::Run(mut options)) => {
+            if options.is_remote() {
+                match run_remote(&options) {
+                    Ok(_) => return,
+                    // Without a running editor, files are opened here instead
+                    Err(err) if options.remote_commands.is_empty() => {
+                        eprintln!("{}", err);
+                        options.remote = None;
+                        open_locally(&mut options);
+                    },
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    },
+                }
+            }
+            read_stdin(&mut options);
+            if options.headless {
+                editor::run_headless(options);
+            } else {
+                editor::run("Editor", options);
+            }
+        },
+        Ok(cli::Command::Help) => println!("{}", cli::usage()),
+        Ok(cli::Command::Version) => println!("{}", cli::version()),
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::usage());
+            process::exit(2);
+        },
+    }
+}
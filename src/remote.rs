@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{
+    BufRead,
+    BufReader,
+    Write,
+};
+use std::os::unix::net::{
+    UnixListener,
+    UnixStream,
+};
+use std::path::PathBuf;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::thread;
+
+use serde::{
+    Serialize,
+    Deserialize,
+};
+use rpc::Action;
+
+use crate::events::{
+    EditorEvent,
+    EditorEventLoopProxy,
+};
+
+/// Messages sent by `--remote` clients to a running editor, one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    Open {
+        file: String,
+        line: Option<usize>,
+        wait: bool,
+    },
+    Command(String),
+}
+
+/// Sent back to clients waiting on a buffer once it has been closed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Closed(String),
+}
+
+pub fn default_socket_path() -> String {
+    let dir = dirs::runtime_dir().unwrap_or(env::temp_dir());
+    let user = env::var("USER").unwrap_or_default();
+
+    dir.join(format!("editor-{}.sock", user)).to_string_lossy().to_string()
+}
+
+/// Splits `file:line` into an absolute path and the line, the file need not exist yet
+pub fn parse_file_line(arg: &str) -> (String, Option<usize>) {
+    let (file, line) = match arg.rfind(':') {
+        Some(ix) => match arg[ix + 1..].parse::<usize>() {
+            Ok(line) => (&arg[..ix], Some(line)),
+            Err(_) => (arg, None),
+        },
+        None => (arg, None),
+    };
+
+    let path = fs::canonicalize(file)
+        .or_else(|_| env::current_dir().map(|dir| dir.join(file)))
+        .unwrap_or(PathBuf::from(file));
+
+    (path.to_string_lossy().to_string(), line)
+}
+
+/// Accepts remote clients on a Unix domain socket, translating their requests into actions
+#[derive(Clone)]
+pub struct RemoteServer {
+    path: String,
+    waiting: Arc<Mutex<Waiting>>,
+}
+
+/// Clients waiting on a file, first for its view to open and then for that view to close
+#[derive(Default)]
+struct Waiting {
+    opening: HashMap<String, Vec<UnixStream>>,
+    // The file each view was opened for, with the clients waiting on it
+    views: HashMap<String, (String, Vec<UnixStream>)>,
+}
+
+impl RemoteServer {
+    pub fn listen(path: &str, proxy: EditorEventLoopProxy) -> Result<Self, String> {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("an editor is already listening on {}", path));
+        }
+        // Nothing is listening, so the socket was left behind by an editor which has exited
+        let _ = fs::remove_file(path);
+
+        let listener = UnixListener::bind(path)
+            .map_err(|err| format!("unable to listen on {}: {}", path, err))?;
+        let server = Self {
+            path: path.to_string(),
+            waiting: Default::default(),
+        };
+
+        let accept_server = server.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let server = accept_server.clone();
+                        let proxy = proxy.clone();
+                        thread::spawn(move || server.handle_client(stream, proxy));
+                    },
+                    Err(err) => println!("remote connection failed: {}", err),
+                }
+            }
+        });
+
+        Ok(server)
+    }
+
+    fn handle_client(&self, stream: UnixStream, proxy: EditorEventLoopProxy) {
+        let reader = match stream.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(err) => {
+                println!("unable to read remote connection: {}", err);
+                return;
+            },
+        };
+
+        for line in reader.lines() {
+            let request = match line.map(|l| serde_json::from_str::<Request>(&l)) {
+                Ok(Ok(request)) => request,
+                Ok(Err(err)) => {
+                    println!("invalid remote request: {}", err);
+                    continue;
+                },
+                Err(_) => break,
+            };
+
+            let action = match request {
+                Request::Open { file, line, wait } => {
+                    if wait {
+                        if let Ok(stream) = stream.try_clone() {
+                            self.waiting.lock().unwrap().opening
+                                .entry(file.clone())
+                                .or_insert(vec![])
+                                .push(stream);
+                        }
+                    }
+                    match line {
+                        Some(line) => Action::OpenAt((file, line)),
+                        None => Action::Open(Some(file)),
+                    }
+                },
+                Request::Command(command) => Action::ExCommand(command),
+            };
+
+            if proxy.send_event(EditorEvent::Action(action)).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Removes the socket, waiting clients see the connection close
+    pub fn shutdown(&self) {
+        let _ = fs::remove_file(&self.path);
+        *self.waiting.lock().unwrap() = Waiting::default();
+    }
+
+    /// Moves clients waiting on the file over to the view opened for it. When the file could not
+    /// be opened, ie. there is no view, they are told it has closed.
+    pub fn view_opened(&self, file: &str, view_id: Option<&str>) {
+        let mut waiting = self.waiting.lock().unwrap();
+        let streams = match waiting.opening.remove(file) {
+            Some(streams) => streams,
+            None => return,
+        };
+
+        match view_id {
+            Some(view_id) => {
+                waiting.views.entry(view_id.to_string())
+                    .or_insert_with(|| (file.to_string(), vec![]))
+                    .1.extend(streams);
+            },
+            None => send_closed(file, streams),
+        }
+    }
+
+    /// Lets clients waiting on views which are no longer open know their file has been closed
+    pub fn views_closed(&self, open_views: &[String]) {
+        let mut waiting = self.waiting.lock().unwrap();
+        let closed: Vec<String> = waiting.views.keys()
+            .filter(|view_id| !open_views.contains(view_id))
+            .cloned()
+            .collect();
+
+        for view_id in closed {
+            if let Some((file, streams)) = waiting.views.remove(&view_id) {
+                send_closed(&file, streams);
+            }
+        }
+    }
+}
+
+fn send_closed(file: &str, streams: Vec<UnixStream>) {
+    let response = serde_json::to_string(&Response::Closed(file.to_string())).unwrap();
+    for mut stream in streams {
+        let _ = writeln!(stream, "{}", response);
+    }
+}
+
+/// Sends requests to a running editor, when waiting this blocks until every file opened with
+/// `wait` has been closed or the editor exits
+pub fn send(path: &str, requests: Vec<Request>) -> Result<(), String> {
+    let mut stream = UnixStream::connect(path)
+        .map_err(|err| format!("unable to connect to an editor on {}: {}", path, err))?;
+
+    let mut waiting: Vec<String> = vec![];
+    for request in requests.iter() {
+        if let Request::Open { file, wait: true, .. } = request {
+            waiting.push(file.clone());
+        }
+        let message = serde_json::to_string(request).unwrap();
+        writeln!(stream, "{}", message)
+            .map_err(|err| format!("unable to send to editor: {}", err))?;
+    }
+
+    let mut lines = BufReader::new(stream).lines();
+    while !waiting.is_empty() {
+        match lines.next().map(|line| line.map(|l| serde_json::from_str::<Response>(&l))) {
+            Some(Ok(Ok(Response::Closed(file)))) => waiting.retain(|f| *f != file),
+            Some(Ok(Err(err))) => println!("invalid response from editor: {}", err),
+            _ => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_line_arguments() {
+        let cwd = env::current_dir().unwrap();
+        let absolute = |file: &str| cwd.join(file).to_string_lossy().to_string();
+
+        assert_eq!(parse_file_line("missing.txt:12"), (absolute("missing.txt"), Some(12)));
+        assert_eq!(parse_file_line("missing.txt"), (absolute("missing.txt"), None));
+        assert_eq!(parse_file_line("missing.txt:"), (absolute("missing.txt:"), None));
+        assert_eq!(parse_file_line("c:notes.txt"), (absolute("c:notes.txt"), None));
+        assert_eq!(parse_file_line("/tmp/missing.txt:3"), ("/tmp/missing.txt".to_string(), Some(3)));
+    }
+}